@@ -6,13 +6,15 @@ use serde_json::json;
 use std::fs;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::Semaphore;
 use tracing::{debug, error, info};
 use uuid::Uuid;
 
 use crate::config::Config;
+use crate::delivery::DeliveryPreferences;
 use crate::image::ImageGenerator;
-use crate::session::SessionManager;
+use crate::session::{SessionManager, SessionStatus, TokenUsage, Turn};
 
 pub use self::models::*;
 
@@ -22,6 +24,7 @@ pub struct APIClient {
     pub config: Config,
     pub session_manager: SessionManager,
     pub image_generator: ImageGenerator,
+    pub delivery_preferences: DeliveryPreferences,
     semaphore: Arc<Semaphore>,
 }
 
@@ -47,6 +50,9 @@ impl APIClient {
         // 创建图像生成器
         let image_generator = ImageGenerator::new(&config)?;
 
+        // 加载回答发送方式偏好
+        let delivery_preferences = DeliveryPreferences::new(&config);
+
         // 并发请求限流
         let semaphore = Arc::new(Semaphore::new(config.api_concurrency_limit));
 
@@ -55,11 +61,13 @@ impl APIClient {
             config,
             session_manager,
             image_generator,
+            delivery_preferences,
             semaphore,
         })
     }
 
     /// 从FastGPT获取响应
+    #[allow(clippy::too_many_arguments)]
     pub async fn get_chat_response<Fut>(
         &self,
         // 可选的对话 ID，不传则不使用上下文
@@ -170,7 +178,7 @@ impl APIClient {
                             if let Some(choices) = resp_val.get("choices") {
                                 debug!("找到choices: {:?}", choices);
                                 if let Some(choices_array) = choices.as_array() {
-                                    if let Some(first_choice) = choices_array.get(0) {
+                                    if let Some(first_choice) = choices_array.first() {
                                         debug!("找到first_choice: {:?}", first_choice);
                                         if let Some(delta) = first_choice.get("delta") {
                                             debug!("找到delta: {:?}", delta);
@@ -252,8 +260,10 @@ impl APIClient {
         debug!("成功解析API响应，内容长度: {} 字符", content.len());
         debug!("获取到的内容: {}", content);
 
+        let usage = usage_from_events(&events);
         Ok(ChatResponse {
             content,
+            usage,
             raw_response: ChatCompletionResponse {
                 // 补全默认字段
                 id: "".to_string(),
@@ -280,7 +290,7 @@ impl APIClient {
 
         // 保存用户输入
         self.session_manager
-            .save_user_input(&session_id, prompt)
+            .append_turn(&session_id, Turn::user(prompt, &[], None))
             .await?;
 
         // 构建 messages 并从 API 获取响应
@@ -288,7 +298,8 @@ impl APIClient {
             role: "user".into(),
             content: json!([{"type": "text", "text": prompt}]),
         }];
-        let chat_response = self
+        let started = Instant::now();
+        let result = self
             .get_chat_response(
                 Some(session_id.clone()),
                 None,
@@ -298,11 +309,35 @@ impl APIClient {
                 None,
                 |_, _| async { Ok(()) },
             )
-            .await?;
+            .await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+        let app_name = self.config.fastgpt_app_name.clone();
+        let chat_response = match result {
+            Ok(chat_response) => {
+                self.session_manager.update_meta(&session_id, |meta| {
+                    meta.app_name = app_name;
+                    meta.chat_id = Some(session_id.clone());
+                    meta.usage = chat_response.usage;
+                    meta.latency_ms = Some(latency_ms);
+                    meta.status = SessionStatus::Ok;
+                })?;
+                chat_response
+            }
+            Err(e) => {
+                self.session_manager.update_meta(&session_id, |meta| {
+                    meta.app_name = app_name;
+                    meta.chat_id = Some(session_id.clone());
+                    meta.latency_ms = Some(latency_ms);
+                    meta.status = SessionStatus::Error;
+                    meta.error = Some(e.to_string());
+                })?;
+                return Err(e);
+            }
+        };
 
         // 保存响应内容
         self.session_manager
-            .save_response_markdown(&session_id, &chat_response.content)
+            .append_turn(&session_id, Turn::assistant(&chat_response.content))
             .await?;
 
         // 生成图片
@@ -346,6 +381,8 @@ impl APIClient {
 
 pub struct ChatResponse {
     pub content: String,
+    /// 根据 flowResponses 事件统计的token用量，非详细模式下为空
+    pub usage: Option<TokenUsage>,
     #[allow(dead_code)]
     pub raw_response: ChatCompletionResponse,
     /// 流式事件 (event, data)
@@ -361,6 +398,36 @@ pub struct ImageResponse {
     pub markdown_text: String,
 }
 
+/// 汇总 flowResponses 事件中各节点的token用量
+///
+/// FastGPT 在详细模式下返回每个节点的 `inputTokens` 与 `outputTokens`，
+/// 旧版本只返回合计的 `tokens`。没有任何用量信息时返回 `None`。
+fn usage_from_events(events: &[(String, String)]) -> Option<TokenUsage> {
+    let mut usage = TokenUsage::default();
+    let mut found = false;
+    for (event, data) in events {
+        if event != "flowResponses" {
+            continue;
+        }
+        let Ok(serde_json::Value::Array(nodes)) = serde_json::from_str(data) else {
+            continue;
+        };
+        for node in &nodes {
+            let input = node.get("inputTokens").and_then(|v| v.as_u64());
+            let output = node.get("outputTokens").and_then(|v| v.as_u64());
+            let tokens = node.get("tokens").and_then(|v| v.as_u64());
+            if input.is_none() && output.is_none() && tokens.is_none() {
+                continue;
+            }
+            found = true;
+            usage.prompt_tokens += input.unwrap_or(0);
+            usage.completion_tokens += output.unwrap_or(0);
+            usage.total_tokens += tokens.unwrap_or(input.unwrap_or(0) + output.unwrap_or(0));
+        }
+    }
+    found.then_some(usage)
+}
+
 // 安全截断UTF-8字符串的辅助函数
 fn safe_truncate(s: &str, max_len: usize) -> String {
     if s.chars().count() <= max_len {
@@ -375,7 +442,7 @@ fn safe_truncate(s: &str, max_len: usize) -> String {
 
 #[cfg(test)]
 mod tests {
-    use super::safe_truncate;
+    use super::{safe_truncate, usage_from_events};
 
     #[test]
     fn truncate_short() {
@@ -394,4 +461,23 @@ mod tests {
         let t = safe_truncate(&s, 10);
         assert_eq!(t.chars().count(), 10);
     }
+
+    #[test]
+    fn usage_sums_flow_responses() {
+        let events = vec![
+            ("answer".to_string(), "{}".to_string()),
+            (
+                "flowResponses".to_string(),
+                r#"[{"moduleName":"AI 对话","inputTokens":100,"outputTokens":50},
+                    {"moduleName":"问题分类","tokens":30},
+                    {"moduleName":"知识库搜索"}]"#
+                    .to_string(),
+            ),
+        ];
+        let usage = usage_from_events(&events).unwrap();
+        assert_eq!(usage.prompt_tokens, 100);
+        assert_eq!(usage.completion_tokens, 50);
+        assert_eq!(usage.total_tokens, 180);
+        assert!(usage_from_events(&events[..1]).is_none());
+    }
 }
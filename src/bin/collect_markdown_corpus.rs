@@ -0,0 +1,63 @@
+use anyhow::{Context, Result};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+// 这里我们导入主项目的模块
+use rust_discord_bot::image::repair_markdown;
+
+// 从已保存的会话中收集有问题的回答，加入Markdown修复语料库
+//
+// 用法: cargo run --bin collect_markdown_corpus [会话目录] [语料库目录]
+// 生成的 .expected.md 为当前修复结果，需人工检查后再提交。
+fn main() -> Result<()> {
+    let mut args = env::args().skip(1);
+    let sessions_dir = PathBuf::from(args.next().unwrap_or_else(|| "data/sessions".into()));
+    let corpus_dir = PathBuf::from(
+        args.next()
+            .unwrap_or_else(|| "src/image/repair_corpus".into()),
+    );
+    fs::create_dir_all(&corpus_dir).context("创建语料库目录失败")?;
+
+    let mut scanned = 0;
+    let mut collected = 0;
+    for entry in fs::read_dir(&sessions_dir).context("读取会话目录失败")? {
+        let session_dir = entry?.path();
+        let response_file = session_dir.join("response.md");
+        let Ok(markdown) = fs::read_to_string(&response_file) else {
+            continue;
+        };
+        scanned += 1;
+
+        let report = repair_markdown(&markdown);
+        if report.defects.is_empty() {
+            continue;
+        }
+
+        let session_id = session_dir
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let name: String = session_id.chars().take(8).collect();
+        let input_path = corpus_dir.join(format!("{}.md", name));
+        if input_path.exists() {
+            continue;
+        }
+        fs::write(&input_path, &markdown)?;
+        fs::write(
+            corpus_dir.join(format!("{}.expected.md", name)),
+            &report.markdown,
+        )?;
+        println!("{}: {:?}", name, report.defects);
+        collected += 1;
+    }
+
+    println!(
+        "已扫描 {} 个回答，收集 {} 个有问题的回答",
+        scanned, collected
+    );
+    if collected > 0 {
+        println!("请检查 {} 中新增的 .expected.md 文件", corpus_dir.display());
+    }
+    Ok(())
+}
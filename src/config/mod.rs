@@ -1,8 +1,14 @@
 use anyhow::{Context, Result};
 use std::env;
 use std::fs;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 
+use crate::delivery::{CodeAttachMode, DeliveryMode};
+use crate::image::{OutputFormat, RenderPreset};
+use crate::scheduler::Schedule;
+use crate::session::{RetentionConfig, RetentionPolicy, SessionStoreKind};
+
 #[derive(Debug, Clone)]
 pub struct Config {
     // 应用根目录
@@ -15,14 +21,44 @@ pub struct Config {
     // FastGPT配置
     pub fastgpt_api_url: String,
     pub fastgpt_auth_token: String,
+    // FastGPT 应用名称，显示在回答图片头部
+    pub fastgpt_app_name: Option<String>,
 
     // 图片生成配置
     pub image_output_dir: PathBuf,
     pub font_paths: Vec<PathBuf>,
-    #[allow(dead_code)]
+    // 桌面布局的画布宽度、字号、行高与内边距，以及默认的图片布局
+    pub render_width: u32,
     pub font_size: u32,
-    #[allow(dead_code)]
+    pub line_height: f32,
     pub padding: u32,
+    pub render_profile: RenderPreset,
+    // 单张图片的最大高度，超出时按块分页
+    pub max_page_height: u32,
+    // 图片输出格式与压缩设置
+    pub image_format: OutputFormat,
+    pub jpeg_quality: u8,
+    pub png_optimize: bool,
+    // 是否预先下载并内联回答中引用的远程图片
+    pub allow_remote_resources: bool,
+    // 远程图片的数量、大小与下载超时限制
+    pub remote_image_max_count: usize,
+    pub remote_image_max_bytes: u64,
+    pub remote_image_timeout_secs: u64,
+    // 回答图片是否显示问题与提问者信息头部
+    pub image_header: bool,
+    // 回答图片页脚文字，{guild} 会替换为服务器名称
+    pub image_footer_text: Option<String>,
+    // 渲染缓存的大小上限（MB），0 表示禁用
+    pub render_cache_max_mb: u64,
+
+    // 回答发送配置：默认发送方式、图片生成失败时是否改为文字、文字回答是否使用嵌入消息
+    pub delivery_mode: DeliveryMode,
+    pub text_fallback: bool,
+    pub text_use_embed: bool,
+    // 是否随回答图片附上原始 answer.md，以及代码块附件的发送方式
+    pub attach_markdown: bool,
+    pub code_attachments: CodeAttachMode,
 
     // Discord配置
     pub discord_token: String,
@@ -30,8 +66,22 @@ pub struct Config {
     pub discord_channel_whitelist: Vec<String>,
 
     // 会话配置
-    #[allow(dead_code)]
-    pub session_expiry: u64,
+    // 会话索引后端
+    pub session_store: SessionStoreKind,
+    // 会话保留策略：各类内容的保存期限、磁盘配额与服务器覆盖设置
+    pub retention: RetentionConfig,
+    // 会话清理任务的执行计划
+    pub cleanup_schedule: Schedule,
+    // 临时文件清理任务的执行计划
+    pub temp_sweep_schedule: Schedule,
+    // 临时文件保留时间（小时），超过后被清理
+    pub temp_max_age_hours: u64,
+    // 后台任务执行时间的最大随机延迟（秒）
+    pub job_jitter_secs: u64,
+    // 分享网页服务器的监听地址，未设置时不启动
+    pub share_web_addr: Option<SocketAddr>,
+    // 分享链接的公开地址前缀，未设置时使用监听地址
+    pub share_base_url: Option<String>,
     // API 并发请求限制
     #[allow(dead_code)]
     pub api_concurrency_limit: usize,
@@ -59,6 +109,10 @@ impl Config {
         let fastgpt_auth_token =
             env::var("FASTGPT_AUTH_TOKEN").context("缺少FASTGPT_AUTH_TOKEN环境变量")?;
 
+        let fastgpt_app_name = env::var("FASTGPT_APP_NAME")
+            .ok()
+            .filter(|s| !s.trim().is_empty());
+
         // 字体配置
         let font_paths_str = env::var("FONT_PATHS")
             .unwrap_or_else(|_| "./assets/fonts/LXGWWenKaiGBScreen.ttf".to_string());
@@ -74,16 +128,116 @@ impl Config {
             }
         }
 
+        let render_width = env::var("RENDER_WIDTH")
+            .unwrap_or_else(|_| "1024".to_string())
+            .parse()
+            .context("RENDER_WIDTH必须是数字（像素）")?;
+
         let font_size = env::var("FONT_SIZE")
             .unwrap_or_else(|_| "24".to_string())
             .parse()
             .context("FONT_SIZE必须是数字")?;
 
+        let line_height = env::var("LINE_HEIGHT")
+            .unwrap_or_else(|_| "1.8".to_string())
+            .parse()
+            .context("LINE_HEIGHT必须是数字")?;
+
         let padding = env::var("PADDING")
             .unwrap_or_else(|_| "30".to_string())
             .parse()
             .context("PADDING必须是数字")?;
 
+        // 默认图片布局：desktop 或 mobile，可被 /图片布局 的个人设置覆盖
+        let render_profile = env::var("RENDER_PROFILE")
+            .unwrap_or_else(|_| "desktop".to_string())
+            .parse()
+            .context("RENDER_PROFILE必须是 desktop 或 mobile")?;
+
+        let max_page_height = env::var("MAX_PAGE_HEIGHT")
+            .unwrap_or_else(|_| "4000".to_string())
+            .parse()
+            .context("MAX_PAGE_HEIGHT必须是数字")?;
+
+        // 图片输出格式：png、webp 或 jpeg
+        let image_format = env::var("IMAGE_FORMAT")
+            .unwrap_or_else(|_| "png".to_string())
+            .parse()
+            .context("IMAGE_FORMAT必须是 png、webp 或 jpeg")?;
+
+        let jpeg_quality = env::var("JPEG_QUALITY")
+            .unwrap_or_else(|_| "90".to_string())
+            .parse()
+            .ok()
+            .filter(|quality| (1..=100).contains(quality))
+            .context("JPEG_QUALITY必须是1-100之间的数字")?;
+
+        let png_optimize = env::var("PNG_OPTIMIZE")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .context("PNG_OPTIMIZE必须是 true 或 false")?;
+
+        let allow_remote_resources = env::var("ALLOW_REMOTE_RESOURCES")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .context("ALLOW_REMOTE_RESOURCES必须是 true 或 false")?;
+
+        let remote_image_max_count = env::var("REMOTE_IMAGE_MAX_COUNT")
+            .unwrap_or_else(|_| "5".to_string())
+            .parse()
+            .context("REMOTE_IMAGE_MAX_COUNT必须是数字")?;
+
+        let remote_image_max_bytes = env::var("REMOTE_IMAGE_MAX_BYTES")
+            .unwrap_or_else(|_| "5242880".to_string())
+            .parse()
+            .context("REMOTE_IMAGE_MAX_BYTES必须是数字（字节）")?;
+
+        let remote_image_timeout_secs = env::var("REMOTE_IMAGE_TIMEOUT_SECS")
+            .unwrap_or_else(|_| "10".to_string())
+            .parse()
+            .context("REMOTE_IMAGE_TIMEOUT_SECS必须是数字（秒）")?;
+
+        let image_header = env::var("IMAGE_HEADER")
+            .unwrap_or_else(|_| "true".to_string())
+            .parse()
+            .context("IMAGE_HEADER必须是 true 或 false")?;
+
+        let image_footer_text = env::var("IMAGE_FOOTER_TEXT")
+            .ok()
+            .filter(|s| !s.trim().is_empty());
+
+        let render_cache_max_mb = env::var("RENDER_CACHE_MAX_MB")
+            .unwrap_or_else(|_| "200".to_string())
+            .parse()
+            .context("RENDER_CACHE_MAX_MB必须是数字（MB）")?;
+
+        // 回答发送方式：image 或 text
+        let delivery_mode = env::var("DELIVERY_MODE")
+            .unwrap_or_else(|_| "image".to_string())
+            .parse()
+            .context("DELIVERY_MODE必须是 image 或 text")?;
+
+        let text_fallback = env::var("TEXT_FALLBACK")
+            .unwrap_or_else(|_| "true".to_string())
+            .parse()
+            .context("TEXT_FALLBACK必须是 true 或 false")?;
+
+        let text_use_embed = env::var("TEXT_USE_EMBED")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .context("TEXT_USE_EMBED必须是 true 或 false")?;
+
+        let attach_markdown = env::var("ATTACH_MARKDOWN")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .context("ATTACH_MARKDOWN必须是 true 或 false")?;
+
+        // 代码块附件：off、button 或 auto
+        let code_attachments = env::var("CODE_ATTACHMENTS")
+            .unwrap_or_else(|_| "button".to_string())
+            .parse()
+            .context("CODE_ATTACHMENTS必须是 off、button 或 auto")?;
+
         // Discord配置
         let discord_token = env::var("DISCORD_TOKEN").context("缺少DISCORD_TOKEN环境变量")?;
 
@@ -100,6 +254,90 @@ impl Config {
             .parse()
             .context("SESSION_EXPIRY必须是数字（表示天数）")?;
 
+        let session_store = env::var("SESSION_STORE")
+            .unwrap_or_else(|_| "sqlite".to_string())
+            .parse()
+            .context("SESSION_STORE必须是 sqlite 或 fs")?;
+
+        // 保留策略（以天为单位，0 表示永久保存），图片默认沿用 SESSION_EXPIRY
+        let retention_image_days = match env::var("RETENTION_IMAGE_DAYS") {
+            Ok(days) => days
+                .parse()
+                .context("RETENTION_IMAGE_DAYS必须是数字（表示天数）")?,
+            Err(_) => session_expiry,
+        };
+
+        let retention_markdown_days = env::var("RETENTION_MARKDOWN_DAYS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .context("RETENTION_MARKDOWN_DAYS必须是数字（表示天数）")?;
+
+        let retention_session_days = env::var("RETENTION_SESSION_DAYS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .context("RETENTION_SESSION_DAYS必须是数字（表示天数）")?;
+
+        let retention_user_quota_mb = env::var("RETENTION_USER_QUOTA_MB")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .context("RETENTION_USER_QUOTA_MB必须是数字（MB）")?;
+
+        let retention_global_quota_mb = env::var("RETENTION_GLOBAL_QUOTA_MB")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .context("RETENTION_GLOBAL_QUOTA_MB必须是数字（MB）")?;
+
+        let retention_dry_run = env::var("RETENTION_DRY_RUN")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .context("RETENTION_DRY_RUN必须是 true 或 false")?;
+
+        let retention = RetentionConfig {
+            default: RetentionPolicy {
+                image_days: Some(retention_image_days),
+                markdown_days: Some(retention_markdown_days),
+                session_days: Some(retention_session_days),
+            },
+            guilds: RetentionConfig::load_guild_overrides(&data_dir.join("retention.json"))?,
+            user_quota_mb: retention_user_quota_mb,
+            global_quota_mb: retention_global_quota_mb,
+            dry_run: retention_dry_run,
+        };
+
+        // 后台任务：间隔（如 6h）或 cron 表达式
+        let cleanup_schedule = env::var("CLEANUP_SCHEDULE")
+            .unwrap_or_else(|_| "6h".to_string())
+            .parse()
+            .context("CLEANUP_SCHEDULE必须是间隔（如 6h）或 cron 表达式")?;
+
+        let temp_sweep_schedule = env::var("TEMP_SWEEP_SCHEDULE")
+            .unwrap_or_else(|_| "1h".to_string())
+            .parse()
+            .context("TEMP_SWEEP_SCHEDULE必须是间隔（如 1h）或 cron 表达式")?;
+
+        let temp_max_age_hours = env::var("TEMP_MAX_AGE_HOURS")
+            .unwrap_or_else(|_| "6".to_string())
+            .parse()
+            .context("TEMP_MAX_AGE_HOURS必须是数字（小时）")?;
+
+        let job_jitter_secs = env::var("JOB_JITTER_SECS")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse()
+            .context("JOB_JITTER_SECS必须是数字（秒）")?;
+
+        // 分享网页：监听地址（如 0.0.0.0:8080）与对外访问的地址前缀
+        let share_web_addr = env::var("SHARE_WEB_ADDR")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| s.trim().parse())
+            .transpose()
+            .context("SHARE_WEB_ADDR必须是监听地址，如 0.0.0.0:8080")?;
+
+        let share_base_url = env::var("SHARE_BASE_URL")
+            .ok()
+            .map(|s| s.trim().trim_end_matches('/').to_string())
+            .filter(|s| !s.is_empty());
+
         // API 并发请求限制，默认 5
         let api_concurrency_limit = env::var("FASTGPT_CONCURRENCY_LIMIT")
             .unwrap_or_else(|_| "5".to_string())
@@ -111,13 +349,40 @@ impl Config {
             data_dir,
             fastgpt_api_url,
             fastgpt_auth_token,
+            fastgpt_app_name,
             image_output_dir,
             font_paths,
+            render_width,
             font_size,
+            line_height,
             padding,
+            render_profile,
+            max_page_height,
+            image_format,
+            jpeg_quality,
+            png_optimize,
+            allow_remote_resources,
+            remote_image_max_count,
+            remote_image_max_bytes,
+            remote_image_timeout_secs,
+            image_header,
+            image_footer_text,
+            render_cache_max_mb,
+            delivery_mode,
+            text_fallback,
+            text_use_embed,
+            attach_markdown,
+            code_attachments,
             discord_token,
             discord_channel_whitelist,
-            session_expiry,
+            session_store,
+            retention,
+            cleanup_schedule,
+            temp_sweep_schedule,
+            temp_max_age_hours,
+            job_jitter_secs,
+            share_web_addr,
+            share_base_url,
             api_concurrency_limit,
         })
     }
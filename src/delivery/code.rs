@@ -0,0 +1,158 @@
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag};
+
+/// 从回答中提取的代码块
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock {
+    /// 附件文件名，如 snippet_1.py 或代码块标注的 config.toml
+    pub filename: String,
+    pub code: String,
+}
+
+/// 提取Markdown中的所有围栏代码块
+///
+/// 文件名默认为 `snippet_{序号}.{扩展名}`；信息字符串中带有文件名时
+/// （如 ```` ```toml:config.toml ```` 或 ```` ```toml title="config.toml" ````）使用该文件名。
+pub fn extract_code_blocks(markdown: &str) -> Vec<CodeBlock> {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+
+    let mut blocks: Vec<CodeBlock> = Vec::new();
+    // 正在收集的代码块：(语言, 标注的文件名, 代码)
+    let mut current: Option<(String, Option<String>, String)> = None;
+    for event in Parser::new_ext(markdown, options) {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+                let (language, filename) = parse_info(&info);
+                current = Some((language, filename, String::new()));
+            }
+            Event::Text(text) => {
+                if let Some((_, _, code)) = current.as_mut() {
+                    code.push_str(&text);
+                }
+            }
+            Event::End(Tag::CodeBlock(_)) => {
+                if let Some((language, filename, code)) = current.take() {
+                    if code.trim().is_empty() {
+                        continue;
+                    }
+                    let filename = filename.unwrap_or_else(|| {
+                        format!("snippet_{}.{}", blocks.len() + 1, extension_for(&language))
+                    });
+                    let filename = unique_filename(&blocks, filename);
+                    blocks.push(CodeBlock { filename, code });
+                }
+            }
+            _ => {}
+        }
+    }
+    blocks
+}
+
+// 解析信息字符串，返回 (语言, 标注的文件名)
+fn parse_info(info: &str) -> (String, Option<String>) {
+    let mut words = info.split_whitespace();
+    let first = words.next().unwrap_or("");
+    if let Some((language, filename)) = first.split_once(':') {
+        return (language.to_lowercase(), sanitize_filename(filename));
+    }
+
+    let filename = words
+        .filter_map(|w| w.split_once('='))
+        .find(|(key, _)| matches!(*key, "title" | "filename" | "file"))
+        .and_then(|(_, value)| sanitize_filename(value.trim_matches(|c| c == '"' || c == '\'')));
+    (first.to_lowercase(), filename)
+}
+
+// 只保留文件名本身，去掉路径与不安全的字符
+fn sanitize_filename(name: &str) -> Option<String> {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or("");
+    let cleaned: String = base
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+        .collect();
+    if cleaned.is_empty() || cleaned.starts_with('.') {
+        None
+    } else {
+        Some(cleaned)
+    }
+}
+
+// 文件名重复时在扩展名前加序号
+fn unique_filename(blocks: &[CodeBlock], filename: String) -> String {
+    if !blocks.iter().any(|b| b.filename == filename) {
+        return filename;
+    }
+    let (stem, ext) = match filename.rsplit_once('.') {
+        Some((stem, ext)) => (stem.to_string(), format!(".{}", ext)),
+        None => (filename.clone(), String::new()),
+    };
+    (2..)
+        .map(|n| format!("{}_{}{}", stem, n, ext))
+        .find(|name| !blocks.iter().any(|b| &b.filename == name))
+        .unwrap_or(filename)
+}
+
+// 根据代码块语言获取文件扩展名，未知语言使用 txt
+fn extension_for(language: &str) -> &'static str {
+    match language.to_lowercase().as_str() {
+        "py" | "python" | "python3" => "py",
+        "rs" | "rust" => "rs",
+        "js" | "javascript" | "node" => "js",
+        "jsx" => "jsx",
+        "ts" | "typescript" => "ts",
+        "tsx" => "tsx",
+        "java" => "java",
+        "kt" | "kotlin" => "kt",
+        "c" => "c",
+        "h" => "h",
+        "cpp" | "c++" | "cc" | "cxx" => "cpp",
+        "cs" | "csharp" | "c#" => "cs",
+        "go" | "golang" => "go",
+        "rb" | "ruby" => "rb",
+        "php" => "php",
+        "lua" => "lua",
+        "swift" => "swift",
+        "sh" | "bash" | "shell" | "zsh" | "console" => "sh",
+        "ps1" | "powershell" | "pwsh" => "ps1",
+        "bat" | "cmd" | "batch" => "bat",
+        "sql" => "sql",
+        "html" | "htm" => "html",
+        "css" => "css",
+        "scss" => "scss",
+        "xml" => "xml",
+        "json" => "json",
+        "yaml" | "yml" => "yml",
+        "toml" => "toml",
+        "ini" | "cfg" | "conf" => "ini",
+        "md" | "markdown" => "md",
+        "diff" | "patch" => "diff",
+        "dockerfile" | "docker" => "dockerfile",
+        "makefile" | "make" => "mk",
+        _ => "txt",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_blocks_with_extensions() {
+        let markdown = "说明\n\n```python\nprint('hi')\n```\n\n```\n纯文本\n```\n\n```toml title=\"config.toml\"\n[server]\nport = 8080\n```\n";
+        let blocks = extract_code_blocks(markdown);
+        let names: Vec<&str> = blocks.iter().map(|b| b.filename.as_str()).collect();
+        assert_eq!(names, vec!["snippet_1.py", "snippet_2.txt", "config.toml"]);
+        assert_eq!(blocks[0].code, "print('hi')\n");
+        assert!(blocks[2].code.contains("port = 8080"));
+    }
+
+    #[test]
+    fn annotated_filenames_are_sanitized_and_unique() {
+        let markdown =
+            "```rust:../../src/main.rs\nfn main() {}\n```\n\n```rust:main.rs\nfn main() {}\n```\n";
+        let blocks = extract_code_blocks(markdown);
+        assert_eq!(blocks[0].filename, "main.rs");
+        assert_eq!(blocks[1].filename, "main_2.rs");
+    }
+}
@@ -0,0 +1,175 @@
+mod code;
+mod table;
+mod text;
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+use tracing::{error, info};
+
+use crate::config::Config;
+use crate::image::RenderPreset;
+
+pub use self::code::{extract_code_blocks, CodeBlock};
+pub use self::text::{
+    split_message, to_discord_markdown, to_discord_segments, TextSegment, EMBED_LIMIT,
+    MESSAGE_LIMIT,
+};
+
+/// 回答的发送方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryMode {
+    /// 渲染为图片
+    Image,
+    /// 以Discord Markdown文字发送
+    Text,
+}
+
+impl FromStr for DeliveryMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "image" | "图片" => Ok(DeliveryMode::Image),
+            "text" | "文字" => Ok(DeliveryMode::Text),
+            other => Err(anyhow!("不支持的发送方式: {}", other)),
+        }
+    }
+}
+
+/// 代码块附件的发送方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeAttachMode {
+    /// 不发送代码文件
+    Off,
+    /// 在回答下方显示"获取代码"按钮
+    Button,
+    /// 回答包含代码时自动附上代码文件
+    Auto,
+}
+
+impl FromStr for CodeAttachMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "off" => Ok(CodeAttachMode::Off),
+            "button" => Ok(CodeAttachMode::Button),
+            "auto" => Ok(CodeAttachMode::Auto),
+            other => Err(anyhow!("不支持的代码附件方式: {}", other)),
+        }
+    }
+}
+
+// 持久化的偏好数据
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PreferenceData {
+    #[serde(default)]
+    users: HashMap<String, DeliveryMode>,
+    #[serde(default)]
+    guilds: HashMap<String, DeliveryMode>,
+    // 用户的图片布局偏好
+    #[serde(default)]
+    profiles: HashMap<String, RenderPreset>,
+}
+
+/// 用户与服务器的回答发送方式偏好，以及用户的图片布局偏好，保存在 data/preferences.json
+#[derive(Debug)]
+pub struct DeliveryPreferences {
+    path: PathBuf,
+    default_mode: DeliveryMode,
+    default_profile: RenderPreset,
+    data: Mutex<PreferenceData>,
+}
+
+impl DeliveryPreferences {
+    pub fn new(config: &Config) -> Self {
+        let path = config.data_dir.join("preferences.json");
+        let data = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                error!("解析偏好设置失败，将使用默认设置: {}", e);
+                PreferenceData::default()
+            }),
+            Err(_) => PreferenceData::default(),
+        };
+
+        DeliveryPreferences {
+            path,
+            default_mode: config.delivery_mode,
+            default_profile: config.render_profile,
+            data: Mutex::new(data),
+        }
+    }
+
+    /// 获取实际使用的发送方式：个人设置优先，其次为服务器设置，最后为全局默认
+    pub fn resolve(&self, user_id: &str, guild_id: Option<&str>) -> DeliveryMode {
+        let data = self.data.lock().unwrap();
+        data.users
+            .get(user_id)
+            .or_else(|| guild_id.and_then(|id| data.guilds.get(id)))
+            .copied()
+            .unwrap_or(self.default_mode)
+    }
+
+    /// 设置个人偏好，`None` 表示恢复默认
+    pub fn set_user(&self, user_id: &str, mode: Option<DeliveryMode>) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        match mode {
+            Some(mode) => data.users.insert(user_id.to_string(), mode),
+            None => data.users.remove(user_id),
+        };
+        self.save(&data)
+    }
+
+    /// 设置服务器偏好，`None` 表示恢复默认
+    pub fn set_guild(&self, guild_id: &str, mode: Option<DeliveryMode>) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        match mode {
+            Some(mode) => data.guilds.insert(guild_id.to_string(), mode),
+            None => data.guilds.remove(guild_id),
+        };
+        self.save(&data)
+    }
+
+    /// 获取用户的图片布局，未设置时使用全局默认
+    pub fn resolve_profile(&self, user_id: &str) -> RenderPreset {
+        let data = self.data.lock().unwrap();
+        data.profiles
+            .get(user_id)
+            .copied()
+            .unwrap_or(self.default_profile)
+    }
+
+    /// 设置用户的图片布局，`None` 表示恢复默认
+    pub fn set_user_profile(&self, user_id: &str, preset: Option<RenderPreset>) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        match preset {
+            Some(preset) => data.profiles.insert(user_id.to_string(), preset),
+            None => data.profiles.remove(user_id),
+        };
+        self.save(&data)
+    }
+
+    /// 删除用户的全部个人偏好，返回是否有设置被删除
+    pub fn forget_user(&self, user_id: &str) -> Result<bool> {
+        let mut data = self.data.lock().unwrap();
+        let removed =
+            data.users.remove(user_id).is_some() | data.profiles.remove(user_id).is_some();
+        if removed {
+            self.save(&data)?;
+        }
+        Ok(removed)
+    }
+
+    fn save(&self, data: &PreferenceData) -> Result<()> {
+        let content = serde_json::to_string_pretty(data).context("序列化偏好设置失败")?;
+        fs::write(&self.path, content).context("保存偏好设置失败")?;
+        info!("偏好设置已保存: {}", self.path.display());
+        Ok(())
+    }
+}
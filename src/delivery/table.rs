@@ -0,0 +1,186 @@
+use pulldown_cmark::Alignment;
+use unicode_width::UnicodeWidthStr;
+
+// 列数不超过该值的表格在嵌入消息中按行转换为字段
+const NARROW_TABLE_COLUMNS: usize = 3;
+// 单个嵌入消息最多的字段数
+const MAX_EMBED_FIELDS: usize = 25;
+// 字段名与字段值的长度限制
+const FIELD_NAME_LIMIT: usize = 256;
+const FIELD_VALUE_LIMIT: usize = 1024;
+// 嵌入消息的总字符数限制（留出余量）
+const EMBED_TOTAL_LIMIT: usize = 5500;
+
+/// 从Markdown解析出的表格
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    pub alignments: Vec<Alignment>,
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    fn columns(&self) -> usize {
+        self.rows
+            .iter()
+            .map(Vec::len)
+            .chain([self.header.len(), self.alignments.len()])
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// 渲染为等宽对齐的代码块，中日韩等宽字符按两格计算
+    pub fn to_monospace(&self) -> String {
+        let columns = self.columns();
+        let mut widths = vec![0usize; columns];
+        for row in std::iter::once(&self.header).chain(&self.rows) {
+            for (i, cell) in row.iter().enumerate() {
+                widths[i] = widths[i].max(cell.width());
+            }
+        }
+
+        let format_row = |row: &Vec<String>| {
+            (0..columns)
+                .map(|i| {
+                    let cell = row.get(i).map(String::as_str).unwrap_or("");
+                    let alignment = self.alignments.get(i).copied().unwrap_or(Alignment::None);
+                    pad_cell(cell, widths[i], alignment)
+                })
+                .collect::<Vec<_>>()
+                .join(" │ ")
+                .trim_end()
+                .to_string()
+        };
+
+        let mut lines = vec![format_row(&self.header)];
+        lines.push(
+            widths
+                .iter()
+                .map(|w| "─".repeat(*w))
+                .collect::<Vec<_>>()
+                .join("─┼─"),
+        );
+        lines.extend(self.rows.iter().map(format_row));
+        format!("```\n{}\n```", lines.join("\n"))
+    }
+
+    /// 列数较少的表格转换为嵌入字段：每行一个字段，第一列作为字段名
+    ///
+    /// 表格过宽、行数超过字段上限或内容过长时返回 `None`，应改用等宽代码块。
+    pub fn to_fields(&self) -> Option<Vec<(String, String)>> {
+        if self.columns() > NARROW_TABLE_COLUMNS
+            || self.rows.is_empty()
+            || self.rows.len() > MAX_EMBED_FIELDS
+        {
+            return None;
+        }
+
+        let fields: Vec<(String, String)> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let name = row.first().map(|c| c.trim()).unwrap_or("");
+                let name = if name.is_empty() { "—" } else { name };
+                let value = row
+                    .iter()
+                    .enumerate()
+                    .skip(1)
+                    .map(|(i, cell)| match self.header.get(i) {
+                        Some(header) if !header.trim().is_empty() => {
+                            format!("**{}**: {}", header.trim(), cell.trim())
+                        }
+                        _ => cell.trim().to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                // 字段值不能为空
+                let value = if value.trim().is_empty() {
+                    "\u{200b}".to_string()
+                } else {
+                    value
+                };
+                (
+                    truncate_chars(name, FIELD_NAME_LIMIT),
+                    truncate_chars(&value, FIELD_VALUE_LIMIT),
+                )
+            })
+            .collect();
+
+        let total: usize = fields
+            .iter()
+            .map(|(name, value)| name.chars().count() + value.chars().count())
+            .sum();
+        if total > EMBED_TOTAL_LIMIT {
+            return None;
+        }
+        Some(fields)
+    }
+}
+
+// 按显示宽度填充单元格
+fn pad_cell(cell: &str, width: usize, alignment: Alignment) -> String {
+    let padding = width.saturating_sub(cell.width());
+    match alignment {
+        Alignment::Right => format!("{}{}", " ".repeat(padding), cell),
+        Alignment::Center => {
+            let left = padding / 2;
+            format!("{}{}{}", " ".repeat(left), cell, " ".repeat(padding - left))
+        }
+        Alignment::Left | Alignment::None => format!("{}{}", cell, " ".repeat(padding)),
+    }
+}
+
+fn truncate_chars(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        text.to_string()
+    } else {
+        let mut truncated: String = text.chars().take(max_chars - 1).collect();
+        truncated.push('…');
+        truncated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Table {
+        Table {
+            alignments: vec![Alignment::None, Alignment::Right],
+            header: vec!["名称".to_string(), "count".to_string()],
+            rows: vec![
+                vec!["苹果".to_string(), "3".to_string()],
+                vec!["banana".to_string(), "12".to_string()],
+            ],
+        }
+    }
+
+    #[test]
+    fn monospace_aligns_cjk_by_display_width() {
+        let text = sample().to_monospace();
+        let lines: Vec<&str> = text
+            .lines()
+            .filter(|l| !l.starts_with("```") && !l.starts_with('─'))
+            .collect();
+        // 每行分隔符前的显示宽度一致
+        let widths: Vec<usize> = lines
+            .iter()
+            .map(|l| l.split(" │ ").next().unwrap().width())
+            .collect();
+        assert!(widths.iter().all(|&w| w == widths[0]), "{}", text);
+        assert!(text.contains("苹果   │     3"), "{}", text);
+    }
+
+    #[test]
+    fn narrow_tables_become_fields() {
+        let fields = sample().to_fields().expect("应转换为字段");
+        assert_eq!(fields[0], ("苹果".to_string(), "**count**: 3".to_string()));
+
+        let mut wide = sample();
+        wide.header = vec!["a", "b", "c", "d"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        assert!(wide.to_fields().is_none());
+    }
+}
@@ -0,0 +1,405 @@
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use std::ops::Range;
+
+use super::table::Table;
+
+/// Discord 普通消息的最大字符数
+pub const MESSAGE_LIMIT: usize = 2000;
+/// Discord 嵌入消息描述的最大字符数
+pub const EMBED_LIMIT: usize = 4096;
+
+// 替代分隔线的文字
+const RULE_TEXT: &str = "──────────";
+
+/// 转换后的文字回答片段
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextSegment {
+    /// Discord Markdown 文本
+    Markdown(String),
+    /// 以嵌入字段显示的窄表格：(字段名, 字段值)
+    Fields(Vec<(String, String)>),
+}
+
+// 源码中某个范围的替换内容
+enum Replacement {
+    Text(String),
+    Fields(Vec<(String, String)>),
+}
+
+/// 将模型输出的Markdown转换为Discord支持的格式
+///
+/// Discord 只支持一到三级标题，不支持表格、图片、分隔线和任务列表，
+/// 这些内容会被转换为等效的写法；表格转换为等宽对齐的代码块，代码块内的内容保持不变。
+pub fn to_discord_markdown(markdown: &str) -> String {
+    convert(markdown, false)
+        .into_iter()
+        .map(|segment| match segment {
+            TextSegment::Markdown(text) => text,
+            TextSegment::Fields(_) => unreachable!("未启用字段转换"),
+        })
+        .collect()
+}
+
+/// 与 [`to_discord_markdown`] 相同，但列数较少的表格转换为嵌入字段，用于嵌入消息发送
+pub fn to_discord_segments(markdown: &str) -> Vec<TextSegment> {
+    convert(markdown, true)
+}
+
+fn convert(markdown: &str, table_fields: bool) -> Vec<TextSegment> {
+    // 与图片渲染使用相同的解析选项
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_TASKLISTS);
+
+    let mut replacements: Vec<(Range<usize>, Replacement)> = Vec::new();
+    // 正在收集替代文本的图片：(源码范围, 地址, 替代文本)
+    let mut image: Option<(Range<usize>, String, String)> = None;
+    // 正在收集的表格及其源码范围
+    let mut table: Option<(Range<usize>, Table)> = None;
+
+    for (event, range) in Parser::new_ext(markdown, options).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading(level, _, _)) if level as u32 >= 4 => {
+                let range = trim_range(markdown, range);
+                let title = markdown[range.clone()]
+                    .trim_start_matches('#')
+                    .trim_end_matches('#')
+                    .trim();
+                replacements.push((range, Replacement::Text(format!("**{}**", title))));
+            }
+            Event::Start(Tag::Table(alignments)) => {
+                let parsed = Table {
+                    alignments,
+                    ..Default::default()
+                };
+                table = Some((trim_range(markdown, range), parsed));
+            }
+            Event::Start(Tag::TableRow) => {
+                if let Some((_, parsed)) = table.as_mut() {
+                    parsed.rows.push(Vec::new());
+                }
+            }
+            Event::Start(Tag::TableCell) => {
+                if let Some((_, parsed)) = table.as_mut() {
+                    // 表头的单元格没有 TableRow 包裹
+                    match parsed.rows.last_mut() {
+                        Some(row) => row.push(String::new()),
+                        None => parsed.header.push(String::new()),
+                    }
+                }
+            }
+            Event::End(Tag::Table(_)) => {
+                if let Some((range, parsed)) = table.take() {
+                    let replacement = match parsed.to_fields() {
+                        Some(fields) if table_fields => Replacement::Fields(fields),
+                        _ => Replacement::Text(parsed.to_monospace()),
+                    };
+                    replacements.push((range, replacement));
+                }
+            }
+            Event::Start(Tag::Image(_, url, _)) => {
+                image = Some((range, url.to_string(), String::new()));
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, _, alt)) = image.as_mut() {
+                    alt.push_str(&text);
+                } else if let Some((_, parsed)) = table.as_mut() {
+                    let row = match parsed.rows.last_mut() {
+                        Some(row) => row,
+                        None => &mut parsed.header,
+                    };
+                    if let Some(cell) = row.last_mut() {
+                        cell.push_str(&text);
+                    }
+                }
+            }
+            Event::End(Tag::Image(..)) => {
+                if let Some((range, url, alt)) = image.take() {
+                    let lower = url.trim().to_ascii_lowercase();
+                    let text = if lower.starts_with("http://") || lower.starts_with("https://") {
+                        format!("[🖼 {}]({})", alt, url)
+                    } else {
+                        format!("🖼 {}", alt)
+                    };
+                    replacements.push((range, Replacement::Text(text)));
+                }
+            }
+            Event::Rule => {
+                replacements.push((
+                    trim_range(markdown, range),
+                    Replacement::Text(RULE_TEXT.to_string()),
+                ));
+            }
+            Event::TaskListMarker(checked) => {
+                let mark = if checked { "☑" } else { "☐" };
+                replacements.push((range, Replacement::Text(mark.to_string())));
+            }
+            _ => {}
+        }
+    }
+
+    // 按顺序拼接，跳过位于已替换区域内部的替换（如表格中的图片）
+    replacements.sort_by_key(|(range, _)| range.start);
+    let mut segments = Vec::new();
+    let mut output = String::with_capacity(markdown.len());
+    let mut last = 0;
+    for (range, replacement) in replacements {
+        if range.start < last {
+            continue;
+        }
+        output.push_str(&markdown[last..range.start]);
+        match replacement {
+            Replacement::Text(text) => output.push_str(&text),
+            Replacement::Fields(fields) => {
+                if !output.trim().is_empty() {
+                    segments.push(TextSegment::Markdown(std::mem::take(&mut output)));
+                }
+                output.clear();
+                segments.push(TextSegment::Fields(fields));
+            }
+        }
+        last = range.end;
+    }
+    output.push_str(&markdown[last..]);
+    if !output.trim().is_empty() || segments.is_empty() {
+        segments.push(TextSegment::Markdown(output));
+    }
+    segments
+}
+
+// 去掉范围末尾的换行，替换时保留原有的换行
+fn trim_range(source: &str, range: Range<usize>) -> Range<usize> {
+    let trimmed = source[range.clone()].trim_end();
+    range.start..range.start + trimmed.len()
+}
+
+/// 尚未闭合的代码块围栏
+#[derive(Debug, Clone)]
+struct OpenFence {
+    marker: char,
+    count: usize,
+    // 开始行（含语言标记），分段后用于重新打开代码块
+    opening: String,
+}
+
+impl OpenFence {
+    fn closing(&self) -> String {
+        self.marker.to_string().repeat(self.count)
+    }
+}
+
+// 解析围栏行，返回 (围栏字符, 数量, 信息字符串)
+fn parse_fence(line: &str) -> Option<(char, usize, &str)> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let marker = trimmed.chars().next()?;
+    if marker != '`' && marker != '~' {
+        return None;
+    }
+    let count = trimmed.chars().take_while(|&c| c == marker).count();
+    if count < 3 {
+        return None;
+    }
+    Some((marker, count, trimmed[count..].trim()))
+}
+
+// 计算处理完一行后的围栏状态
+fn next_fence(fence: &Option<OpenFence>, line: &str) -> Option<OpenFence> {
+    match (fence, parse_fence(line)) {
+        (None, Some((marker, count, _))) => Some(OpenFence {
+            marker,
+            count,
+            opening: line.trim_start_matches(' ').to_string(),
+        }),
+        (Some(open), Some((marker, count, info)))
+            if marker == open.marker && count >= open.count && info.is_empty() =>
+        {
+            None
+        }
+        (open, _) => open.clone(),
+    }
+}
+
+/// 按Discord的字符数限制切分消息
+///
+/// 优先在行之间切分；超长的行会在空白处或强制截断。
+/// 切分点位于代码块内时，当前段落补上闭合围栏，下一段以相同的语言重新打开代码块。
+pub fn split_message(text: &str, limit: usize) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut current_len = 0usize;
+    // 当前段落是否包含除重新打开的围栏以外的内容
+    let mut has_content = false;
+    let mut fence: Option<OpenFence> = None;
+
+    for line in text.lines() {
+        let after = next_fence(&fence, line);
+        // 为闭合围栏预留空间
+        let reserve = [&fence, &after]
+            .iter()
+            .filter_map(|f| f.as_ref().map(|f| f.count + 1))
+            .max()
+            .unwrap_or(0);
+        // 重新打开代码块后，单行片段也必须放得下
+        let overhead = fence
+            .as_ref()
+            .map(|f| f.opening.chars().count() + f.count + 2)
+            .unwrap_or(0);
+        let max_piece = limit.saturating_sub(overhead).max(1);
+
+        for piece in wrap_line(line, max_piece) {
+            let piece_len = piece.chars().count();
+            let needed = if current.is_empty() {
+                piece_len
+            } else {
+                current_len + 1 + piece_len
+            };
+            if has_content && needed + reserve > limit {
+                if let Some(open) = &fence {
+                    current.push('\n');
+                    current.push_str(&open.closing());
+                }
+                push_part(&mut parts, &current);
+                current.clear();
+                current_len = 0;
+                has_content = false;
+                if let Some(open) = &fence {
+                    current.push_str(&open.opening);
+                    current_len = open.opening.chars().count();
+                }
+            }
+
+            // 段落开头的空行没有意义
+            if current.is_empty() && fence.is_none() && piece.trim().is_empty() {
+                continue;
+            }
+            if !current.is_empty() {
+                current.push('\n');
+                current_len += 1;
+            }
+            current.push_str(&piece);
+            current_len += piece_len;
+            has_content = true;
+        }
+        fence = after;
+    }
+    if has_content {
+        push_part(&mut parts, &current);
+    }
+    parts
+}
+
+fn push_part(parts: &mut Vec<String>, part: &str) {
+    let part = part.trim_end();
+    if !part.trim().is_empty() {
+        parts.push(part.to_string());
+    }
+}
+
+// 将超长的行拆为不超过 max_chars 的片段，尽量在空白处断开
+fn wrap_line(line: &str, max_chars: usize) -> Vec<String> {
+    let chars: Vec<char> = line.chars().collect();
+    if chars.len() <= max_chars {
+        return vec![line.to_string()];
+    }
+
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    while chars.len() - start > max_chars {
+        let window = &chars[start..start + max_chars];
+        let split = window
+            .iter()
+            .rposition(|c| c.is_whitespace())
+            .filter(|&i| i > max_chars / 2)
+            .unwrap_or(max_chars);
+        pieces.push(window[..split].iter().collect::<String>());
+        start += split;
+        // 断开处的空白不保留到下一段开头
+        while start < chars.len() && chars[start].is_whitespace() {
+            start += 1;
+        }
+    }
+    if start < chars.len() {
+        pieces.push(chars[start..].iter().collect());
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fence_lines(part: &str) -> usize {
+        part.lines().filter(|l| parse_fence(l).is_some()).count()
+    }
+
+    #[test]
+    fn short_text_is_single_part() {
+        assert_eq!(
+            split_message("你好\n\n世界", MESSAGE_LIMIT),
+            vec!["你好\n\n世界"]
+        );
+    }
+
+    #[test]
+    fn split_never_cuts_code_fence() {
+        let mut text = String::from("说明文字\n\n```rust\n");
+        for i in 0..60 {
+            text.push_str(&format!("let value_{} = {};\n", i, i));
+        }
+        text.push_str("```\n\n结尾");
+        let parts = split_message(&text, 300);
+        assert!(parts.len() > 2);
+        for (i, part) in parts.iter().enumerate() {
+            assert!(part.chars().count() <= 300, "第{}段超出限制", i);
+            assert_eq!(fence_lines(part) % 2, 0, "第{}段代码块未闭合: {}", i, part);
+        }
+        // 后续段落以相同语言重新打开代码块
+        assert!(parts[1].starts_with("```rust\n"));
+        assert!(parts.last().unwrap().ends_with("结尾"));
+    }
+
+    #[test]
+    fn long_lines_are_wrapped() {
+        let text = "字".repeat(5000);
+        let parts = split_message(&text, MESSAGE_LIMIT);
+        assert_eq!(parts.len(), 3);
+        assert!(parts.iter().all(|p| p.chars().count() <= MESSAGE_LIMIT));
+        assert_eq!(parts.concat(), text);
+    }
+
+    #[test]
+    fn converts_unsupported_syntax() {
+        let markdown = "#### 小标题\n\n![示意图](https://example.com/a.png)\n\n---\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n- [x] 完成\n\n```md\n#### 不要转换\n```\n";
+        let converted = to_discord_markdown(markdown);
+        assert!(converted.contains("**小标题**"));
+        assert!(converted.contains("[🖼 示意图](https://example.com/a.png)"));
+        assert!(converted.contains(RULE_TEXT));
+        assert!(
+            converted.contains("```\na │ b\n──┼──\n1 │ 2\n```"),
+            "{}",
+            converted
+        );
+        assert!(converted.contains("☑ 完成"));
+        assert!(converted.contains("#### 不要转换"));
+    }
+
+    #[test]
+    fn narrow_tables_become_embed_fields() {
+        let markdown = "前言\n\n| 命令 | 说明 |\n|---|---|\n| /帮助 | 查看指南 |\n\n结尾";
+        let segments = to_discord_segments(markdown);
+        assert_eq!(segments.len(), 3);
+        assert_eq!(
+            segments[1],
+            TextSegment::Fields(vec![(
+                "/帮助".to_string(),
+                "**说明**: 查看指南".to_string()
+            )])
+        );
+        assert!(matches!(&segments[2], TextSegment::Markdown(text) if text.contains("结尾")));
+    }
+}
@@ -1,14 +1,214 @@
 use anyhow::Result;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
 use poise::serenity_prelude as serenity;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use tracing::{debug, info};
+use std::time::Instant;
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
 use super::Context;
 use crate::api::FastGPTMessage;
+use crate::delivery::{
+    extract_code_blocks, split_message, to_discord_markdown, to_discord_segments, CodeAttachMode,
+    CodeBlock, DeliveryMode, TextSegment, EMBED_LIMIT, MESSAGE_LIMIT,
+};
+use crate::image::{repair_markdown, AnswerMeta, RenderPreset, RenderProfile};
+use crate::session::{
+    markdown_with_context, RetentionPolicy, SearchPage, SearchQuery, SessionMeta, SessionStatus,
+    Turn, TurnRole,
+};
+use crate::util::format_bytes;
+use crate::web::{share_base_url, share_url};
 use serde_json::json;
 
+// Discord 单条消息允许的最大附件数
+const MAX_ATTACHMENTS_PER_MESSAGE: usize = 10;
+// 每页显示的搜索结果数
+const SEARCH_PAGE_SIZE: usize = 5;
+// 搜索结果翻页按钮的有效时间（秒）
+const SEARCH_PAGINATION_TIMEOUT_SECS: u64 = 600;
+// 查看会话时追问与分享按钮的有效时间（秒）
+const VIEW_SESSION_TIMEOUT_SECS: u64 = 600;
+// 删除全部数据确认按钮的有效时间（秒）
+const DELETE_CONFIRM_TIMEOUT_SECS: u64 = 60;
+// 多轮会话渲染回答时，作为上下文显示的之前消息数
+const RENDER_CONTEXT_TURNS: usize = 4;
+// 清理报告中最多列出的操作数
+const RETENTION_REPORT_MAX_ITEMS: usize = 15;
+
+// 根据服务器加成等级获取上传大小限制（字节），私信使用默认限制
+fn upload_limit_bytes(ctx: Context<'_>) -> u64 {
+    upload_limit_for_tier(ctx.guild().map(|g| g.premium_tier))
+}
+
+fn upload_limit_for_tier(tier: Option<serenity::PremiumTier>) -> u64 {
+    let megabytes = match tier {
+        Some(serenity::PremiumTier::Tier1) => 25,
+        Some(serenity::PremiumTier::Tier2) => 50,
+        Some(serenity::PremiumTier::Tier3) => 100,
+        _ => 8,
+    };
+    megabytes * 1024 * 1024
+}
+
+// 构造回答图片头部信息：问题、提问者、时间、会话ID与应用名称
+//
+// 时间与应用名称取自会话元数据，从历史记录重新渲染时显示提问时间，且HTML不随渲染时间变化，
+// 可以命中渲染缓存。
+async fn answer_meta(ctx: Context<'_>, question: &str, session_id: &str) -> AnswerMeta {
+    let config = &ctx.data().config;
+    let stored = match ctx.data().api_client.session_manager.read_meta(session_id) {
+        Ok(meta) => Some(meta),
+        Err(e) => {
+            warn!("读取会话 {} 元数据失败: {:?}", session_id, e);
+            None
+        }
+    };
+    let asker_name = match ctx.author_member().await {
+        Some(member) => member.display_name().to_string(),
+        None => ctx.author().name.clone(),
+    };
+    // 仅在启用头部时下载头像
+    let avatar_data_uri = if config.image_header {
+        ctx.data()
+            .api_client
+            .image_generator
+            .fetch_avatar(&ctx.author().face())
+            .await
+    } else {
+        None
+    };
+    AnswerMeta {
+        question: Some(question.to_string()),
+        asker_name: Some(asker_name),
+        avatar_data_uri,
+        timestamp: Some(
+            stored
+                .as_ref()
+                .map_or_else(Local::now, |meta| meta.created_at.with_timezone(&Local)),
+        ),
+        session_id: Some(session_id.to_string()),
+        app_name: stored
+            .and_then(|meta| meta.app_name)
+            .or_else(|| config.fastgpt_app_name.clone()),
+        guild_name: ctx.guild().map(|g| g.name),
+    }
+}
+
+// 以文字形式发送回答，按Discord的长度限制分段，代码块在分段处会重新打开
+//
+// 使用嵌入消息时，列数较少的表格改为每行一个字段显示
+async fn send_text_answer(ctx: Context<'_>, markdown: &str, notice: Option<&str>) -> Result<()> {
+    let use_embed = ctx.data().config.text_use_embed;
+    // 先修复未闭合的代码块等问题，避免Discord中整段显示错乱
+    let markdown = repair_markdown(markdown).markdown;
+    let markdown = match notice {
+        Some(notice) => format!("{}\n\n{}", notice, markdown),
+        None => markdown,
+    };
+
+    if !use_embed {
+        for part in split_message(&to_discord_markdown(&markdown), MESSAGE_LIMIT) {
+            // 回答内容不应触发任何提及
+            ctx.send(|reply| reply.content(part).allowed_mentions(|m| m.empty_parse()))
+                .await?;
+        }
+        return Ok(());
+    }
+
+    for segment in to_discord_segments(&markdown) {
+        match segment {
+            TextSegment::Markdown(text) => {
+                for part in split_message(&text, EMBED_LIMIT) {
+                    ctx.send(|reply| {
+                        reply
+                            .embed(|e| e.description(part).color(0x3498db))
+                            .allowed_mentions(|m| m.empty_parse())
+                    })
+                    .await?;
+                }
+            }
+            TextSegment::Fields(fields) => {
+                ctx.send(|reply| {
+                    reply
+                        .embed(|e| {
+                            e.fields(fields.into_iter().map(|(n, v)| (n, v, false)))
+                                .color(0x3498db)
+                        })
+                        .allowed_mentions(|m| m.empty_parse())
+                })
+                .await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// 渲染回答图片并按上传限制压缩，返回待发送的图片路径与会话中保存的全部图片文件名
+//
+// `earlier` 为本次问答之前的对话，多轮会话中最后几条作为上下文显示在回答上方。
+async fn render_answer_images(
+    ctx: Context<'_>,
+    question: &str,
+    session_id: &str,
+    markdown: &str,
+    earlier: &[Turn],
+) -> Result<(Vec<PathBuf>, Vec<String>)> {
+    let api_client = &ctx.data().api_client;
+    let session_dir = api_client.session_manager.get_session_dir(session_id);
+    let image_path = session_dir.join(format!("response_{}.png", Uuid::new_v4()));
+    let meta = answer_meta(ctx, question, session_id).await;
+    let markdown = &markdown_with_context(earlier, markdown, RENDER_CONTEXT_TURNS);
+    // 预先下载回答中引用的图片，渲染时内联显示
+    let images = api_client
+        .image_generator
+        .fetch_remote_images(markdown)
+        .await;
+    let profile = user_render_profile(ctx);
+    let image_paths = api_client.image_generator.create_images_from_markdown(
+        markdown,
+        &image_path,
+        Some(&meta),
+        &images,
+        &profile,
+    )?;
+    let fitted_paths = fit_images_to_upload_limit(ctx, &image_paths)?;
+    let files = [image_paths, fitted_paths.clone()].concat();
+    api_client
+        .session_manager
+        .record_artifacts(session_id, &files);
+    let file_names = files
+        .iter()
+        .filter_map(|path| path.file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .collect();
+    Ok((fitted_paths, file_names))
+}
+
+// 按用户的图片布局偏好获取渲染参数
+fn user_render_profile(ctx: Context<'_>) -> RenderProfile {
+    let api_client = &ctx.data().api_client;
+    let preset = api_client
+        .delivery_preferences
+        .resolve_profile(&ctx.author().id.to_string());
+    api_client.image_generator.render_profile(preset)
+}
+
+// 根据服务器的上传限制压缩图片，多张图片平分同一条消息的限制
+fn fit_images_to_upload_limit(ctx: Context<'_>, image_paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let image_generator = &ctx.data().api_client.image_generator;
+    let upload_limit = upload_limit_bytes(ctx);
+    let mut fitted_paths = Vec::with_capacity(image_paths.len());
+    for chunk in image_paths.chunks(MAX_ATTACHMENTS_PER_MESSAGE) {
+        let per_file_limit = upload_limit / chunk.len() as u64;
+        for path in chunk {
+            fitted_paths.push(image_generator.fit_to_upload_limit(path, per_file_limit)?);
+        }
+    }
+    Ok(fitted_paths)
+}
+
 // 安全截断字符串助手函数
 fn truncate(s: &str, max_len: usize) -> &str {
     if s.chars().count() <= max_len {
@@ -21,8 +221,52 @@ fn truncate(s: &str, max_len: usize) -> &str {
     }
 }
 
+// 构造带图片的用户消息
+fn user_message(question: &str, image_urls: &[String]) -> FastGPTMessage {
+    let mut content_array = Vec::new();
+    content_array.push(json!({"type":"text","text": question}));
+    for url in image_urls {
+        content_array.push(json!({"type":"image_url","image_url":{"url": url}}));
+    }
+    FastGPTMessage {
+        role: "user".into(),
+        content: json!(content_array),
+    }
+}
+
+// 将对话记录转换为发送给 FastGPT 的消息
+fn transcript_messages(turns: &[Turn]) -> Vec<FastGPTMessage> {
+    turns
+        .iter()
+        .map(|turn| match turn.role {
+            TurnRole::User => user_message(&turn.content, &turn.attachments),
+            TurnRole::Assistant => FastGPTMessage {
+                role: "assistant".into(),
+                content: json!(turn.content),
+            },
+        })
+        .collect()
+}
+
+// 去掉最后一组问答，得到其之前的对话
+fn earlier_turns(turns: &[Turn]) -> &[Turn] {
+    let last_question = turns
+        .iter()
+        .rposition(|turn| turn.role == TurnRole::User)
+        .unwrap_or(turns.len());
+    &turns[..last_question]
+}
+
 /// 新增通用问答流程，支持最多10张图片
-async fn run_qa_flow(ctx: Context<'_>, question: String, image_urls: Vec<String>) -> Result<()> {
+///
+/// `continue_session` 为要继续追问的会话，之前的对话会放在本次问题之前一并发送，
+/// 问答追加到该会话的对话记录中；为 `None` 时创建新的会话。
+async fn run_qa_flow(
+    ctx: Context<'_>,
+    question: String,
+    image_urls: Vec<String>,
+    continue_session: Option<String>,
+) -> Result<()> {
     // 获取用户ID和 API 客户端
     let user_id = ctx.author().id.to_string();
     debug!(
@@ -32,16 +276,19 @@ async fn run_qa_flow(ctx: Context<'_>, question: String, image_urls: Vec<String>
         image_urls.len()
     );
     let api_client = &ctx.data().api_client;
+    // 继续追问时读取之前的对话
+    let history = match &continue_session {
+        Some(session_id) => {
+            api_client
+                .session_manager
+                .load_transcript(session_id)
+                .await?
+        }
+        None => Vec::new(),
+    };
     // 构造 FastGPT 消息体
-    let mut content_array = Vec::new();
-    content_array.push(json!({"type":"text","text": question.clone()}));
-    for url in &image_urls {
-        content_array.push(json!({"type":"image_url","image_url":{"url": url}}));
-    }
-    let messages = vec![FastGPTMessage {
-        role: "user".into(),
-        content: json!(content_array),
-    }];
+    let mut messages = transcript_messages(&history);
+    messages.push(user_message(&question, &image_urls));
     // 发送嵌入式初始确认消息
     let initial_msg = ctx
         .send(|reply| {
@@ -52,8 +299,19 @@ async fn run_qa_flow(ctx: Context<'_>, question: String, image_urls: Vec<String>
             })
         })
         .await?;
-    // 创建新的会话并记录
-    let session_id = api_client.session_manager.create_session(&user_id)?;
+    // 创建新的会话（或继续已有会话）并记录来源
+    let session_id = match continue_session {
+        Some(session_id) => session_id,
+        None => api_client.session_manager.create_session(&user_id)?,
+    };
+    update_session_meta(ctx, &session_id, |meta| {
+        meta.guild_id = ctx.guild_id().map(|id| id.to_string());
+        meta.channel_id = Some(ctx.channel_id().to_string());
+        meta.message_id = Some(ctx.id().to_string());
+        meta.app_name = ctx.data().config.fastgpt_app_name.clone();
+        meta.status = SessionStatus::Pending;
+        meta.error = None;
+    });
     // 信息级别：记录简要提问
     info!(
         "用户{} 提问: {}",
@@ -62,14 +320,14 @@ async fn run_qa_flow(ctx: Context<'_>, question: String, image_urls: Vec<String>
     );
     // 调用 FastGPT 获取对话响应，启用流式与详细模式
     let status_lines: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
-    let chat_resp = api_client
+    let started = Instant::now();
+    let chat_result = api_client
         .get_chat_response(None, None, messages, true, true, None, {
             let status_lines = Arc::clone(&status_lines);
-            let ctx = ctx.clone();
             let initial_msg = initial_msg.clone();
             move |evt, data| {
                 let status_lines = Arc::clone(&status_lines);
-                let ctx = ctx.clone();
+                let ctx = ctx;
                 let evt = evt.to_string();
                 let data = data.to_string();
                 let msg = initial_msg.clone();
@@ -91,7 +349,7 @@ async fn run_qa_flow(ctx: Context<'_>, question: String, image_urls: Vec<String>
                                         lines.push(format!("🔄 丨{}", name));
                                         lines.join("\n")
                                     };
-                                    msg.edit(ctx.clone(), |m| {
+                                    msg.edit(ctx, |m| {
                                         m.embed(|e| {
                                             e.title("运行状态")
                                                 .description(description.clone())
@@ -107,12 +365,25 @@ async fn run_qa_flow(ctx: Context<'_>, question: String, image_urls: Vec<String>
                 }
             }
         })
-        .await?;
+        .await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+    let chat_resp = match chat_result {
+        Ok(chat_resp) => chat_resp,
+        Err(e) => {
+            mark_session(ctx, &session_id, SessionStatus::Error, Some(&e));
+            return Err(e);
+        }
+    };
+    update_session_meta(ctx, &session_id, |meta| {
+        meta.usage = chat_resp.usage;
+        meta.latency_ms = Some(latency_ms);
+    });
     // 检查回复是否为空
     if chat_resp.content.trim().is_empty() {
         debug!("回复内容为空，取消后续操作");
+        mark_session(ctx, &session_id, SessionStatus::Cancelled, None);
         initial_msg
-            .edit(ctx.clone(), |m| {
+            .edit(ctx, |m| {
                 m.embed(|e| {
                     e.title("错误")
                         .description("未收到有效回复，已取消图片生成。")
@@ -126,7 +397,7 @@ async fn run_qa_flow(ctx: Context<'_>, question: String, image_urls: Vec<String>
     {
         let history = status_lines.lock().unwrap().join("\n");
         initial_msg
-            .edit(ctx.clone(), |m| {
+            .edit(ctx, |m| {
                 m.embed(|e| {
                     e.title("运行状态")
                         .description([history, "✅ 接收到fastgpt完整响应！".to_string()].join("\n"))
@@ -135,24 +406,34 @@ async fn run_qa_flow(ctx: Context<'_>, question: String, image_urls: Vec<String>
             })
             .await?;
     }
-    // 保存用户输入、响应和图片链接
-    api_client
-        .session_manager
-        .save_user_input(&session_id, &question)
-        .await?;
+    // 将本次提问追加到对话记录，回答在生成图片后连同图片文件名一起追加
     api_client
         .session_manager
-        .save_response_markdown(&session_id, &chat_resp.content)
-        .await?;
-    api_client
-        .session_manager
-        .save_user_images(&session_id, &image_urls)
+        .append_turn(
+            &session_id,
+            Turn::user(&question, &image_urls, Some(ctx.id().to_string())),
+        )
         .await?;
+    // 按个人或服务器偏好选择发送方式
+    let guild_id = ctx.guild_id().map(|id| id.to_string());
+    let mode = api_client
+        .delivery_preferences
+        .resolve(&user_id, guild_id.as_deref());
+    if mode == DeliveryMode::Text {
+        api_client
+            .session_manager
+            .append_turn(&session_id, Turn::assistant(&chat_resp.content))
+            .await?;
+        mark_session(ctx, &session_id, SessionStatus::Ok, None);
+        initial_msg.delete(ctx).await?;
+        send_text_answer(ctx, &chat_resp.content, None).await?;
+        return Ok(());
+    }
     // 更新状态：图片生成中
     {
         let history = status_lines.lock().unwrap().join("\n");
         initial_msg
-            .edit(ctx.clone(), |m| {
+            .edit(ctx, |m| {
                 m.embed(|e| {
                     e.title("运行状态")
                         .description([history, "图片生成中...".to_string()].join("\n"))
@@ -161,17 +442,45 @@ async fn run_qa_flow(ctx: Context<'_>, question: String, image_urls: Vec<String>
             })
             .await?;
     }
-    // 生成图片
-    let session_dir = api_client.session_manager.get_session_dir(&session_id);
-    let image_path = session_dir.join(format!("response_{}.png", Uuid::new_v4()));
+    // 生成图片，失败时按配置改为文字回答
+    let rendered =
+        render_answer_images(ctx, &question, &session_id, &chat_resp.content, &history).await;
+    let image_files = match &rendered {
+        Ok((_, files)) => files.clone(),
+        Err(_) => Vec::new(),
+    };
     api_client
-        .image_generator
-        .create_image_from_markdown(&chat_resp.content, &image_path)?;
+        .session_manager
+        .append_turn(
+            &session_id,
+            Turn::assistant(&chat_resp.content).with_attachments(image_files),
+        )
+        .await?;
+    let fitted_paths = match rendered {
+        Ok((paths, _)) => paths,
+        Err(e) if ctx.data().config.text_fallback => {
+            error!("图片生成失败，改为文字回答: {:?}", e);
+            mark_session(ctx, &session_id, SessionStatus::Ok, None);
+            initial_msg.delete(ctx).await?;
+            send_text_answer(
+                ctx,
+                &chat_resp.content,
+                Some("⚠️ 图片生成失败，已改为文字回答"),
+            )
+            .await?;
+            return Ok(());
+        }
+        Err(e) => {
+            mark_session(ctx, &session_id, SessionStatus::Error, Some(&e));
+            return Err(e);
+        }
+    };
+    mark_session(ctx, &session_id, SessionStatus::Ok, None);
     // 更新状态：图片生成完成
     {
         let history = status_lines.lock().unwrap().join("\n");
         initial_msg
-            .edit(ctx.clone(), |m| {
+            .edit(ctx, |m| {
                 m.embed(|e| {
                     e.title("运行状态")
                         .description([history, "图片生成完成！".to_string()].join("\n"))
@@ -180,13 +489,249 @@ async fn run_qa_flow(ctx: Context<'_>, question: String, image_urls: Vec<String>
             })
             .await?;
     }
-    // 删除初始消息并发送最终图片回复
-    initial_msg.delete(ctx.clone()).await?;
-    ctx.send(|reply| reply.attachment(serenity::AttachmentType::Path(&image_path)))
+    // 图片之后按配置附上原始Markdown与代码文件
+    let config = &ctx.data().config;
+    let code_blocks = extract_code_blocks(&chat_resp.content);
+    let mut files: Vec<serenity::AttachmentType> = Vec::new();
+    if config.attach_markdown {
+        files.push(serenity::AttachmentType::Bytes {
+            data: chat_resp.content.as_bytes().into(),
+            filename: "answer.md".to_string(),
+        });
+    }
+    if config.code_attachments == CodeAttachMode::Auto {
+        files.extend(code_blocks.iter().map(code_attachment));
+    }
+    let show_code_button =
+        config.code_attachments == CodeAttachMode::Button && !code_blocks.is_empty();
+
+    // 图片已按每条消息的上传限制压缩，文件另起消息发送，不占用图片消息的限额
+    let images: Vec<serenity::AttachmentType> = fitted_paths
+        .iter()
+        .map(|path| serenity::AttachmentType::Path(path))
+        .collect();
+    let chunks: Vec<&[serenity::AttachmentType]> = images
+        .chunks(MAX_ATTACHMENTS_PER_MESSAGE)
+        .chain(files.chunks(MAX_ATTACHMENTS_PER_MESSAGE))
+        .collect();
+
+    // 删除初始消息并发送最终回复，多页时合并为一条消息（每条最多10个附件）
+    // 最后一条消息附带导出PDF与获取代码按钮
+    initial_msg.delete(ctx).await?;
+    let chunk_count = chunks.len();
+    for (i, chunk) in chunks.into_iter().enumerate() {
+        ctx.send(|reply| {
+            for attachment in chunk {
+                reply.attachment(attachment.clone());
+            }
+            if i + 1 == chunk_count {
+                reply.components(|c| {
+                    c.create_action_row(|row| {
+                        row.create_button(|b| {
+                            b.custom_id(format!("pdf_{}", session_id))
+                                .label("导出PDF")
+                                .emoji('📄')
+                                .style(serenity::ButtonStyle::Secondary)
+                        });
+                        if show_code_button {
+                            row.create_button(|b| {
+                                b.custom_id(format!("code_{}", session_id))
+                                    .label("获取代码")
+                                    .emoji('📋')
+                                    .style(serenity::ButtonStyle::Secondary)
+                            });
+                        }
+                        row
+                    })
+                });
+            }
+            reply
+        })
+        .await?;
+    }
+    Ok(())
+}
+
+// 记录会话的最终状态，元数据写入失败不影响回答
+fn mark_session(
+    ctx: Context<'_>,
+    session_id: &str,
+    status: SessionStatus,
+    error: Option<&anyhow::Error>,
+) {
+    update_session_meta(ctx, session_id, |meta| {
+        meta.status = status;
+        meta.error = error.map(|e| e.to_string());
+    });
+}
+
+// 更新会话元数据，session.json 写入失败只记录警告，不影响回答
+fn update_session_meta(ctx: Context<'_>, session_id: &str, update: impl FnOnce(&mut SessionMeta)) {
+    let result = ctx
+        .data()
+        .api_client
+        .session_manager
+        .update_meta(session_id, update);
+    if let Err(e) = result {
+        warn!("更新会话 {} 元数据失败: {:?}", session_id, e);
+    }
+}
+
+// 将代码块转换为附件
+fn code_attachment(block: &CodeBlock) -> serenity::AttachmentType<'_> {
+    serenity::AttachmentType::Bytes {
+        data: block.code.as_bytes().into(),
+        filename: block.filename.clone(),
+    }
+}
+
+/// 处理"获取代码"按钮：将回答中的代码块作为文件仅发送给点击者
+///
+/// custom_id 格式: code_{session_id}
+pub(super) async fn handle_code_export(
+    ctx: &serenity::Context,
+    component: &serenity::MessageComponentInteraction,
+    data: &super::Data,
+) -> Result<()> {
+    let session_id = component.data.custom_id.trim_start_matches("code_");
+    let blocks = match data
+        .api_client
+        .session_manager
+        .load_response(session_id)
+        .await
+    {
+        Ok((_, markdown)) => extract_code_blocks(&markdown),
+        Err(e) => {
+            component
+                .create_interaction_response(&ctx.http, |response| {
+                    response
+                        .kind(serenity::InteractionResponseType::ChannelMessageWithSource)
+                        .interaction_response_data(|m| {
+                            m.content(format!("❌ 获取代码失败: {}", e)).ephemeral(true)
+                        })
+                })
+                .await?;
+            return Err(e);
+        }
+    };
+
+    component
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(serenity::InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|m| {
+                    if blocks.is_empty() {
+                        m.content("📭 该回答中没有代码块");
+                    } else {
+                        for block in blocks.iter().take(MAX_ATTACHMENTS_PER_MESSAGE) {
+                            m.add_file(code_attachment(block));
+                        }
+                        if blocks.len() > MAX_ATTACHMENTS_PER_MESSAGE {
+                            m.content(format!(
+                                "共 {} 个代码块，仅附上前 {} 个",
+                                blocks.len(),
+                                MAX_ATTACHMENTS_PER_MESSAGE
+                            ));
+                        }
+                    }
+                    m.ephemeral(true)
+                })
+        })
         .await?;
     Ok(())
 }
 
+/// 处理"导出PDF"按钮：根据会话保存的 response.md 生成PDF并回复到原消息
+///
+/// custom_id 格式: pdf_{session_id}
+pub(super) async fn handle_pdf_export(
+    ctx: &serenity::Context,
+    component: &serenity::MessageComponentInteraction,
+    data: &super::Data,
+) -> Result<()> {
+    let session_id = component.data.custom_id.trim_start_matches("pdf_");
+    let api_client = &data.api_client;
+
+    // PDF生成可能超过3秒，先延迟响应
+    component
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(serenity::InteractionResponseType::DeferredChannelMessageWithSource)
+                .interaction_response_data(|m| m.ephemeral(true))
+        })
+        .await?;
+
+    let result = async {
+        let (question, markdown) = api_client.session_manager.load_response(session_id).await?;
+        let meta = AnswerMeta {
+            question: Some(question).filter(|q| !q.trim().is_empty()),
+            session_id: Some(session_id.to_string()),
+            app_name: data.config.fastgpt_app_name.clone(),
+            guild_name: component
+                .guild_id
+                .and_then(|id| ctx.cache.guild(id))
+                .map(|g| g.name),
+            ..Default::default()
+        };
+        let images = api_client
+            .image_generator
+            .fetch_remote_images(&markdown)
+            .await;
+        let output_path = api_client
+            .session_manager
+            .get_session_dir(session_id)
+            .join("answer.pdf");
+        // wkhtmltopdf 是阻塞调用，放到阻塞线程池中执行
+        let pdf_path = tokio::task::spawn_blocking({
+            let api_client = Arc::clone(api_client);
+            move || {
+                api_client.image_generator.create_pdf_from_markdown(
+                    &markdown,
+                    &output_path,
+                    Some(&meta),
+                    &images,
+                )
+            }
+        })
+        .await??;
+        api_client
+            .session_manager
+            .record_artifacts(session_id, &[&pdf_path]);
+
+        let tier = component
+            .guild_id
+            .and_then(|id| ctx.cache.guild(id))
+            .map(|g| g.premium_tier);
+        let size = std::fs::metadata(&pdf_path)?.len();
+        if size > upload_limit_for_tier(tier) {
+            return Err(anyhow::anyhow!(
+                "PDF大小 {:.2}MB 超过服务器上传限制",
+                size as f64 / 1024.0 / 1024.0
+            ));
+        }
+
+        // 延迟响应是仅自己可见的，PDF改为回复原回答消息，让频道内其他人也能看到并与回答关联
+        component
+            .channel_id
+            .send_message(&ctx.http, |m| {
+                m.add_file(serenity::AttachmentType::Path(&pdf_path))
+                    .reference_message(&component.message)
+            })
+            .await?;
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+
+    let content = match &result {
+        Ok(_) => "✅ PDF已生成".to_string(),
+        Err(e) => format!("❌ 导出PDF失败: {}", e),
+    };
+    component
+        .edit_original_interaction_response(&ctx.http, |m| m.content(content))
+        .await?;
+    result
+}
+
 /// 向AI提问并获取图片形式的回答
 #[poise::command(slash_command, rename = "答疑bot")]
 pub async fn qa_bot(
@@ -201,7 +746,126 @@ pub async fn qa_bot(
         .iter()
         .filter_map(|opt| opt.clone())
         .collect();
-    run_qa_flow(ctx, 问题, api_image_urls).await?;
+    run_qa_flow(ctx, 问题, api_image_urls, None).await?;
+    Ok(())
+}
+
+/// 回答发送方式选项
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum DeliveryChoice {
+    #[name = "图片"]
+    Image,
+    #[name = "文字"]
+    Text,
+    #[name = "恢复默认"]
+    Default,
+}
+
+/// 偏好设置的作用范围
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum PreferenceScope {
+    #[name = "个人"]
+    User,
+    #[name = "服务器"]
+    Guild,
+}
+
+/// 设置回答的发送方式（图片或文字）
+#[poise::command(slash_command, rename = "回答模式")]
+pub async fn delivery_mode(
+    ctx: Context<'_>,
+    #[description = "回答的发送方式"] 模式: DeliveryChoice,
+    #[description = "设置范围，默认为个人"] 范围: Option<PreferenceScope>,
+) -> Result<()> {
+    let (mode, mode_label) = match 模式 {
+        DeliveryChoice::Image => (Some(DeliveryMode::Image), "图片"),
+        DeliveryChoice::Text => (Some(DeliveryMode::Text), "文字"),
+        DeliveryChoice::Default => (None, "默认"),
+    };
+    let preferences = &ctx.data().api_client.delivery_preferences;
+    let scope_label = match 范围.unwrap_or(PreferenceScope::User) {
+        PreferenceScope::User => {
+            preferences.set_user(&ctx.author().id.to_string(), mode)?;
+            "你的"
+        }
+        PreferenceScope::Guild => {
+            let guild_id = match ctx.guild_id() {
+                Some(id) => id,
+                None => {
+                    ctx.send(|r| r.content("❌ 服务器设置只能在服务器中使用").ephemeral(true))
+                        .await?;
+                    return Ok(());
+                }
+            };
+            // 仅允许拥有"管理服务器"权限的成员修改服务器设置
+            let can_manage = match ctx.author_member().await {
+                Some(member) => member
+                    .permissions(ctx.serenity_context())
+                    .map(|p| p.manage_guild())
+                    .unwrap_or(false),
+                None => false,
+            };
+            if !can_manage {
+                ctx.send(|r| {
+                    r.content("❌ 只有拥有“管理服务器”权限的成员可以修改服务器设置")
+                        .ephemeral(true)
+                })
+                .await?;
+                return Ok(());
+            }
+            preferences.set_guild(&guild_id.to_string(), mode)?;
+            "本服务器的"
+        }
+    };
+    info!(
+        "用户 {} 将{}回答模式设置为: {}",
+        ctx.author().id,
+        scope_label,
+        mode_label
+    );
+    ctx.send(|r| {
+        r.content(format!(
+            "✅ 已将{}回答模式设置为：{}",
+            scope_label, mode_label
+        ))
+        .ephemeral(true)
+    })
+    .await?;
+    Ok(())
+}
+
+/// 图片布局选项
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum LayoutChoice {
+    #[name = "桌面"]
+    Desktop,
+    #[name = "手机"]
+    Mobile,
+    #[name = "恢复默认"]
+    Default,
+}
+
+/// 设置回答图片的布局（桌面宽图或手机窄图）
+#[poise::command(slash_command, rename = "图片布局")]
+pub async fn render_layout(
+    ctx: Context<'_>,
+    #[description = "手机布局画布更窄、字号更大，手机上无需放大即可阅读"] 布局: LayoutChoice,
+) -> Result<()> {
+    let (preset, label) = match 布局 {
+        LayoutChoice::Desktop => (Some(RenderPreset::Desktop), "桌面"),
+        LayoutChoice::Mobile => (Some(RenderPreset::Mobile), "手机"),
+        LayoutChoice::Default => (None, "默认"),
+    };
+    ctx.data()
+        .api_client
+        .delivery_preferences
+        .set_user_profile(&ctx.author().id.to_string(), preset)?;
+    info!("用户 {} 将图片布局设置为: {}", ctx.author().id, label);
+    ctx.send(|r| {
+        r.content(format!("✅ 已将你的图片布局设置为：{}", label))
+            .ephemeral(true)
+    })
+    .await?;
     Ok(())
 }
 
@@ -217,7 +881,8 @@ pub async fn history_sessions(ctx: Context<'_>) -> Result<()> {
         .data()
         .api_client
         .session_manager
-        .get_user_sessions(&user_id);
+        .get_user_sessions(&user_id)
+        .await;
 
     // 如果没有会话，直接提示
     if sessions.is_empty() {
@@ -228,7 +893,7 @@ pub async fn history_sessions(ctx: Context<'_>) -> Result<()> {
     // 分页参数
     let per_page = 10;
     let total = sessions.len();
-    let total_pages = (total + per_page - 1) / per_page;
+    let total_pages = total.div_ceil(per_page);
     let page = 0;
     let start = page * per_page;
     let end = ((page + 1) * per_page).min(total);
@@ -271,6 +936,932 @@ pub async fn history_sessions(ctx: Context<'_>) -> Result<()> {
     Ok(())
 }
 
+/// 按关键词搜索自己的历史问答
+#[poise::command(slash_command, rename = "搜索历史")]
+pub async fn search_history(
+    ctx: Context<'_>,
+    #[description = "关键词，多个关键词用空格分隔"] 关键词: String,
+    #[description = "(可选) 开始日期，格式 2024-01-31"] 开始日期: Option<String>,
+    #[description = "(可选) 结束日期（包含当天），格式 2024-01-31"] 结束日期: Option<String>,
+    #[description = "(可选) 只搜索指定应用的回答"] 应用: Option<String>,
+) -> Result<()> {
+    use futures::StreamExt;
+
+    // 搜索结果只对自己可见
+    ctx.defer_ephemeral().await?;
+
+    let keywords = SearchQuery::parse_keywords(&关键词);
+    if keywords.is_empty() {
+        ctx.say("❌ 请输入至少一个关键词").await?;
+        return Ok(());
+    }
+    let from = match 开始日期.as_deref().map(parse_search_date).transpose() {
+        Ok(date) => date,
+        Err(e) => {
+            ctx.say(format!("❌ {}", e)).await?;
+            return Ok(());
+        }
+    };
+    // 结束日期包含当天，查询时取次日零点
+    let to = match 结束日期.as_deref().map(parse_search_date).transpose() {
+        Ok(date) => date.map(|d| d + chrono::Duration::days(1)),
+        Err(e) => {
+            ctx.say(format!("❌ {}", e)).await?;
+            return Ok(());
+        }
+    };
+    let query = SearchQuery {
+        user_id: ctx.author().id.to_string(),
+        keywords,
+        from,
+        to,
+        app_name: 应用.map(|a| a.trim().to_string()).filter(|a| !a.is_empty()),
+    };
+    info!("用户 {} 搜索历史: {:?}", ctx.author().name, query.keywords);
+
+    let session_manager = &ctx.data().api_client.session_manager;
+    let mut page = 0;
+    let mut result = session_manager
+        .search(query.clone(), 0, SEARCH_PAGE_SIZE)
+        .await?;
+    if result.total == 0 {
+        ctx.say("🔍 没有找到匹配的历史问答。").await?;
+        return Ok(());
+    }
+
+    let handle = ctx
+        .send(|r| {
+            r.embed(|e| search_embed(e, &query, &result, page))
+                .components(|c| search_buttons(c, page, result.total))
+        })
+        .await?;
+    if result.total <= SEARCH_PAGE_SIZE {
+        return Ok(());
+    }
+
+    // 搜索条件无法放进按钮ID，在此等待翻页按钮
+    let message = handle.message().await?;
+    let mut presses = message
+        .await_component_interactions(ctx.serenity_context())
+        .author_id(ctx.author().id)
+        .timeout(std::time::Duration::from_secs(
+            SEARCH_PAGINATION_TIMEOUT_SECS,
+        ))
+        .build();
+    while let Some(press) = presses.next().await {
+        let total_pages = result.total.div_ceil(SEARCH_PAGE_SIZE);
+        page = match press.data.custom_id.as_str() {
+            "search_prev" => page.saturating_sub(1),
+            "search_next" => (page + 1).min(total_pages.saturating_sub(1)),
+            _ => continue,
+        };
+        result = session_manager
+            .search(query.clone(), page * SEARCH_PAGE_SIZE, SEARCH_PAGE_SIZE)
+            .await?;
+        press
+            .create_interaction_response(&ctx.serenity_context().http, |response| {
+                response
+                    .kind(serenity::InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|m| {
+                        m.embed(|e| search_embed(e, &query, &result, page))
+                            .components(|c| search_buttons(c, page, result.total))
+                    })
+            })
+            .await?;
+    }
+
+    // 超时后移除翻页按钮
+    handle.edit(ctx, |r| r.components(|c| c)).await?;
+    Ok(())
+}
+
+// 解析 YYYY-MM-DD 格式的日期，返回本地时间当天零点
+fn parse_search_date(text: &str) -> Result<DateTime<Utc>> {
+    let date = NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("日期格式应为 YYYY-MM-DD，例如 2024-01-31: {}", text))?;
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok_or_else(|| anyhow::anyhow!("无效的日期: {}", text))
+}
+
+// 生成搜索结果的嵌入消息
+fn search_embed<'a>(
+    e: &'a mut serenity::CreateEmbed,
+    query: &SearchQuery,
+    result: &SearchPage,
+    page: usize,
+) -> &'a mut serenity::CreateEmbed {
+    let total_pages = result.total.div_ceil(SEARCH_PAGE_SIZE);
+    let description = result
+        .hits
+        .iter()
+        .enumerate()
+        .map(|(i, hit)| {
+            let mut entry = format!(
+                "**{}.** `{}` · {}",
+                page * SEARCH_PAGE_SIZE + i + 1,
+                short_session_id(&hit.session_id),
+                format_time(hit.created_at)
+            );
+            if let Some(app_name) = &hit.app_name {
+                entry.push_str(&format!(" · {}", app_name));
+            }
+            entry.push_str(&format!("\n问：{}", hit.input_snippet));
+            if let Some(snippet) = &hit.response_snippet {
+                entry.push_str(&format!("\n答：{}", snippet));
+            }
+            entry
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    e.title(format!("🔍 搜索结果：{}", query.keywords.join(" ")))
+        .color(0x3498db)
+        .description(description)
+        .footer(|f| {
+            f.text(format!(
+                "第 {}/{} 页 · 共 {} 条结果",
+                page + 1,
+                total_pages,
+                result.total
+            ))
+        })
+}
+
+// 生成搜索结果的翻页按钮，只有一页时不显示
+fn search_buttons(
+    c: &mut serenity::CreateComponents,
+    page: usize,
+    total: usize,
+) -> &mut serenity::CreateComponents {
+    let total_pages = total.div_ceil(SEARCH_PAGE_SIZE);
+    if total_pages <= 1 {
+        return c;
+    }
+    c.create_action_row(|row| {
+        row.create_button(|b| {
+            b.custom_id("search_prev")
+                .label("上一页")
+                .style(serenity::ButtonStyle::Secondary)
+                .disabled(page == 0)
+        })
+        .create_button(|b| {
+            b.custom_id("search_next")
+                .label("下一页")
+                .style(serenity::ButtonStyle::Secondary)
+                .disabled(page + 1 >= total_pages)
+        })
+    })
+}
+
+// 自动补全用户自己的会话ID，可按ID前缀或问题内容筛选
+async fn autocomplete_session_id(
+    ctx: Context<'_>,
+    partial: &str,
+) -> impl Iterator<Item = poise::AutocompleteChoice<String>> {
+    let partial = partial.trim().to_lowercase();
+    let sessions = ctx
+        .data()
+        .api_client
+        .session_manager
+        .get_user_sessions(&ctx.author().id.to_string())
+        .await;
+    sessions
+        .into_iter()
+        .filter(move |s| {
+            s.id.starts_with(&partial) || s.input_preview.to_lowercase().contains(&partial)
+        })
+        .take(25)
+        .map(|s| {
+            let short_id = short_session_id(&s.id).to_string();
+            let name = format!(
+                "{} · {} · {}",
+                short_id,
+                format_time(s.last_modified),
+                s.input_preview
+            );
+            poise::AutocompleteChoice {
+                name: truncate(&name, 100).to_string(),
+                value: short_id,
+            }
+        })
+}
+
+/// 查看历史会话，重新发送回答图片
+#[poise::command(slash_command, rename = "查看会话")]
+pub async fn view_session(
+    ctx: Context<'_>,
+    #[description = "会话ID，可只输入前几位"]
+    #[autocomplete = "autocomplete_session_id"]
+    会话: String,
+) -> Result<()> {
+    use futures::StreamExt;
+
+    // 会话内容只对自己可见，公开需点击分享
+    ctx.defer_ephemeral().await?;
+
+    let user_id = ctx.author().id.to_string();
+    let session_manager = &ctx.data().api_client.session_manager;
+    let session_id = match session_manager.find_user_session(&user_id, &会话).await {
+        Ok(session_id) => session_id,
+        Err(e) => {
+            ctx.say(format!("❌ {}", e)).await?;
+            return Ok(());
+        }
+    };
+    let (question, markdown) = match session_manager.load_response(&session_id).await {
+        Ok(response) => response,
+        Err(e) => {
+            warn!("读取会话 {} 的回答失败: {:?}", session_id, e);
+            ctx.say("❌ 该会话没有保存回答，无法查看").await?;
+            return Ok(());
+        }
+    };
+    let image_urls = session_manager.load_user_images(&session_id).await?;
+    let meta = session_manager.read_meta(&session_id)?;
+    let turns = session_manager.load_transcript(&session_id).await?;
+    info!("用户 {} 查看会话 {}", ctx.author().name, session_id);
+
+    let (images, rerendered) =
+        session_answer_images(ctx, &session_id, &question, &markdown, &turns).await?;
+    let web_url = meta
+        .share
+        .as_ref()
+        .and_then(|share| share_url(&ctx.data().config, &share.token));
+
+    // 第一条消息附带会话信息，最后一条消息附带追问与分享按钮
+    let mut chunks: Vec<&[PathBuf]> = images.chunks(MAX_ATTACHMENTS_PER_MESSAGE).collect();
+    if chunks.is_empty() {
+        chunks.push(&[]);
+    }
+    let mut handle = None;
+    for (i, chunk) in chunks.iter().enumerate() {
+        let last = i + 1 == chunks.len();
+        let reply = ctx
+            .send(|r| {
+                if i == 0 {
+                    r.embed(|e| {
+                        view_session_embed(
+                            e,
+                            &session_id,
+                            &question,
+                            &image_urls,
+                            &meta,
+                            web_url.as_deref(),
+                            rerendered,
+                        )
+                    });
+                }
+                for path in chunk.iter() {
+                    r.attachment(serenity::AttachmentType::Path(path));
+                }
+                if last {
+                    r.components(|c| view_session_buttons(c, false));
+                }
+                r.ephemeral(true)
+            })
+            .await?;
+        handle = Some(reply);
+    }
+    let Some(handle) = handle else {
+        return Ok(());
+    };
+
+    // 按钮只在本次查看中有效，超时后移除
+    let http = &ctx.serenity_context().http;
+    let message = handle.message().await?;
+    let mut presses = message
+        .await_component_interactions(ctx.serenity_context())
+        .author_id(ctx.author().id)
+        .timeout(std::time::Duration::from_secs(VIEW_SESSION_TIMEOUT_SECS))
+        .build();
+    while let Some(press) = presses.next().await {
+        match press.data.custom_id.as_str() {
+            "view_share" => {
+                press
+                    .create_interaction_response(http, |response| {
+                        response
+                            .kind(serenity::InteractionResponseType::UpdateMessage)
+                            .interaction_response_data(|m| {
+                                m.components(|c| view_session_buttons(c, true))
+                            })
+                    })
+                    .await?;
+                share_answer(ctx, &question, &images).await?;
+                info!("用户 {} 公开分享了会话 {}", ctx.author().name, session_id);
+            }
+            "view_followup" => {
+                let modal_id = format!("view_followup_{}", press.id);
+                press
+                    .create_interaction_response(http, |response| {
+                        response
+                            .kind(serenity::InteractionResponseType::Modal)
+                            .interaction_response_data(|m| {
+                                m.custom_id(&modal_id).title("继续追问").components(|c| {
+                                    c.create_action_row(|row| {
+                                        row.create_input_text(|t| {
+                                            t.custom_id("question")
+                                                .label("追问内容")
+                                                .style(serenity::InputTextStyle::Paragraph)
+                                                .max_length(2000)
+                                                .required(true)
+                                        })
+                                    })
+                                })
+                            })
+                    })
+                    .await?;
+                let submit = serenity::CollectModalInteraction::new(ctx.serenity_context())
+                    .author_id(ctx.author().id)
+                    .filter(move |m| m.data.custom_id == modal_id)
+                    .timeout(std::time::Duration::from_secs(VIEW_SESSION_TIMEOUT_SECS))
+                    .await;
+                let Some(submit) = submit else {
+                    continue;
+                };
+                submit
+                    .create_interaction_response(http, |response| {
+                        response.kind(serenity::InteractionResponseType::DeferredUpdateMessage)
+                    })
+                    .await?;
+                let follow_up = modal_input(&submit, "question");
+                if follow_up.trim().is_empty() {
+                    continue;
+                }
+                // 追问追加到同一会话，之前的对话一并作为上下文发送
+                run_qa_flow(ctx, follow_up, Vec::new(), Some(session_id.clone())).await?;
+            }
+            _ => {}
+        }
+    }
+
+    handle.edit(ctx, |r| r.components(|c| c)).await?;
+    Ok(())
+}
+
+// 会话的回答图片，返回图片路径与是否为重新渲染
+//
+// 图片已被定期清理时根据保存的回答重新渲染，之前的对话作为上下文显示。
+async fn session_answer_images(
+    ctx: Context<'_>,
+    session_id: &str,
+    question: &str,
+    markdown: &str,
+    turns: &[Turn],
+) -> Result<(Vec<PathBuf>, bool)> {
+    let session_manager = &ctx.data().api_client.session_manager;
+    let stored_images = session_manager.answer_images(session_id)?;
+    if stored_images.is_empty() {
+        let (images, _) =
+            render_answer_images(ctx, question, session_id, markdown, earlier_turns(turns)).await?;
+        Ok((images, true))
+    } else {
+        let fitted_paths = fit_images_to_upload_limit(ctx, &stored_images)?;
+        // 压缩后的副本与原图一起记录为产物，清理与删除会话时一并处理
+        let new_files: Vec<&PathBuf> = fitted_paths
+            .iter()
+            .filter(|path| !stored_images.contains(path))
+            .collect();
+        session_manager.record_artifacts(session_id, &new_files);
+        Ok((fitted_paths, false))
+    }
+}
+
+// 生成查看会话的嵌入消息
+fn view_session_embed<'a>(
+    e: &'a mut serenity::CreateEmbed,
+    session_id: &str,
+    question: &str,
+    image_urls: &[String],
+    meta: &SessionMeta,
+    share_url: Option<&str>,
+    rerendered: bool,
+) -> &'a mut serenity::CreateEmbed {
+    let question = if question.trim().is_empty() {
+        "（未保存问题）"
+    } else {
+        truncate(question, 1000)
+    };
+    let image_urls = if image_urls.is_empty() {
+        "无".to_string()
+    } else {
+        image_urls.join("\n")
+    };
+    e.title(format!("📄 会话 {}", short_session_id(session_id)))
+        .color(0x3498db)
+        .field("问题", question, false)
+        .field("图片链接", truncate(&image_urls, 1000), false)
+        .field("时间", format_time(meta.created_at), true);
+    if meta.turns > 2 {
+        e.field("消息数", meta.turns, true);
+    }
+    if let Some(app_name) = &meta.app_name {
+        e.field("应用", app_name, true);
+    }
+    if let Some(share_url) = share_url {
+        e.field("网页分享", share_url, false);
+    }
+    if rerendered {
+        e.footer(|f| f.text("原图片已被清理，已根据保存的回答重新生成"));
+    }
+    e
+}
+
+// 生成查看会话的追问与分享按钮
+fn view_session_buttons(
+    c: &mut serenity::CreateComponents,
+    shared: bool,
+) -> &mut serenity::CreateComponents {
+    c.create_action_row(|row| {
+        row.create_button(|b| {
+            b.custom_id("view_followup")
+                .label("继续追问")
+                .emoji('💬')
+                .style(serenity::ButtonStyle::Primary)
+        })
+        .create_button(|b| {
+            b.custom_id("view_share")
+                .label(if shared { "已分享" } else { "公开分享" })
+                .emoji('📢')
+                .style(serenity::ButtonStyle::Secondary)
+                .disabled(shared)
+        })
+    })
+}
+
+// 读取弹窗中指定输入框的内容
+fn modal_input(submit: &serenity::ModalSubmitInteraction, custom_id: &str) -> String {
+    submit
+        .data
+        .components
+        .iter()
+        .flat_map(|row| row.components.iter())
+        .find_map(|component| match component {
+            serenity::ActionRowComponent::InputText(input) if input.custom_id == custom_id => {
+                Some(input.value.clone())
+            }
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+// 将问答公开发送到当前频道，并注明分享者
+async fn share_answer(ctx: Context<'_>, question: &str, images: &[PathBuf]) -> Result<()> {
+    let header = format!(
+        "📢 <@{}> 分享了一个问答\n**问题：** {}",
+        ctx.author().id,
+        truncate(question, 1500)
+    );
+    let mut chunks: Vec<&[PathBuf]> = images.chunks(MAX_ATTACHMENTS_PER_MESSAGE).collect();
+    if chunks.is_empty() {
+        chunks.push(&[]);
+    }
+    for (i, chunk) in chunks.iter().enumerate() {
+        ctx.channel_id()
+            .send_message(&ctx.serenity_context().http, |m| {
+                if i == 0 {
+                    m.content(&header);
+                }
+                m.add_files(
+                    chunk
+                        .iter()
+                        .map(|path| serenity::AttachmentType::Path(path)),
+                )
+                .allowed_mentions(|a| a.empty_parse())
+            })
+            .await?;
+    }
+    Ok(())
+}
+
+/// 分享方式
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum ShareChoice {
+    #[name = "发送到频道"]
+    Channel,
+    #[name = "网页链接"]
+    Link,
+    #[name = "撤销网页链接"]
+    Revoke,
+}
+
+/// 分享自己的一个历史会话
+#[poise::command(slash_command, rename = "分享")]
+pub async fn share_session(
+    ctx: Context<'_>,
+    #[description = "会话ID，可只输入前几位"]
+    #[autocomplete = "autocomplete_session_id"]
+    会话: String,
+    #[description = "默认发送到当前频道"] 方式: Option<ShareChoice>,
+) -> Result<()> {
+    ctx.defer_ephemeral().await?;
+
+    let user_id = ctx.author().id.to_string();
+    let session_manager = &ctx.data().api_client.session_manager;
+    let session_id = match session_manager.find_user_session(&user_id, &会话).await {
+        Ok(session_id) => session_id,
+        Err(e) => {
+            ctx.say(format!("❌ {}", e)).await?;
+            return Ok(());
+        }
+    };
+    let short_id = short_session_id(&session_id);
+
+    match 方式.unwrap_or(ShareChoice::Channel) {
+        ShareChoice::Channel => {
+            let (question, markdown) = match session_manager.load_response(&session_id).await {
+                Ok(response) => response,
+                Err(e) => {
+                    warn!("读取会话 {} 的回答失败: {:?}", session_id, e);
+                    ctx.say("❌ 该会话没有保存回答，无法分享").await?;
+                    return Ok(());
+                }
+            };
+            let turns = session_manager.load_transcript(&session_id).await?;
+            let (images, _) =
+                session_answer_images(ctx, &session_id, &question, &markdown, &turns).await?;
+            share_answer(ctx, &question, &images).await?;
+            info!("用户 {} 公开分享了会话 {}", ctx.author().name, session_id);
+            ctx.say(format!("📢 已将会话 `{}` 分享到当前频道", short_id))
+                .await?;
+        }
+        ShareChoice::Link => {
+            let config = &ctx.data().config;
+            if config.share_web_addr.is_none() {
+                ctx.say("❌ 机器人未启用分享网页，请联系管理员配置 SHARE_WEB_ADDR")
+                    .await?;
+                return Ok(());
+            }
+            // 监听 0.0.0.0 时不知道对外地址，生成的链接无法访问
+            if share_base_url(config).is_none() {
+                warn!("分享网页监听在未指定地址且未设置 SHARE_BASE_URL，无法生成分享链接");
+                ctx.say("❌ 机器人未配置分享网页的对外地址，请联系管理员设置 SHARE_BASE_URL")
+                    .await?;
+                return Ok(());
+            }
+            let token = session_manager.share_session(&user_id, &session_id)?;
+            let url = share_url(config, &token).unwrap_or_default();
+            ctx.say(format!(
+                "🔗 会话 `{}` 的只读网页：{}\n任何拿到链接的人都可以查看，使用 `/分享 方式:撤销网页链接` 可随时撤销",
+                short_id, url
+            ))
+            .await?;
+        }
+        ShareChoice::Revoke => {
+            let message = if session_manager.revoke_share(&user_id, &session_id)? {
+                format!("✅ 已撤销会话 `{}` 的网页链接，原链接将无法访问", short_id)
+            } else {
+                format!("ℹ️ 会话 `{}` 没有网页链接", short_id)
+            };
+            ctx.say(message).await?;
+        }
+    }
+    Ok(())
+}
+
+/// 删除自己的一个历史会话
+#[poise::command(slash_command, rename = "删除会话")]
+pub async fn delete_session(
+    ctx: Context<'_>,
+    #[description = "会话ID，可只输入前几位"]
+    #[autocomplete = "autocomplete_session_id"]
+    会话: String,
+) -> Result<()> {
+    ctx.defer_ephemeral().await?;
+
+    let user_id = ctx.author().id.to_string();
+    let session_manager = &ctx.data().api_client.session_manager;
+    let session_id = match session_manager.find_user_session(&user_id, &会话).await {
+        Ok(session_id) => session_id,
+        Err(e) => {
+            ctx.say(format!("❌ {}", e)).await?;
+            return Ok(());
+        }
+    };
+    session_manager
+        .delete_session(&user_id, &session_id)
+        .await?;
+    ctx.say(format!(
+        "🗑️ 已删除会话 `{}` 及其全部文件",
+        short_session_id(&session_id)
+    ))
+    .await?;
+    Ok(())
+}
+
+/// 删除自己的全部历史会话与个人设置
+#[poise::command(slash_command, rename = "删除全部数据")]
+pub async fn delete_all_data(ctx: Context<'_>) -> Result<()> {
+    ctx.defer_ephemeral().await?;
+
+    let user_id = ctx.author().id.to_string();
+    let api_client = &ctx.data().api_client;
+    let session_count = api_client
+        .session_manager
+        .get_user_sessions(&user_id)
+        .await
+        .len();
+    let handle = ctx
+        .send(|r| {
+            r.embed(|e| {
+                e.title("⚠️ 确认删除全部数据")
+                    .description(format!(
+                        "将永久删除你的 {} 个历史会话（问题、回答与图片）以及个人偏好设置，无法恢复。\n\
+                         如需保留记录，请先使用 `/导出历史`。",
+                        session_count
+                    ))
+                    .color(0xe74c3c)
+            })
+            .components(|c| delete_all_buttons(c, false))
+            .ephemeral(true)
+        })
+        .await?;
+
+    // 等待确认，超时视为取消
+    let message = handle.message().await?;
+    let press = message
+        .await_component_interaction(ctx.serenity_context())
+        .author_id(ctx.author().id)
+        .timeout(std::time::Duration::from_secs(DELETE_CONFIRM_TIMEOUT_SECS))
+        .await;
+    let confirmed = press
+        .as_ref()
+        .is_some_and(|press| press.data.custom_id == "delete_all_confirm");
+    if let Some(press) = &press {
+        press
+            .create_interaction_response(&ctx.serenity_context().http, |response| {
+                response.kind(serenity::InteractionResponseType::DeferredUpdateMessage)
+            })
+            .await?;
+    }
+    if !confirmed {
+        handle
+            .edit(ctx, |r| {
+                r.embed(|e| {
+                    e.title("已取消")
+                        .description("没有删除任何数据。")
+                        .color(0x95a5a6)
+                })
+                .components(|c| delete_all_buttons(c, true))
+            })
+            .await?;
+        return Ok(());
+    }
+
+    let deleted = api_client
+        .session_manager
+        .delete_user_sessions(&user_id)
+        .await?;
+    api_client.delivery_preferences.forget_user(&user_id)?;
+    info!(
+        "用户 {}({}) 删除了全部数据，共 {} 个会话",
+        ctx.author().name,
+        user_id,
+        deleted
+    );
+    handle
+        .edit(ctx, |r| {
+            r.embed(|e| {
+                e.title("🗑️ 已删除全部数据")
+                    .description(format!("已删除 {} 个历史会话与个人偏好设置。", deleted))
+                    .color(0x2ecc71)
+            })
+            .components(|c| delete_all_buttons(c, true))
+        })
+        .await?;
+    Ok(())
+}
+
+// 生成删除全部数据的确认按钮
+fn delete_all_buttons(
+    c: &mut serenity::CreateComponents,
+    disabled: bool,
+) -> &mut serenity::CreateComponents {
+    c.create_action_row(|row| {
+        row.create_button(|b| {
+            b.custom_id("delete_all_confirm")
+                .label("确认删除")
+                .style(serenity::ButtonStyle::Danger)
+                .disabled(disabled)
+        })
+        .create_button(|b| {
+            b.custom_id("delete_all_cancel")
+                .label("取消")
+                .style(serenity::ButtonStyle::Secondary)
+                .disabled(disabled)
+        })
+    })
+}
+
+/// 导出自己的全部历史会话，以zip文件私信发送
+#[poise::command(slash_command, rename = "导出历史")]
+pub async fn export_history(ctx: Context<'_>) -> Result<()> {
+    ctx.defer_ephemeral().await?;
+
+    let user_id = ctx.author().id.to_string();
+    let session_manager = &ctx.data().api_client.session_manager;
+    let (mut data, count) = session_manager.export_user_sessions(&user_id, true).await?;
+    if count == 0 {
+        ctx.say("📭 你还没有历史会话记录。").await?;
+        return Ok(());
+    }
+
+    // 私信使用默认上传限制，超过时只导出文字内容
+    let upload_limit = upload_limit_for_tier(None);
+    let mut notice = "";
+    if data.len() as u64 > upload_limit {
+        (data, _) = session_manager
+            .export_user_sessions(&user_id, false)
+            .await?;
+        notice = "\n⚠️ 图片过多超过上传限制，本次只导出了问题、回答与元数据。";
+    }
+    if data.len() as u64 > upload_limit {
+        ctx.say("❌ 导出文件超过私信的上传限制，请先删除部分会话后重试")
+            .await?;
+        return Ok(());
+    }
+    info!(
+        "用户 {} 导出历史会话: {} 个会话, {} 字节",
+        ctx.author().name,
+        count,
+        data.len()
+    );
+
+    let http = &ctx.serenity_context().http;
+    let filename = format!("history_{}.zip", Local::now().format("%Y%m%d_%H%M%S"));
+    let sent = match ctx.author().create_dm_channel(http).await {
+        Ok(channel) => channel
+            .send_message(http, |m| {
+                m.content(format!(
+                    "📦 你的历史会话导出，共 {} 个会话。{}",
+                    count, notice
+                ))
+                .add_file(serenity::AttachmentType::Bytes {
+                    data: data.into(),
+                    filename,
+                })
+            })
+            .await
+            .map(|_| ()),
+        Err(e) => Err(e),
+    };
+    match sent {
+        Ok(()) => {
+            ctx.say(format!("✅ 已通过私信发送 {} 个会话的导出文件", count))
+                .await?;
+        }
+        Err(e) => {
+            warn!("向用户 {} 发送导出文件失败: {:?}", ctx.author().name, e);
+            ctx.say("❌ 无法发送私信，请检查是否允许接收来自服务器成员的私信")
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// 预览保留策略将要执行的清理操作（仅限机器人所有者）
+#[poise::command(slash_command, rename = "清理报告", owners_only)]
+pub async fn retention_report(
+    ctx: Context<'_>,
+    #[description = "立即按策略执行清理，默认只生成报告"] 执行: Option<bool>,
+) -> Result<()> {
+    ctx.defer_ephemeral().await?;
+
+    let config = &ctx.data().config.retention;
+    // 全局配置为演练模式时也不允许手动执行
+    let dry_run = !执行.unwrap_or(false) || config.dry_run;
+    let report = ctx
+        .data()
+        .api_client
+        .session_manager
+        .apply_retention(config, dry_run)
+        .await?;
+    info!(
+        "用户 {} 运行保留策略（{}）:\n{}",
+        ctx.author().name,
+        if dry_run { "演练" } else { "执行" },
+        report.summary()
+    );
+
+    let mut details: Vec<String> = report
+        .actions
+        .iter()
+        .take(RETENTION_REPORT_MAX_ITEMS)
+        .map(|a| {
+            format!(
+                "`{}` · {} · {}",
+                short_session_id(&a.session_id),
+                a.action.label(),
+                format_bytes(a.bytes)
+            )
+        })
+        .collect();
+    if report.actions.len() > RETENTION_REPORT_MAX_ITEMS {
+        details.push(format!(
+            "……另有 {} 项",
+            report.actions.len() - RETENTION_REPORT_MAX_ITEMS
+        ));
+    }
+    ctx.send(|r| {
+        r.embed(|e| {
+            e.title(if dry_run {
+                "🧹 清理报告（演练，未删除文件）"
+            } else {
+                "🧹 清理完成"
+            })
+            .color(if dry_run { 0xf1c40f } else { 0x2ecc71 })
+            .description(report.summary());
+            if !details.is_empty() {
+                e.field("操作明细", truncate(&details.join("\n"), 1000), false);
+            }
+            e
+        })
+        .ephemeral(true)
+    })
+    .await?;
+    Ok(())
+}
+
+async fn autocomplete_job_name(
+    ctx: Context<'_>,
+    partial: &str,
+) -> impl Iterator<Item = poise::AutocompleteChoice<String>> {
+    let partial = partial.trim().to_lowercase();
+    ctx.data()
+        .scheduler
+        .jobs()
+        .into_iter()
+        .filter(move |job| job.name.contains(&partial) || job.description.contains(&partial))
+        .map(|job| poise::AutocompleteChoice {
+            name: format!("{} · {}", job.name, job.description),
+            value: job.name,
+        })
+}
+
+/// 查看后台任务状态，或立即执行一个任务（仅限机器人所有者）
+#[poise::command(slash_command, rename = "后台任务", owners_only)]
+pub async fn background_jobs(
+    ctx: Context<'_>,
+    #[description = "要立即执行的任务，留空则列出全部任务"]
+    #[autocomplete = "autocomplete_job_name"]
+    任务: Option<String>,
+) -> Result<()> {
+    ctx.defer_ephemeral().await?;
+    let scheduler = &ctx.data().scheduler;
+
+    if let Some(name) = 任务 {
+        info!("用户 {} 手动执行后台任务 {}", ctx.author().name, name);
+        let content = match scheduler.run_now(&name).await {
+            Ok(message) => format!("✅ 任务 `{}` 执行完成\n{}", name, message),
+            Err(e) => format!("❌ 任务 `{}` 执行失败: {:#}", name, e),
+        };
+        ctx.send(|r| r.content(truncate(&content, MESSAGE_LIMIT)).ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+
+    let jobs = scheduler.jobs();
+    ctx.send(|r| {
+        r.embed(|e| {
+            e.title("⏱️ 后台任务").color(0x3498db);
+            if jobs.is_empty() {
+                e.description("没有注册任何后台任务");
+            }
+            for job in &jobs {
+                let status = &job.status;
+                let last_run = match (status.last_started, status.last_success) {
+                    _ if job.running => "运行中".to_string(),
+                    (Some(started), Some(success)) => format!(
+                        "{} {}（{:.1}s）",
+                        if success { "✅" } else { "❌" },
+                        format_local_time(started),
+                        status.last_duration_ms.unwrap_or(0) as f64 / 1000.0
+                    ),
+                    _ => "从未执行".to_string(),
+                };
+                let next_run = job
+                    .next_run
+                    .map(format_local_time)
+                    .unwrap_or_else(|| "无".to_string());
+                let mut value = format!(
+                    "{}\n计划: {} · 下次: {}\n上次: {} · 共 {} 次，失败 {} 次",
+                    job.description, job.schedule, next_run, last_run, status.runs, status.failures
+                );
+                if let Some(message) = &status.last_message {
+                    value.push_str(&format!("\n> {}", truncate(message, 300)));
+                }
+                e.field(&job.name, value, false);
+            }
+            e
+        })
+        .ephemeral(true)
+    })
+    .await?;
+    Ok(())
+}
+
 /// 获取机器人使用指南
 #[poise::command(slash_command, rename = "帮助")]
 pub async fn help_command(ctx: Context<'_>) -> Result<()> {
@@ -278,7 +1869,10 @@ pub async fn help_command(ctx: Context<'_>) -> Result<()> {
     ctx.defer().await?;
     info!("用户 {}({}) 请求帮助", ctx.author().name, ctx.author().id);
 
-    let help_text = r#"# 🤖 Discord AI助手使用指南
+    let guild_id = ctx.guild_id().map(|id| id.to_string());
+    let policy = ctx.data().config.retention.policy_for(guild_id.as_deref());
+    let help_text = format!(
+        r#"# 🤖 Discord AI助手使用指南
 
 ## 基本命令
 
@@ -290,6 +1884,36 @@ pub async fn help_command(ctx: Context<'_>) -> Result<()> {
 
 **/历史会话** - 查看你的历史会话列表
 
+**/搜索历史 [关键词] [开始日期] [结束日期] [应用]** - 搜索你过去的问题与回答
+- `关键词`: 多个关键词用空格分隔，问题或回答中需包含全部关键词
+- `开始日期` / `结束日期`: (可选) 格式为 2024-01-31，结束日期包含当天
+- `应用`: (可选) 只搜索指定应用的回答
+
+**/查看会话 [会话]** - 查看一个历史会话并重新发送回答图片
+- `会话`: 会话ID，输入时会自动补全你自己的会话，也可只输入ID的前几位
+- 点击"继续追问"可在同一会话中继续提问，点击"公开分享"会把问答发送到当前频道
+
+**/分享 [会话] [方式]** - 公开分享你的一个历史会话
+- `方式`: (可选) 发送到频道（默认，注明分享者）、网页链接（生成只读网页）或撤销网页链接
+
+**/删除会话 [会话]** - 删除你的一个历史会话及其全部文件
+
+**/删除全部数据** - 删除你的全部历史会话与个人设置，需要点击按钮确认
+
+**/导出历史** - 将你的全部历史会话（问题、回答、图片与元数据）打包为zip文件，通过私信发送
+
+**/渲染 [内容] [文件]** - 将Markdown直接渲染为图片，不调用AI
+- `内容`: (可选) Markdown文本，可用 `\n` 表示换行
+- `文件`: (可选) Markdown文件（.md），提供时忽略内容参数
+- 也可以右键消息 → Apps → **渲染为图片**，渲染该消息的内容或其中的Markdown文件
+
+**/回答模式 [模式] [范围]** - 设置回答以图片还是文字形式发送
+- `模式`: 图片、文字或恢复默认
+- `范围`: (可选) 个人或服务器，修改服务器设置需要"管理服务器"权限
+
+**/图片布局 [布局]** - 设置回答图片的布局
+- `布局`: 桌面、手机或恢复默认；手机布局画布更窄、字号更大，在手机上无需放大即可阅读
+
 **/帮助** - 获取机器人使用指南
 
 **/存储统计** - 查看会话存储状态和统计信息
@@ -299,16 +1923,32 @@ pub async fn help_command(ctx: Context<'_>) -> Result<()> {
 1. 提问时尽量描述清晰，以获得更准确的回答
 2. 支持任何有效的图片URL地址
 3. 可以同时上传多张图片（最多3张）进行分析
-4. 历史会话默认保存，但图片会在2天后自动清理
+4. {}
 5. 每个用户的会话互相隔离，其他人无法看到你的会话内容
 
-如有问题，请联系管理员。"#;
+如有问题，请联系管理员。"#,
+        retention_tip(&policy)
+    );
 
     ctx.say(help_text).await?;
 
     Ok(())
 }
 
+// 根据保留策略生成帮助中的清理说明，0 表示永久保存
+fn retention_tip(policy: &RetentionPolicy) -> String {
+    let days = |days: Option<u64>| days.filter(|d| *d > 0);
+    match (days(policy.image_days), days(policy.session_days)) {
+        (None, None) => "历史会话与图片默认永久保存".to_string(),
+        (Some(image), None) => format!("历史会话默认保存，但图片会在{}天后自动清理", image),
+        (None, Some(session)) => format!("历史会话会在{}天后自动删除", session),
+        (Some(image), Some(session)) => format!(
+            "图片会在{}天后自动清理，历史会话会在{}天后自动删除",
+            image, session
+        ),
+    }
+}
+
 /// 查看会话存储状态和统计信息
 #[poise::command(slash_command, rename = "存储统计")]
 pub async fn storage_stats(
@@ -321,7 +1961,7 @@ pub async fn storage_stats(
     let detailed = 详细信息.unwrap_or(false);
     let session_manager = &ctx.data().api_client.session_manager;
     let user_id = ctx.author().id.to_string();
-    let sessions = session_manager.get_user_sessions(&user_id);
+    let sessions = session_manager.get_user_sessions(&user_id).await;
     let total_sessions = sessions.len();
     // 准备各会话目录
     let session_dirs: Vec<std::path::PathBuf> = sessions
@@ -345,7 +1985,7 @@ pub async fn storage_stats(
                             .and_then(|e| e.to_str())
                             .map(|s| s.to_lowercase())
                         {
-                            if ext == "png" || ext == "jpg" || ext == "jpeg" {
+                            if ext == "png" || ext == "jpg" || ext == "jpeg" || ext == "webp" {
                                 if let Ok(meta) = std::fs::metadata(&path) {
                                     size += meta.len();
                                 }
@@ -396,7 +2036,7 @@ pub async fn storage_stats(
                         .and_then(|e| e.to_str())
                         .map(|s| s.to_lowercase())
                     {
-                        if ext == "png" || ext == "jpg" || ext == "jpeg" {
+                        if ext == "png" || ext == "jpg" || ext == "jpeg" || ext == "webp" {
                             if let Ok(meta) = std::fs::metadata(&path) {
                                 ss += meta.len();
                             }
@@ -424,7 +2064,7 @@ pub async fn storage_stats(
         // 分页显示，每页10条
         let per_page = 10;
         let detail_count = per_details.len();
-        let total_pages = (detail_count + per_page - 1) / per_page;
+        let total_pages = detail_count.div_ceil(per_page);
         let page = 0;
         let start = page * per_page;
         let end = ((page + 1) * per_page).min(detail_count);
@@ -476,6 +2116,11 @@ pub(super) fn format_time(dt: DateTime<Utc>) -> String {
     dt.format("%Y-%m-%d %H:%M:%S").to_string()
 }
 
+// 以本地时间显示，用于后台任务的执行时间
+fn format_local_time(dt: DateTime<Utc>) -> String {
+    dt.with_timezone(&Local).format("%m-%d %H:%M").to_string()
+}
+
 // 获取简短会话ID
 fn short_session_id(session_id: &str) -> &str {
     if session_id.len() > 8 {
@@ -488,12 +2133,13 @@ fn short_session_id(session_id: &str) -> &str {
 // 格式化会话信息
 pub(super) fn format_session_info(index: usize, session: &crate::session::SessionInfo) -> String {
     format!(
-        "**{}. 会话 `{}`**\n   问题: {}\n   时间: {}\n   图片数: {}\n",
+        "**{}. 会话 `{}`**\n   问题: {}\n   时间: {}\n   图片数: {} | 消息数: {}\n",
         index + 1,
         short_session_id(&session.id),
         session.input_preview,
         format_time(session.last_modified),
-        session.images
+        session.images,
+        session.turns
     )
 }
 
@@ -511,6 +2157,130 @@ pub async fn qa_context_reply(ctx: Context<'_>, message: serenity::Message) -> R
         .take(9)
         .map(|att| att.url.clone())
         .collect();
-    run_qa_flow(ctx, question, image_urls).await?;
+    run_qa_flow(ctx, question, image_urls, None).await?;
     Ok(())
 }
+
+// 渲染预览时Markdown附件的最大大小
+const MAX_MARKDOWN_ATTACHMENT_BYTES: u64 = 1024 * 1024;
+
+// 判断附件是否为可渲染的Markdown或文本文件
+fn is_markdown_attachment(attachment: &serenity::Attachment) -> bool {
+    let filename = attachment.filename.to_lowercase();
+    filename.ends_with(".md") || filename.ends_with(".markdown") || filename.ends_with(".txt")
+}
+
+// 下载Markdown附件内容
+async fn read_markdown_attachment(attachment: &serenity::Attachment) -> Result<String> {
+    if attachment.size > MAX_MARKDOWN_ATTACHMENT_BYTES {
+        anyhow::bail!(
+            "文件过大（{:.1}KB），最大支持 {}KB",
+            attachment.size as f64 / 1024.0,
+            MAX_MARKDOWN_ATTACHMENT_BYTES / 1024
+        );
+    }
+    let bytes = attachment.download().await?;
+    String::from_utf8(bytes).map_err(|_| anyhow::anyhow!("文件不是有效的UTF-8文本"))
+}
+
+// 将Markdown直接渲染为图片并发送，不调用AI
+//
+// 图片生成在临时目录中，发送后删除，不创建会话
+async fn send_rendered_markdown(ctx: Context<'_>, markdown: &str) -> Result<()> {
+    if markdown.trim().is_empty() {
+        ctx.say("❌ 没有可渲染的内容").await?;
+        return Ok(());
+    }
+    info!(
+        "用户 {}({}) 渲染Markdown，长度: {}",
+        ctx.author().name,
+        ctx.author().id,
+        markdown.chars().count()
+    );
+
+    let image_generator = &ctx.data().api_client.image_generator;
+    let image_path = ctx
+        .data()
+        .config
+        .image_output_dir
+        .join("temp")
+        .join(format!("render_{}.png", Uuid::new_v4()));
+    // 预览不显示问题与提问者，只保留页脚中的服务器名称
+    let meta = AnswerMeta {
+        guild_name: ctx.guild().map(|g| g.name),
+        ..Default::default()
+    };
+    let images = image_generator.fetch_remote_images(markdown).await;
+    let profile = user_render_profile(ctx);
+    let image_paths = image_generator.create_images_from_markdown(
+        markdown,
+        &image_path,
+        Some(&meta),
+        &images,
+        &profile,
+    )?;
+
+    let result = async {
+        let fitted_paths = fit_images_to_upload_limit(ctx, &image_paths)?;
+        for chunk in fitted_paths.chunks(MAX_ATTACHMENTS_PER_MESSAGE) {
+            ctx.send(|reply| {
+                for path in chunk {
+                    reply.attachment(serenity::AttachmentType::Path(path));
+                }
+                reply
+            })
+            .await?;
+        }
+        Ok::<_, anyhow::Error>(fitted_paths)
+    }
+    .await;
+
+    // 删除临时图片（包括压缩后的副本）
+    let fitted_paths = result.as_ref().map(Vec::as_slice).unwrap_or(&[]);
+    for path in image_paths.iter().chain(fitted_paths) {
+        let _ = std::fs::remove_file(path);
+    }
+    result.map(|_| ())
+}
+
+/// 将Markdown渲染为图片（不调用AI），可用于发布排版好的指南或预览主题
+#[poise::command(slash_command, rename = "渲染")]
+pub async fn render_markdown(
+    ctx: Context<'_>,
+    #[description = "要渲染的Markdown，可用 \\n 表示换行"] 内容: Option<String>,
+    #[description = "Markdown文件（.md），提供时忽略内容参数"] 文件: Option<serenity::Attachment>,
+) -> Result<()> {
+    ctx.defer().await?;
+    let markdown = match (文件, 内容) {
+        (Some(file), _) => {
+            if !is_markdown_attachment(&file) {
+                ctx.say("❌ 只支持 .md、.markdown 或 .txt 文件").await?;
+                return Ok(());
+            }
+            read_markdown_attachment(&file).await?
+        }
+        // 斜线命令参数不能换行，用 \n 表示换行
+        (None, Some(text)) => text.replace("\\n", "\n"),
+        (None, None) => {
+            ctx.say("❌ 请提供要渲染的内容或Markdown文件").await?;
+            return Ok(());
+        }
+    };
+    send_rendered_markdown(ctx, &markdown).await
+}
+
+// 消息上下文菜单命令：右键→Apps→渲染为图片
+#[poise::command(context_menu_command = "渲染为图片")]
+pub async fn render_message(ctx: Context<'_>, message: serenity::Message) -> Result<()> {
+    ctx.defer().await?;
+    // 消息带有Markdown文件时渲染文件，否则渲染消息内容
+    let markdown = match message
+        .attachments
+        .iter()
+        .find(|att| is_markdown_attachment(att))
+    {
+        Some(attachment) => read_markdown_attachment(attachment).await?,
+        None => message.content.clone(),
+    };
+    send_rendered_markdown(ctx, &markdown).await
+}
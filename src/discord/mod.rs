@@ -2,14 +2,14 @@ mod commands;
 
 use anyhow::Result;
 use poise::serenity_prelude as serenity;
-use poise::FrameworkBuilder;
 use std::sync::Arc;
-use tokio::select;
-use tokio::time::{interval, Duration};
+use std::time::Duration;
 use tracing::{debug, error, info, warn};
 
 use crate::api::APIClient;
 use crate::config::Config;
+use crate::scheduler::{Job, Scheduler};
+use crate::web::{share_base_url, start_share_server};
 
 use commands::*;
 
@@ -21,6 +21,7 @@ pub struct Data {
     #[allow(dead_code)]
     pub config: Config,
     pub api_client: Arc<APIClient>,
+    pub scheduler: Scheduler,
 }
 
 // 启动Discord机器人
@@ -34,10 +35,14 @@ pub async fn start_bot(config: &Config) -> Result<()> {
     );
     info!("正在初始化Discord机器人...");
 
+    // 注册后台任务
+    let scheduler = build_scheduler(config, &api_client);
+
     // 创建共享数据
     let data = Data {
         config: config.clone(),
         api_client: api_client.clone(),
+        scheduler: scheduler.clone(),
     };
 
     // 创建框架
@@ -47,8 +52,20 @@ pub async fn start_bot(config: &Config) -> Result<()> {
                 qa_bot(),
                 qa_context_reply(),
                 history_sessions(),
+                search_history(),
+                view_session(),
+                share_session(),
+                delete_session(),
+                delete_all_data(),
+                export_history(),
+                delivery_mode(),
+                render_layout(),
+                render_markdown(),
+                render_message(),
                 help_command(),
                 storage_stats(),
+                retention_report(),
+                background_jobs(),
             ],
             prefix_options: poise::PrefixFrameworkOptions {
                 prefix: Some("!".into()),
@@ -110,65 +127,68 @@ pub async fn start_bot(config: &Config) -> Result<()> {
 
     info!("正在启动Discord机器人...");
 
-    // 启动周期性清理任务和机器人
-    start_with_periodic_cleanup(framework, api_client).await
-}
-
-// 并发运行机器人和清理任务
-async fn start_with_periodic_cleanup(
-    framework: FrameworkBuilder<Data, anyhow::Error>,
-    api_client: Arc<APIClient>,
-) -> Result<()> {
-    // 创建一个关闭信号通道
-    let (shutdown_send, mut shutdown_recv) = tokio::sync::oneshot::channel::<()>();
-    let mut shutdown_send = Some(shutdown_send);
-
-    // 机器人任务
-    let bot_task = tokio::spawn(async move {
-        info!("机器人框架开始运行");
-        match framework.run().await {
-            Ok(_) => info!("机器人正常关闭"),
-            Err(e) => error!("机器人运行时错误: {}", e),
+    // 启用时启动分享网页服务器，启动失败不影响机器人运行
+    if let Some(addr) = config.share_web_addr {
+        if let Err(e) = start_share_server(addr, api_client.session_manager.clone()) {
+            error!("启动分享网页服务器失败: {:?}", e);
         }
-
-        // 如果机器人关闭，发送关闭信号
-        if let Some(sender) = shutdown_send.take() {
-            let _ = sender.send(());
+        if share_base_url(config).is_none() {
+            warn!(
+                "分享网页监听在 {}，未设置 SHARE_BASE_URL 时无法生成分享链接",
+                addr
+            );
         }
-    });
+    }
 
-    // 清理任务 - 每6小时运行一次
-    let cleanup_task = tokio::spawn(async move {
-        let mut interval = interval(Duration::from_secs(6 * 60 * 60));
-        loop {
-            select! {
-                _ = interval.tick() => {
-                    info!("开始执行定期清理任务");
-                    // 执行清理
-                    api_client.session_manager.periodic_cleanup(2).await;
-                },
-                _ = &mut shutdown_recv => {
-                    info!("接收到关闭信号，停止清理任务");
-                    break;
-                }
+    // 启动后台任务和机器人
+    scheduler.start();
+    framework.run().await?;
+    info!("机器人正常关闭");
+    Ok(())
+}
+
+// 创建调度器并注册全部后台任务
+fn build_scheduler(config: &Config, api_client: &Arc<APIClient>) -> Scheduler {
+    let mut scheduler = Scheduler::new(
+        &config.data_dir,
+        Duration::from_secs(config.job_jitter_secs),
+    );
+
+    let client = api_client.clone();
+    scheduler.register(Job::new(
+        "session_cleanup",
+        "按保留策略清理会话",
+        config.cleanup_schedule.clone(),
+        move || {
+            let client = client.clone();
+            async move {
+                client
+                    .session_manager
+                    .periodic_cleanup(&client.config.retention)
+                    .await
             }
-        }
-    });
+        },
+    ));
 
-    // 等待任务完成
-    tokio::select! {
-        _ = bot_task => {
-            info!("机器人任务已结束");
-            // 在这里不需要abort cleanup_task，因为它会收到关闭信号
-        }
-        _ = cleanup_task => {
-            info!("清理任务已结束");
-            // 这种情况不应该发生，因为清理任务应该一直运行
-            error!("清理任务意外结束");
-        }
-    }
+    let client = api_client.clone();
+    let max_age = Duration::from_secs(config.temp_max_age_hours * 60 * 60);
+    scheduler.register(Job::new(
+        "temp_sweep",
+        "清理残留的渲染临时文件",
+        config.temp_sweep_schedule.clone(),
+        move || {
+            let client = client.clone();
+            async move {
+                let report = tokio::task::spawn_blocking(move || {
+                    client.image_generator.sweep_temp_files(max_age)
+                })
+                .await??;
+                Ok(report.summary())
+            }
+        },
+    ));
 
-    Ok(())
+    scheduler
 }
 
 // Discord事件处理
@@ -232,10 +252,11 @@ async fn event_handler(
                             let sessions = _data
                                 .api_client
                                 .session_manager
-                                .get_user_sessions(&target_user_id.to_string());
+                                .get_user_sessions(target_user_id)
+                                .await;
                             let per_page = 10;
                             let total = sessions.len();
-                            let total_pages = (total + per_page - 1) / per_page;
+                            let total_pages = total.div_ceil(per_page);
                             if new_page >= total_pages {
                                 new_page = total_pages.saturating_sub(1);
                             }
@@ -321,7 +342,8 @@ async fn event_handler(
                             let sessions = _data
                                 .api_client
                                 .session_manager
-                                .get_user_sessions(&target_user_id.to_string());
+                                .get_user_sessions(target_user_id)
+                                .await;
                             let session_dirs: Vec<std::path::PathBuf> = sessions
                                 .iter()
                                 .map(|s| _data.api_client.session_manager.get_session_dir(&s.id))
@@ -343,7 +365,11 @@ async fn event_handler(
                                             .and_then(|e| e.to_str())
                                             .map(|s| s.to_lowercase())
                                         {
-                                            if ext == "png" || ext == "jpg" || ext == "jpeg" {
+                                            if ext == "png"
+                                                || ext == "jpg"
+                                                || ext == "jpeg"
+                                                || ext == "webp"
+                                            {
                                                 if let Ok(meta) = std::fs::metadata(&path) {
                                                     ss += meta.len();
                                                 }
@@ -370,7 +396,7 @@ async fn event_handler(
                             let total_images: u32 = sessions.iter().map(|s| s.images).sum();
                             let per_page = 10;
                             let detail_count = per_details.len();
-                            let total_pages = (detail_count + per_page - 1) / per_page;
+                            let total_pages = detail_count.div_ceil(per_page);
                             let mut new_page = match action {
                                 "prev" if page > 0 => page - 1,
                                 "next" => page + 1,
@@ -456,6 +482,16 @@ async fn event_handler(
                                 .await;
                         }
                     }
+                } else if cid.starts_with("pdf_") {
+                    // 导出PDF按钮，custom_id 格式: pdf_{session_id}
+                    if let Err(e) = handle_pdf_export(ctx, msg_component, _data).await {
+                        error!("导出PDF失败: {:?}", e);
+                    }
+                } else if cid.starts_with("code_") {
+                    // 获取代码按钮，custom_id 格式: code_{session_id}
+                    if let Err(e) = handle_code_export(ctx, msg_component, _data).await {
+                        error!("获取代码失败: {:?}", e);
+                    }
                 } else {
                     // 处理其他类型的交互
                 }
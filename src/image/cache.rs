@@ -0,0 +1,244 @@
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+// 缓存格式版本，修改缓存内容的生成方式时递增以使旧缓存失效
+const CACHE_VERSION: &str = "1";
+
+/// 按内容寻址的渲染结果缓存
+///
+/// 键为HTML（包含主题样式与回答内容）、宽度、编码参数与渲染器版本的哈希，
+/// 相同输入再次渲染时直接复制缓存文件，无需启动 wkhtmltoimage。
+/// 总大小超过上限时按最近使用时间淘汰。
+#[derive(Debug)]
+pub struct RenderCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    // 写入与淘汰时加锁，避免并发淘汰重复删除
+    lock: Mutex<()>,
+}
+
+impl RenderCache {
+    pub fn new(dir: PathBuf, max_bytes: u64) -> Self {
+        RenderCache {
+            dir,
+            max_bytes,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// 是否启用缓存（上限为0时禁用）
+    pub fn enabled(&self) -> bool {
+        self.max_bytes > 0
+    }
+
+    /// 计算缓存键，各部分带长度前缀以避免拼接歧义
+    pub fn key(parts: &[&str]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(CACHE_VERSION.as_bytes());
+        for part in parts {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part.as_bytes());
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    fn entry_path(&self, key: &str, extension: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", key, extension))
+    }
+
+    /// 命中时将缓存文件复制到 `output_path`，并更新最近使用时间
+    pub fn get(&self, key: &str, extension: &str, output_path: &Path) -> Option<PathBuf> {
+        if !self.enabled() {
+            return None;
+        }
+        let entry = self.entry_path(key, extension);
+        if !entry.exists() {
+            return None;
+        }
+        if let Err(e) = touch(&entry) {
+            debug!("更新缓存访问时间失败: {}", e);
+        }
+        // 先复制到中间文件再重命名，避免目标目录中出现不完整的图片
+        let partial = super::temp::partial_path(&output_path.with_extension(extension));
+        let result = fs::copy(&entry, &partial)
+            .context("复制缓存文件失败")
+            .and_then(|_| super::temp::commit_partial(&partial, output_path));
+        match result {
+            Ok(output_path) => {
+                debug!("渲染缓存命中: {}", key);
+                Some(output_path)
+            }
+            Err(e) => {
+                let _ = fs::remove_file(&partial);
+                warn!("复制缓存文件失败: {:?}", e);
+                None
+            }
+        }
+    }
+
+    /// 将渲染结果加入缓存，之后按需淘汰最久未使用的条目
+    ///
+    /// 缓存失败只记录警告，不影响渲染结果。
+    pub fn put(&self, key: &str, rendered: &Path) {
+        if !self.enabled() {
+            return;
+        }
+        let extension = rendered
+            .extension()
+            .map(|e| e.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let _guard = self.lock.lock().unwrap();
+        if let Err(e) = self.insert(key, &extension, rendered) {
+            warn!("写入渲染缓存失败: {:?}", e);
+            return;
+        }
+        if let Err(e) = self.evict() {
+            warn!("清理渲染缓存失败: {:?}", e);
+        }
+    }
+
+    fn insert(&self, key: &str, extension: &str, rendered: &Path) -> Result<()> {
+        fs::create_dir_all(&self.dir).context("创建缓存目录失败")?;
+        // 先写入临时文件再重命名，避免读取到不完整的缓存
+        let temp_path = self.dir.join(format!(".{}.tmp", Uuid::new_v4()));
+        fs::copy(rendered, &temp_path).context("复制渲染结果失败")?;
+        fs::rename(&temp_path, self.entry_path(key, extension)).context("重命名缓存文件失败")?;
+        Ok(())
+    }
+
+    // 总大小超过上限时，按修改时间（即最近使用时间）从旧到新删除
+    fn evict(&self) -> Result<()> {
+        let mut entries: Vec<(PathBuf, u64, SystemTime)> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                let name = entry.file_name();
+                if !metadata.is_file() || name.to_string_lossy().starts_with('.') {
+                    return None;
+                }
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                Some((entry.path(), metadata.len(), modified))
+            })
+            .collect();
+
+        let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+        if total <= self.max_bytes {
+            return Ok(());
+        }
+        entries.sort_by_key(|(_, _, modified)| *modified);
+        for (path, size, _) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            if fs::remove_file(&path).is_ok() {
+                total -= size;
+                debug!("已淘汰渲染缓存: {}", path.display());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 删除缓存中与给定文件内容相同的条目，返回删除的条目数
+///
+/// 缓存按渲染输入寻址，无法从会话反查缓存键；会话中的图片与PDF是缓存文件的完整副本，
+/// 因此按文件大小与内容哈希匹配。缓存目录不存在时返回0。
+pub fn remove_cached_copies(cache_dir: &Path, files: &[PathBuf]) -> Result<usize> {
+    let mut targets: Vec<(u64, Vec<u8>)> = Vec::new();
+    for file in files {
+        if let Ok(data) = fs::read(file) {
+            targets.push((data.len() as u64, Sha256::digest(&data).to_vec()));
+        }
+    }
+    if targets.is_empty() || !cache_dir.is_dir() {
+        return Ok(0);
+    }
+
+    let mut removed = 0;
+    for entry in fs::read_dir(cache_dir)?.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        // 只有大小相同的条目才需要读取内容比较
+        if !metadata.is_file() || !targets.iter().any(|(size, _)| *size == metadata.len()) {
+            continue;
+        }
+        let Ok(data) = fs::read(&path) else {
+            continue;
+        };
+        let hash = Sha256::digest(&data).to_vec();
+        if targets.iter().any(|(_, target)| *target == hash) && fs::remove_file(&path).is_ok() {
+            debug!("已删除渲染缓存: {}", path.display());
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+// 更新文件的修改时间，作为最近使用时间
+fn touch(path: &Path) -> std::io::Result<()> {
+    File::options()
+        .append(true)
+        .open(path)?
+        .set_modified(SystemTime::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn temp_cache(max_bytes: u64) -> (RenderCache, tempfile::TempDir) {
+        let temp = tempfile::tempdir().unwrap();
+        (RenderCache::new(temp.path().join("cache"), max_bytes), temp)
+    }
+
+    #[test]
+    fn key_depends_on_every_part() {
+        let key = RenderCache::key(&["<p>hi</p>", "1024", "png"]);
+        assert_eq!(key, RenderCache::key(&["<p>hi</p>", "1024", "png"]));
+        assert_ne!(key, RenderCache::key(&["<p>hi</p>", "800", "png"]));
+        // 拼接结果相同但分段不同时键不同
+        assert_ne!(
+            RenderCache::key(&["ab", "c"]),
+            RenderCache::key(&["a", "bc"])
+        );
+    }
+
+    #[test]
+    fn hit_copies_entry_and_lru_evicts_oldest() {
+        let (cache, temp) = temp_cache(10);
+        let dir = temp.path();
+        let rendered = dir.join("rendered.png");
+
+        fs::write(&rendered, b"123456").unwrap();
+        cache.put("old", &rendered);
+        let hit = cache
+            .get("old", "png", &dir.join("out.png"))
+            .expect("应命中缓存");
+        assert_eq!(fs::read(&hit).unwrap(), b"123456");
+        assert!(cache.get("old", "jpg", &dir.join("out")).is_none());
+
+        // 让新条目的修改时间晚于旧条目
+        let past = SystemTime::now() - Duration::from_secs(60);
+        File::options()
+            .append(true)
+            .open(cache.entry_path("old", "png"))
+            .unwrap()
+            .set_modified(past)
+            .unwrap();
+        cache.put("new", &rendered);
+        assert!(
+            !cache.entry_path("old", "png").exists(),
+            "超出上限时应淘汰旧条目"
+        );
+        assert!(cache.entry_path("new", "png").exists());
+    }
+}
@@ -0,0 +1,200 @@
+use anyhow::{anyhow, Context, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType as ResizeFilter;
+use image::{DynamicImage, ImageEncoder};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::{debug, info, warn};
+
+/// 回答图片的输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Png,
+    WebP,
+    Jpeg,
+}
+
+impl OutputFormat {
+    /// 对应的文件扩展名
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::WebP => "webp",
+            OutputFormat::Jpeg => "jpg",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "png" => Ok(OutputFormat::Png),
+            "webp" => Ok(OutputFormat::WebP),
+            "jpg" | "jpeg" => Ok(OutputFormat::Jpeg),
+            other => Err(anyhow!("不支持的图片格式: {}", other)),
+        }
+    }
+}
+
+/// 编码参数
+#[derive(Debug, Clone, Copy)]
+pub struct EncodeOptions {
+    pub format: OutputFormat,
+    /// JPEG 质量 (1-100)
+    pub jpeg_quality: u8,
+    /// 是否对PNG做无损压缩优化
+    pub optimize_png: bool,
+}
+
+// 超出大小限制时依次尝试的JPEG质量
+const FALLBACK_JPEG_QUALITIES: [u8; 3] = [85, 70, 55];
+// 每次缩小的比例与最小宽度
+const DOWNSCALE_RATIO: f32 = 0.75;
+const MIN_DOWNSCALE_WIDTH: u32 = 480;
+
+/// 将图片编码为指定格式
+pub fn encode_image(
+    img: &DynamicImage,
+    format: OutputFormat,
+    options: &EncodeOptions,
+) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    match format {
+        OutputFormat::Png => {
+            let rgba = img.to_rgba8();
+            let (compression, filter) = if options.optimize_png {
+                (CompressionType::Best, FilterType::Adaptive)
+            } else {
+                (CompressionType::Default, FilterType::Adaptive)
+            };
+            PngEncoder::new_with_quality(&mut buf, compression, filter).write_image(
+                &rgba,
+                rgba.width(),
+                rgba.height(),
+                image::ColorType::Rgba8,
+            )?;
+        }
+        OutputFormat::WebP => {
+            let rgba = img.to_rgba8();
+            WebPEncoder::new_lossless(&mut buf).write_image(
+                &rgba,
+                rgba.width(),
+                rgba.height(),
+                image::ColorType::Rgba8,
+            )?;
+        }
+        OutputFormat::Jpeg => {
+            // JPEG 不支持透明通道
+            let rgb = img.to_rgb8();
+            JpegEncoder::new_with_quality(&mut buf, options.jpeg_quality).write_image(
+                &rgb,
+                rgb.width(),
+                rgb.height(),
+                image::ColorType::Rgb8,
+            )?;
+        }
+    }
+    Ok(buf)
+}
+
+/// 将渲染出的PNG转换为配置的输出格式，返回最终文件路径
+pub fn convert_rendered(png_path: &Path, options: &EncodeOptions) -> Result<PathBuf> {
+    if options.format == OutputFormat::Png && !options.optimize_png {
+        return Ok(png_path.to_path_buf());
+    }
+
+    let img = image::open(png_path).context("读取渲染结果失败")?;
+    let data = encode_image(&img, options.format, options)?;
+    let target = png_path.with_extension(options.format.extension());
+    fs::write(&target, &data).context("写入图片失败")?;
+    if target != png_path {
+        let _ = fs::remove_file(png_path);
+    }
+    debug!(
+        "图片已转换为 {}: {} ({} 字节)",
+        options.format.extension(),
+        target.display(),
+        data.len()
+    );
+    Ok(target)
+}
+
+/// 确保图片不超过上传大小限制
+///
+/// 先按原格式检查，超出时依次尝试降低质量的JPEG，仍然超出则逐步缩小尺寸。
+/// 返回满足限制的文件路径（扩展名可能改变）。压缩后的JPEG写在原图旁边，原图保留，
+/// 原图本身是JPEG时直接覆盖。
+pub fn fit_to_size(path: &Path, max_bytes: u64, options: &EncodeOptions) -> Result<PathBuf> {
+    let size = fs::metadata(path).context("读取图片大小失败")?.len();
+    if size <= max_bytes {
+        return Ok(path.to_path_buf());
+    }
+    info!(
+        "图片 {} 大小 {:.2}MB 超过上传限制 {:.2}MB，尝试重新编码",
+        path.display(),
+        size as f64 / 1024.0 / 1024.0,
+        max_bytes as f64 / 1024.0 / 1024.0
+    );
+
+    let mut img = image::open(path).context("读取图片失败")?;
+    let target = path.with_extension(OutputFormat::Jpeg.extension());
+    loop {
+        for quality in FALLBACK_JPEG_QUALITIES {
+            let jpeg_options = EncodeOptions {
+                jpeg_quality: quality,
+                ..*options
+            };
+            let data = encode_image(&img, OutputFormat::Jpeg, &jpeg_options)?;
+            if data.len() as u64 <= max_bytes {
+                fs::write(&target, &data).context("写入重新编码的图片失败")?;
+                info!(
+                    "已重新编码为 JPEG (质量 {}, 宽度 {}): {:.2}MB",
+                    quality,
+                    img.width(),
+                    data.len() as f64 / 1024.0 / 1024.0
+                );
+                return Ok(target);
+            }
+        }
+
+        let new_width = (img.width() as f32 * DOWNSCALE_RATIO) as u32;
+        if new_width < MIN_DOWNSCALE_WIDTH {
+            warn!("图片缩小到最小宽度后仍超过上传限制: {}", path.display());
+            return Err(anyhow!("图片过大，无法压缩到上传限制以内"));
+        }
+        let new_height = (img.height() as f32 * DOWNSCALE_RATIO) as u32;
+        img = img.resize(new_width, new_height.max(1), ResizeFilter::Lanczos3);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_output_format() {
+        assert_eq!("PNG".parse::<OutputFormat>().unwrap(), OutputFormat::Png);
+        assert_eq!("webp".parse::<OutputFormat>().unwrap(), OutputFormat::WebP);
+        assert_eq!("jpeg".parse::<OutputFormat>().unwrap(), OutputFormat::Jpeg);
+        assert!("gif".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn encode_all_formats() {
+        let img = DynamicImage::new_rgba8(16, 16);
+        let options = EncodeOptions {
+            format: OutputFormat::Png,
+            jpeg_quality: 80,
+            optimize_png: true,
+        };
+        for format in [OutputFormat::Png, OutputFormat::WebP, OutputFormat::Jpeg] {
+            let data = encode_image(&img, format, &options).expect("编码失败");
+            assert!(!data.is_empty());
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+use ttf_parser::{name_id, Face, Language};
+
+/// 字体在回退链中的类别，按此顺序排列
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FontCategory {
+    Latin,
+    Cjk,
+    Emoji,
+}
+
+/// 需要检查覆盖情况的Unicode区块及其代表字符
+const UNICODE_BLOCKS: &[(&str, &[char])] = &[
+    ("基本拉丁字母", &['A', 'z', '0']),
+    ("中日韩统一表意文字", &['中', '文', '字']),
+    ("平假名", &['あ', 'の']),
+    ("片假名", &['ア', 'カ']),
+    ("韩文音节", &['한', '글']),
+    ("中日韩符号和标点", &['。', '「']),
+    ("全角字符", &['，', '！']),
+    ("箭头", &['→', '↑']),
+    ("制表符", &['─', '│']),
+    ("杂项符号", &['☀', '★']),
+    ("表情符号", &['😀', '👍']),
+];
+
+/// 已注册的字体文件
+#[derive(Debug, Clone)]
+pub struct FontFace {
+    /// 字体文件的绝对路径
+    pub path: PathBuf,
+    /// 从字体文件中读取的字族名
+    pub family: String,
+    /// 在回退链中的类别
+    pub category: FontCategory,
+    /// 该字体可显示的Unicode区块
+    pub covered_blocks: Vec<&'static str>,
+}
+
+impl FontFace {
+    /// 读取字体文件，解析字族名与字符覆盖情况
+    pub fn load(path: &Path) -> Option<Self> {
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(e) => {
+                warn!("读取字体文件失败: {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        let face = match Face::parse(&data, 0) {
+            Ok(face) => face,
+            Err(e) => {
+                warn!("解析字体文件失败: {}: {}", path.display(), e);
+                return None;
+            }
+        };
+
+        let family = family_name(&face).unwrap_or_else(|| {
+            path.file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default()
+        });
+
+        let has = |c: char| face.glyph_index(c).is_some();
+        let category = if has('😀') && !has('A') {
+            FontCategory::Emoji
+        } else if has('中') || has('あ') || has('한') {
+            FontCategory::Cjk
+        } else {
+            FontCategory::Latin
+        };
+
+        let covered_blocks = UNICODE_BLOCKS
+            .iter()
+            .filter(|(_, samples)| samples.iter().all(|&c| has(c)))
+            .map(|(name, _)| *name)
+            .collect();
+
+        let path = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            std::env::current_dir()
+                .unwrap_or_else(|_| PathBuf::from("."))
+                .join(path)
+        };
+
+        debug!(
+            "已注册字体: {} ({:?}) -> {}",
+            family,
+            category,
+            path.display()
+        );
+        Some(Self {
+            path,
+            family,
+            category,
+            covered_blocks,
+        })
+    }
+
+    /// 生成对应的 @font-face 规则
+    pub fn css_rule(&self) -> String {
+        let format = match self
+            .path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .as_deref()
+        {
+            Some("ttf") => " format('truetype')",
+            Some("otf") => " format('opentype')",
+            _ => "",
+        };
+        format!(
+            "@font-face {{ font-family: '{family}'; src: local('{family}'), url('file://{path}'){format}; font-weight: normal; font-style: normal; }}",
+            family = css_escape(&self.family),
+            path = self.path.to_string_lossy(),
+            format = format,
+        )
+    }
+}
+
+/// 加载所有已配置的字体，并按 拉丁 → 中日韩 → 表情 的顺序排列
+pub fn load_fonts(paths: &[PathBuf]) -> Vec<FontFace> {
+    let mut fonts: Vec<FontFace> = paths
+        .iter()
+        .filter(|path| path.exists())
+        .filter_map(|path| FontFace::load(path))
+        .collect();
+    // 稳定排序，同类别内保持配置顺序
+    fonts.sort_by_key(|f| f.category);
+    fonts
+}
+
+/// 返回已配置字体均无法显示的Unicode区块
+pub fn missing_blocks(fonts: &[FontFace]) -> Vec<&'static str> {
+    UNICODE_BLOCKS
+        .iter()
+        .map(|(name, _)| *name)
+        .filter(|name| !fonts.iter().any(|f| f.covered_blocks.contains(name)))
+        .collect()
+}
+
+/// 生成CSS字体栈：已注册字体在前，系统常见字体作为兜底
+pub fn font_family_stack(fonts: &[FontFace]) -> String {
+    let mut families: Vec<String> = fonts
+        .iter()
+        .map(|f| format!("'{}'", css_escape(&f.family)))
+        .collect();
+    for fallback in [
+        "'Noto Sans'",
+        "'Noto Sans CJK SC'",
+        "'Noto Sans CJK TC'",
+        "'Noto Sans CJK JP'",
+        "'Noto Sans CJK KR'",
+        "'Microsoft YaHei'",
+        "'SimHei'",
+        "'Noto Color Emoji'",
+        "'Apple Color Emoji'",
+        "'Segoe UI Emoji'",
+        "sans-serif",
+    ] {
+        if !families.iter().any(|f| f == fallback) {
+            families.push(fallback.to_string());
+        }
+    }
+    families.join(", ")
+}
+
+/// 从字体的 name 表中读取字族名，优先使用英文名称
+fn family_name(face: &Face) -> Option<String> {
+    for id in [name_id::TYPOGRAPHIC_FAMILY, name_id::FAMILY] {
+        let names: Vec<_> = face
+            .names()
+            .into_iter()
+            .filter(|n| n.name_id == id && n.is_unicode())
+            .collect();
+        let english = names
+            .iter()
+            .find(|n| n.language() == Language::English_UnitedStates)
+            .and_then(|n| n.to_string());
+        if let Some(name) = english.or_else(|| names.iter().find_map(|n| n.to_string())) {
+            return Some(name);
+        }
+    }
+    None
+}
+
+// 转义CSS字符串中的单引号与反斜杠
+fn css_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn face(family: &str, category: FontCategory, blocks: &[&'static str]) -> FontFace {
+        FontFace {
+            path: PathBuf::from(format!("/fonts/{}.ttf", family)),
+            family: family.to_string(),
+            category,
+            covered_blocks: blocks.to_vec(),
+        }
+    }
+
+    #[test]
+    fn family_stack_keeps_order_and_appends_fallbacks() {
+        let fonts = vec![
+            face("Inter", FontCategory::Latin, &[]),
+            face("LXGW WenKai", FontCategory::Cjk, &[]),
+        ];
+        let stack = font_family_stack(&fonts);
+        assert!(stack.starts_with("'Inter', 'LXGW WenKai'"));
+        assert!(stack.contains("'Noto Color Emoji'"));
+        assert!(stack.ends_with("sans-serif"));
+    }
+
+    #[test]
+    fn missing_blocks_reports_uncovered() {
+        let fonts = vec![face(
+            "LXGW WenKai",
+            FontCategory::Cjk,
+            &["基本拉丁字母", "中日韩统一表意文字"],
+        )];
+        let missing = missing_blocks(&fonts);
+        assert!(missing.contains(&"表情符号"));
+        assert!(!missing.contains(&"中日韩统一表意文字"));
+    }
+}
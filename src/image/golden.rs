@@ -0,0 +1,237 @@
+// 渲染流程的快照测试
+//
+// 输入经过与实际回答相同的修复、过滤与HTML转换流程。
+// `golden/` 目录下每个 `名称.md` 对应一个 `名称.html` 快照，比较完整的HTML（包括样式），
+// 任何排版或样式改动都会导致测试失败。能找到 wkhtmltoimage 时，还会渲染图片并与
+// 参考图片 `名称.png` 做感知比较。参考图片依赖渲染环境的字体，需在装有 wkhtmltoimage
+// 的环境中生成后提交，缺少参考图片的用例只输出警告并跳过图片比较。
+//
+// 有意修改样式后，使用 `UPDATE_SNAPSHOTS=1 cargo test golden` 重新生成快照并检查差异。
+
+use image::{GrayImage, ImageBuffer, Luma};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use uuid::Uuid;
+
+use super::tests::test_config;
+use super::ImageGenerator;
+
+// 感知比较时的分块边长（像素）
+const BLOCK_SIZE: u32 = 8;
+// 分块平均亮度差超过该值视为不同
+const BLOCK_THRESHOLD: f64 = 12.0;
+// 不同分块所占比例超过该值时测试失败
+const MAX_DIFF_RATIO: f64 = 0.005;
+
+fn golden_dir() -> PathBuf {
+    Path::new(file!()).with_file_name("golden")
+}
+
+fn update_snapshots() -> bool {
+    env::var("UPDATE_SNAPSHOTS")
+        .map(|v| v == "1")
+        .unwrap_or(false)
+}
+
+// 检查 wkhtmltoimage 是否可用
+fn renderer_available() -> bool {
+    let path = env::var("WKHTMLTOIMAGE_PATH")
+        .ok()
+        .filter(|p| !p.is_empty())
+        .unwrap_or_else(|| "wkhtmltoimage".to_string());
+    Command::new(path)
+        .arg("--version")
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+fn fixtures() -> Vec<(String, PathBuf)> {
+    let mut fixtures: Vec<(String, PathBuf)> = fs::read_dir(golden_dir())
+        .expect("读取快照目录失败")
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().map(|e| e == "md").unwrap_or(false))
+        .map(|path| {
+            let name = path.file_stem().unwrap().to_string_lossy().to_string();
+            (name, path)
+        })
+        .collect();
+    fixtures.sort();
+    fixtures
+}
+
+// 找出第一处不同的行，便于定位差异
+fn first_difference(expected: &str, actual: &str) -> String {
+    for (i, (e, a)) in expected.lines().zip(actual.lines()).enumerate() {
+        if e != a {
+            return format!(
+                "第 {} 行\n  期望: {}\n  实际: {}",
+                i + 1,
+                e.trim(),
+                a.trim()
+            );
+        }
+    }
+    format!(
+        "行数不同: 期望 {} 行，实际 {} 行",
+        expected.lines().count(),
+        actual.lines().count()
+    )
+}
+
+/// 比较两张图片，返回亮度差异超过阈值的分块比例
+///
+/// 按分块平均亮度比较，可以容忍抗锯齿与字体微调造成的像素级差异。
+/// 尺寸不同时返回 `Err`。
+fn perceptual_diff(expected: &GrayImage, actual: &GrayImage) -> Result<f64, String> {
+    if expected.dimensions() != actual.dimensions() {
+        return Err(format!(
+            "图片尺寸不同: 期望 {:?}，实际 {:?}",
+            expected.dimensions(),
+            actual.dimensions()
+        ));
+    }
+    let (width, height) = expected.dimensions();
+    let mut total = 0usize;
+    let mut different = 0usize;
+    for by in (0..height).step_by(BLOCK_SIZE as usize) {
+        for bx in (0..width).step_by(BLOCK_SIZE as usize) {
+            let (mut sum_e, mut sum_a, mut count) = (0f64, 0f64, 0f64);
+            for y in by..(by + BLOCK_SIZE).min(height) {
+                for x in bx..(bx + BLOCK_SIZE).min(width) {
+                    sum_e += expected.get_pixel(x, y)[0] as f64;
+                    sum_a += actual.get_pixel(x, y)[0] as f64;
+                    count += 1.0;
+                }
+            }
+            total += 1;
+            if ((sum_e - sum_a) / count).abs() > BLOCK_THRESHOLD {
+                different += 1;
+            }
+        }
+    }
+    Ok(if total == 0 {
+        0.0
+    } else {
+        different as f64 / total as f64
+    })
+}
+
+#[test]
+fn html_snapshots() {
+    let generator = ImageGenerator::new(&test_config()).expect("创建 ImageGenerator 失败");
+    let fixtures = fixtures();
+    assert!(!fixtures.is_empty(), "快照目录为空");
+
+    let mut failures = Vec::new();
+    for (name, path) in &fixtures {
+        let markdown = fs::read_to_string(path).unwrap();
+        let html = generator.markdown_to_html(&markdown);
+        let snapshot_path = path.with_extension("html");
+
+        if update_snapshots() {
+            fs::write(&snapshot_path, &html).unwrap();
+            continue;
+        }
+        match fs::read_to_string(&snapshot_path) {
+            Ok(expected) if expected == html => {}
+            Ok(expected) => failures.push(format!(
+                "{}: HTML与快照不符，{}",
+                name,
+                first_difference(&expected, &html)
+            )),
+            Err(_) => failures.push(format!("{}: 缺少快照 {}", name, snapshot_path.display())),
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{}\n如为有意修改，请使用 UPDATE_SNAPSHOTS=1 重新生成快照",
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn image_snapshots() {
+    if !renderer_available() {
+        eprintln!("未找到 wkhtmltoimage，跳过图片快照比较");
+        return;
+    }
+
+    let output_dir = env::temp_dir().join(format!("golden_{}", Uuid::new_v4()));
+    let mut config = test_config();
+    config.image_output_dir = output_dir.clone();
+    let generator = ImageGenerator::new(&config).expect("创建 ImageGenerator 失败");
+
+    let mut failures = Vec::new();
+    for (name, path) in fixtures() {
+        let reference_path = path.with_extension("png");
+        if !reference_path.exists() && !update_snapshots() {
+            eprintln!(
+                "{}: 缺少参考图片 {}，跳过图片比较",
+                name,
+                reference_path.display()
+            );
+            continue;
+        }
+
+        let markdown = fs::read_to_string(&path).unwrap();
+        let html = generator.markdown_to_html(&markdown);
+        let rendered = generator
+            .render_html_to_image(
+                &html,
+                &output_dir.join(format!("{}.png", name)),
+                config.render_width,
+            )
+            .expect("渲染图片失败");
+
+        if update_snapshots() {
+            fs::copy(&rendered, &reference_path).unwrap();
+            continue;
+        }
+        let expected = image::open(&reference_path).unwrap().to_luma8();
+        let actual = image::open(&rendered).unwrap().to_luma8();
+        match perceptual_diff(&expected, &actual) {
+            Ok(ratio) if ratio <= MAX_DIFF_RATIO => {}
+            Ok(ratio) => failures.push(format!(
+                "{}: {:.2}% 的区域与参考图片不同，渲染结果: {}",
+                name,
+                ratio * 100.0,
+                rendered.display()
+            )),
+            Err(e) => failures.push(format!("{}: {}，渲染结果: {}", name, e, rendered.display())),
+        }
+    }
+    // 失败时保留渲染结果以便对比
+    if failures.is_empty() {
+        let _ = fs::remove_dir_all(&output_dir);
+    }
+    assert!(
+        failures.is_empty(),
+        "{}\n如为有意修改，请使用 UPDATE_SNAPSHOTS=1 重新生成参考图片",
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn perceptual_diff_tolerates_small_changes() {
+    let base: GrayImage = ImageBuffer::from_pixel(64, 64, Luma([255u8]));
+
+    // 单个像素的抗锯齿差异不影响结果
+    let mut antialiased = base.clone();
+    antialiased.put_pixel(10, 10, Luma([200]));
+    assert_eq!(perceptual_diff(&base, &antialiased), Ok(0.0));
+
+    // 整块颜色变化会被检测到
+    let mut changed = base.clone();
+    for y in 0..16 {
+        for x in 0..16 {
+            changed.put_pixel(x, y, Luma([0]));
+        }
+    }
+    assert!(perceptual_diff(&base, &changed).unwrap() > MAX_DIFF_RATIO);
+
+    let taller: GrayImage = ImageBuffer::from_pixel(64, 80, Luma([255u8]));
+    assert!(perceptual_diff(&base, &taller).is_err());
+}
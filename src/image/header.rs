@@ -0,0 +1,144 @@
+use base64::Engine;
+use chrono::{DateTime, Local};
+use std::io::Cursor;
+
+/// 回答图片头部显示的元数据，由调用方传入而不写入Markdown
+#[derive(Debug, Clone, Default)]
+pub struct AnswerMeta {
+    /// 用户的问题
+    pub question: Option<String>,
+    /// 提问者的显示名称
+    pub asker_name: Option<String>,
+    /// 提问者头像（data URI）
+    pub avatar_data_uri: Option<String>,
+    /// 提问时间
+    pub timestamp: Option<DateTime<Local>>,
+    /// 会话ID
+    pub session_id: Option<String>,
+    /// FastGPT 应用名称
+    pub app_name: Option<String>,
+    /// 服务器名称，用于页脚中的 {guild} 占位符
+    pub guild_name: Option<String>,
+}
+
+// 头部问题的最大显示字符数
+const QUESTION_PREVIEW_CHARS: usize = 80;
+// 头像缩放后的边长
+const AVATAR_SIZE: u32 = 64;
+
+impl AnswerMeta {
+    /// 生成头部HTML，所有字段均已转义
+    ///
+    /// 没有任何可显示的信息时（如渲染预览）返回空字符串。
+    pub fn header_html(&self) -> String {
+        if self.avatar_data_uri.is_none()
+            && self.question.is_none()
+            && self.asker_name.is_none()
+            && self.timestamp.is_none()
+            && self.session_id.is_none()
+            && self.app_name.is_none()
+        {
+            return String::new();
+        }
+
+        let mut html = String::from("<div class=\"answer-header\">");
+
+        if let Some(avatar) = &self.avatar_data_uri {
+            html.push_str(&format!(
+                "<img class=\"answer-avatar\" src=\"{}\">",
+                html_escape::encode_double_quoted_attribute(avatar)
+            ));
+        }
+
+        html.push_str("<div class=\"answer-header-body\">");
+        if let Some(question) = &self.question {
+            let question = question.split_whitespace().collect::<Vec<_>>().join(" ");
+            let mut preview: String = question.chars().take(QUESTION_PREVIEW_CHARS).collect();
+            if question.chars().count() > QUESTION_PREVIEW_CHARS {
+                preview.push_str("...");
+            }
+            html.push_str(&format!(
+                "<div class=\"answer-question\">{}</div>",
+                html_escape::encode_text(&preview)
+            ));
+        }
+
+        let mut details = Vec::new();
+        if let Some(name) = &self.asker_name {
+            details.push(format!("👤 {}", name));
+        }
+        if let Some(timestamp) = &self.timestamp {
+            details.push(format!("🕒 {}", timestamp.format("%Y-%m-%d %H:%M")));
+        }
+        if let Some(session_id) = &self.session_id {
+            let short: String = session_id.chars().take(8).collect();
+            details.push(format!("🔖 {}", short));
+        }
+        if let Some(app_name) = &self.app_name {
+            details.push(format!("🤖 {}", app_name));
+        }
+        if !details.is_empty() {
+            html.push_str(&format!(
+                "<div class=\"answer-meta\">{}</div>",
+                html_escape::encode_text(&details.join("　"))
+            ));
+        }
+        html.push_str("</div></div>");
+        html
+    }
+
+    /// 将页脚模板中的占位符替换为实际值
+    pub fn footer_text(&self, template: &str) -> String {
+        template.replace("{guild}", self.guild_name.as_deref().unwrap_or(""))
+    }
+}
+
+/// 将头像图片缩放并转换为PNG格式的 data URI
+///
+/// Discord 头像通常是 WebP，wkhtmltoimage 无法直接显示。
+pub fn avatar_data_uri(bytes: &[u8]) -> Option<String> {
+    let img = image::load_from_memory(bytes).ok()?;
+    let img = img.resize_to_fill(
+        AVATAR_SIZE,
+        AVATAR_SIZE,
+        image::imageops::FilterType::Triangle,
+    );
+    let mut png = Vec::new();
+    img.write_to(&mut Cursor::new(&mut png), image::ImageOutputFormat::Png)
+        .ok()?;
+    Some(format!(
+        "data:image/png;base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(png)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_escapes_and_shortens_question() {
+        let meta = AnswerMeta {
+            question: Some(format!("<script>{}</script>", "问".repeat(100))),
+            asker_name: Some("小明".to_string()),
+            session_id: Some("0123456789abcdef".to_string()),
+            ..Default::default()
+        };
+        let html = meta.header_html();
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("..."));
+        assert!(html.contains("01234567"));
+        assert!(!html.contains("89abcdef"));
+    }
+
+    #[test]
+    fn footer_replaces_guild_placeholder() {
+        let meta = AnswerMeta {
+            guild_name: Some("LNDC".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(meta.footer_text("由 {guild} 提供"), "由 LNDC 提供");
+        // 只有服务器名称时不显示头部
+        assert!(meta.header_html().is_empty());
+    }
+}
@@ -1,37 +1,129 @@
+mod cache;
+mod encode;
+mod fonts;
+#[cfg(test)]
+mod golden;
+mod header;
+mod paginate;
+mod profile;
+mod remote;
+mod repair;
+mod sanitize;
+mod temp;
+
 use anyhow::{Context, Result};
 use pulldown_cmark::{html, Options, Parser};
+use std::ffi::OsStr;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use tracing::{debug, error, info};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
 use crate::config::Config;
 
+pub use self::cache::remove_cached_copies;
+use self::cache::RenderCache;
+pub use self::encode::{EncodeOptions, OutputFormat};
+pub use self::fonts::FontFace;
+use self::header::avatar_data_uri;
+pub use self::header::AnswerMeta;
+pub use self::paginate::split_markdown_pages;
+pub use self::profile::{RenderPreset, RenderProfile};
+pub use self::remote::InlineImages;
+pub use self::repair::repair_markdown;
+pub use self::temp::SweepReport;
+pub(crate) use self::temp::{commit_partial, partial_path};
+
 #[derive(Debug)]
 pub struct ImageGenerator {
     config: Config,
+    // 已注册的字体回退链
+    fonts: Vec<FontFace>,
+    // 下载回答中引用的远程图片
+    http: reqwest::Client,
+    // 渲染结果缓存
+    cache: RenderCache,
+    // 渲染器版本，作为缓存键的一部分，首次使用时获取
+    image_renderer_version: OnceLock<String>,
+    pdf_renderer_version: OnceLock<String>,
 }
 
 impl ImageGenerator {
     pub fn new(config: &Config) -> Result<Self> {
-        // 确保至少一个字体文件存在
-        let _font_exists = config.font_paths.iter().any(|path| path.exists());
+        // 注册所有已配置的字体，并检查字符覆盖情况
+        let fonts = fonts::load_fonts(&config.font_paths);
+        if fonts.is_empty() {
+            warn!("没有可用的字体文件，将使用系统默认字体");
+        } else {
+            let missing = fonts::missing_blocks(&fonts);
+            if !missing.is_empty() {
+                warn!(
+                    "已配置的字体无法显示以下Unicode区块: {}，请在 FONT_PATHS 中补充相应字体",
+                    missing.join("、")
+                );
+            }
+        }
+
+        let http = remote::build_client(Duration::from_secs(config.remote_image_timeout_secs))?;
+
+        let cache = RenderCache::new(
+            config.image_output_dir.join("cache"),
+            config.render_cache_max_mb * 1024 * 1024,
+        );
 
         Ok(Self {
             config: config.clone(),
+            fonts,
+            http,
+            cache,
+            image_renderer_version: OnceLock::new(),
+            pdf_renderer_version: OnceLock::new(),
         })
     }
 
+    /// 预先下载回答中引用的远程图片，转换为可内联的 data URI
+    ///
+    /// 未启用 `ALLOW_REMOTE_RESOURCES` 时返回空结果，所有图片都显示为占位框。
+    pub async fn fetch_remote_images(&self, markdown: &str) -> InlineImages {
+        if !self.config.allow_remote_resources {
+            return InlineImages::default();
+        }
+        let limits = remote::RemoteImageLimits {
+            max_count: self.config.remote_image_max_count,
+            max_bytes: self.config.remote_image_max_bytes,
+            timeout: Duration::from_secs(self.config.remote_image_timeout_secs),
+            max_width: self
+                .render_profile(RenderPreset::Desktop)
+                .page_layout(self.config.max_page_height)
+                .content_width(),
+        };
+        remote::fetch_images(&self.http, markdown, &limits).await
+    }
+
+    /// 下载提问者头像并转换为 data URI，失败时返回 `None`（不显示头像）
+    ///
+    /// 与远程图片共用同一个只允许访问公网地址的客户端。
+    pub async fn fetch_avatar(&self, url: &str) -> Option<String> {
+        let response = self.http.get(url).send().await.ok()?;
+        if !response.status().is_success() {
+            debug!("下载头像失败: {} {}", url, response.status());
+            return None;
+        }
+        let bytes = response.bytes().await.ok()?;
+        avatar_data_uri(&bytes)
+    }
+
     /// 从Markdown文本创建图片
+    ///
+    /// 输出格式由配置决定，返回的路径扩展名可能与 `output_path` 不同。
     pub fn create_image_from_markdown(
         &self,
         markdown: &str,
         output_path: &Path,
     ) -> Result<PathBuf> {
-        // 创建临时HTML文件
-        let temp_html_path = self.create_temp_html_from_markdown(markdown)?;
-
         // 确保输出目录存在
         if let Some(parent) = output_path.parent() {
             if !parent.exists() {
@@ -40,17 +132,160 @@ impl ImageGenerator {
         }
 
         // 使用wkhtmltoimage渲染HTML为图片
-        let image_path = self.render_markdown_to_image(&temp_html_path, output_path)?;
+        let html_content = self.markdown_to_html(markdown);
+        let width = self.default_profile().width;
+        let image_path = self.render_html_to_image(&html_content, output_path, width)?;
         debug!("图片已渲染至: {}", image_path.display());
 
-        // 删除临时HTML文件
+        Ok(image_path)
+    }
+
+    /// 从Markdown文本创建分页图片
+    ///
+    /// 内容超过 `max_page_height` 时在块边界处分页，每页页脚带有 "1/3" 样式的页码。
+    /// 只有一页时输出到 `output_path`，否则输出为 `{文件名}_{页码}.{扩展名}`。
+    /// 传入 `meta` 时在第一页顶部显示问题、提问者等信息；
+    /// `images` 为 [`fetch_remote_images`](Self::fetch_remote_images) 预先下载的图片；
+    /// `profile` 决定画布宽度、字号等排版参数。
+    pub fn create_images_from_markdown(
+        &self,
+        markdown: &str,
+        output_path: &Path,
+        meta: Option<&AnswerMeta>,
+        images: &InlineImages,
+        profile: &RenderProfile,
+    ) -> Result<Vec<PathBuf>> {
+        // 确保输出目录存在
+        if let Some(parent) = output_path.parent() {
+            if !parent.exists() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        let markdown = &repair(markdown);
+        let pages =
+            split_markdown_pages(markdown, &profile.page_layout(self.config.max_page_height));
+        if pages.len() <= 1 {
+            let html_content = self.markdown_to_html_page(markdown, None, meta, images, profile);
+            return Ok(vec![self.render_html_to_image(
+                &html_content,
+                output_path,
+                profile.width,
+            )?]);
+        }
+
+        let stem = output_path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "response".to_string());
+        let ext = output_path
+            .extension()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "png".to_string());
+
+        let total = pages.len();
+        let mut image_paths = Vec::with_capacity(total);
+        for (i, page) in pages.iter().enumerate() {
+            let page_path = output_path.with_file_name(format!("{}_{}.{}", stem, i + 1, ext));
+            let html_content =
+                self.markdown_to_html_page(page, Some((i + 1, total)), meta, images, profile);
+            image_paths.push(self.render_html_to_image(
+                &html_content,
+                &page_path,
+                profile.width,
+            )?);
+        }
+        debug!("已分 {} 页渲染图片", total);
+
+        Ok(image_paths)
+    }
+
+    /// 从Markdown文本创建PDF文档
+    ///
+    /// 与图片使用相同的HTML与主题，文字可搜索、链接可点击；由 wkhtmltopdf 自动分页。
+    pub fn create_pdf_from_markdown(
+        &self,
+        markdown: &str,
+        output_path: &Path,
+        meta: Option<&AnswerMeta>,
+        images: &InlineImages,
+    ) -> Result<PathBuf> {
+        // 确保输出目录存在
+        if let Some(parent) = output_path.parent() {
+            if !parent.exists() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        // PDF按A4分页，始终使用桌面布局
+        let profile = self.render_profile(RenderPreset::Desktop);
+        let html_content =
+            self.markdown_to_html_page(&repair(markdown), None, meta, images, &profile);
+        let version = self
+            .pdf_renderer_version
+            .get_or_init(|| renderer_version(&wkhtmltopdf_path()));
+        let key = RenderCache::key(&["pdf", &html_content, "A4", version]);
+        if let Some(pdf_path) = self.cache.get(&key, "pdf", output_path) {
+            return Ok(pdf_path);
+        }
+
+        let temp_html_path = self.write_temp_html(&html_content)?;
+        let result = self.render_html_to_pdf(&temp_html_path, output_path);
         let _ = fs::remove_file(temp_html_path);
+        if let Ok(pdf_path) = &result {
+            self.cache.put(&key, pdf_path);
+        }
+        result
+    }
 
-        Ok(image_path)
+    /// 将完整HTML写入临时文件并渲染为图片
+    ///
+    /// 相同HTML、宽度、编码参数与渲染器版本的结果直接从缓存复制。
+    fn render_html_to_image(
+        &self,
+        html_content: &str,
+        output_path: &Path,
+        width: u32,
+    ) -> Result<PathBuf> {
+        let version = self
+            .image_renderer_version
+            .get_or_init(|| renderer_version(&wkhtmltoimage_path()));
+        let options = self.encode_options();
+        let key = RenderCache::key(&[
+            "image",
+            html_content,
+            &width.to_string(),
+            &format!("{:?}", options),
+            version,
+        ]);
+        if let Some(image_path) = self
+            .cache
+            .get(&key, options.format.extension(), output_path)
+        {
+            return Ok(image_path);
+        }
+
+        let temp_html_path = self.write_temp_html(html_content)?;
+        let result = self.render_markdown_to_image(&temp_html_path, output_path, width);
+        let _ = fs::remove_file(temp_html_path);
+        if let Ok(image_path) = &result {
+            self.cache.put(&key, image_path);
+        }
+        result
+    }
+
+    /// 根据布局预设生成渲染参数
+    pub fn render_profile(&self, preset: RenderPreset) -> RenderProfile {
+        RenderProfile::from_preset(preset, &self.config)
+    }
+
+    /// 配置中默认布局的渲染参数
+    fn default_profile(&self) -> RenderProfile {
+        self.render_profile(self.config.render_profile)
     }
 
-    /// 创建临时HTML文件
-    fn create_temp_html_from_markdown(&self, markdown: &str) -> Result<PathBuf> {
+    /// 将HTML写入临时文件
+    fn write_temp_html(&self, html_content: &str) -> Result<PathBuf> {
         // 创建临时目录
         let temp_dir = self.config.image_output_dir.join("temp");
         if !temp_dir.exists() {
@@ -61,59 +296,58 @@ impl ImageGenerator {
         let temp_html_filename = format!("temp_{}.html", Uuid::new_v4());
         let temp_html_path = temp_dir.join(&temp_html_filename);
 
-        // 将Markdown转换为HTML
-        let html_content = self.markdown_to_html(markdown);
-
         // 写入临时HTML文件
         fs::write(&temp_html_path, html_content)?;
 
         Ok(temp_html_path)
     }
 
+    /// 删除修改时间早于 `max_age` 的临时文件，返回清理结果
+    ///
+    /// 清理 `image_output_dir/temp` 中的全部文件，以及缓存目录和会话目录中写入中途残留的隐藏临时文件。
+    pub fn sweep_temp_files(&self, max_age: Duration) -> Result<SweepReport> {
+        temp::sweep_temp_files(
+            &self.config.image_output_dir.join("temp"),
+            &[
+                self.config.image_output_dir.join("cache"),
+                self.config.data_dir.join("sessions"),
+            ],
+            max_age,
+            SystemTime::now(),
+        )
+    }
+
     /// 将Markdown转换为HTML
     pub(crate) fn markdown_to_html(&self, markdown: &str) -> String {
-        // 获取字体设置
-        let font_paths = self
-            .config
-            .font_paths
-            .iter()
-            .filter(|path| path.exists())
-            .map(|path| path.to_string_lossy().to_string())
-            .collect::<Vec<_>>();
-
-        let font_path = if !font_paths.is_empty() {
-            // 使用第一个有效的字体路径
-            font_paths[0].clone()
-        } else {
-            // 如果没有有效的字体，使用空字符串
-            "".to_string()
-        };
-
-        let font_family = if !font_paths.is_empty() {
-            "'LXGW WenKai', 'Microsoft YaHei', 'SimHei', sans-serif".to_string()
-        } else {
-            "sans-serif".to_string()
-        };
+        self.markdown_to_html_page(
+            &repair(markdown),
+            None,
+            None,
+            &InlineImages::default(),
+            &self.default_profile(),
+        )
+    }
 
-        // 处理字体路径，确保能正确在wkhtmltoimage中使用
-        let font_path_for_css = if !font_path.is_empty() {
-            let path = Path::new(&font_path);
-            if path.is_absolute() {
-                // 已经是绝对路径，直接使用
-                path.to_string_lossy().to_string()
-            } else {
-                // 对于相对路径，转换为绝对路径
-                let current_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
-                current_dir.join(path).to_string_lossy().to_string()
-            }
-        } else {
-            // 默认字体路径，使用绝对路径
-            let current_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
-            current_dir
-                .join("assets/fonts/LXGWWenKaiGBScreen.ttf")
-                .to_string_lossy()
-                .to_string()
-        };
+    /// 将Markdown转换为HTML
+    ///
+    /// `page` 为 (当前页, 总页数) 时在页脚显示页码；`meta` 存在且启用头部时，
+    /// 在第一页顶部显示回答信息。只有 `images` 中已内联的图片会被显示。
+    pub(crate) fn markdown_to_html_page(
+        &self,
+        markdown: &str,
+        page: Option<(usize, usize)>,
+        meta: Option<&AnswerMeta>,
+        images: &InlineImages,
+        profile: &RenderProfile,
+    ) -> String {
+        // 为每个已注册字体生成 @font-face 规则，并构建回退字体栈
+        let font_faces = self
+            .fonts
+            .iter()
+            .map(FontFace::css_rule)
+            .collect::<Vec<_>>()
+            .join("\n");
+        let font_family = fonts::font_family_stack(&self.fonts);
 
         // 创建HTML头部和样式
         let html_header = format!(
@@ -123,12 +357,7 @@ impl ImageGenerator {
         <head>
             <meta charset="UTF-8">
             <style>
-                @font-face {{
-                    font-family: 'LXGW WenKai';
-                    src: local('LXGW WenKai'), url('file://{font_path_for_css}') format('truetype');
-                    font-weight: normal;
-                    font-style: normal;
-                }}
+                {font_faces}
                 @font-face {{
                     font-family: 'Code Font';
                     src: local('Consolas'), local('Source Code Pro'), local('DejaVu Sans Mono'), local('Courier New'), local('Menlo');
@@ -137,12 +366,12 @@ impl ImageGenerator {
                 }}
                 body {{
                     font-family: {font_family};
-                    line-height: 1.8;
+                    line-height: {line_height};
                     padding: {padding}px;
                     background-color: #2b2b2b;  /* 稍微暗一点的灰色背景 */
                     color: #f0f0f0;  /* 更柔和的白色文字 */
                     font-size: {font_size}px;
-                    width: 1024px;
+                    width: {width}px;
                     margin: 0 auto;
                     word-wrap: break-word;
                     overflow-wrap: break-word;
@@ -329,30 +558,103 @@ impl ImageGenerator {
                     vertical-align: super;
                     font-size: 0.8em;
                 }}
+                /* 无法加载的图片占位框 */
+                .image-placeholder {{
+                    display: inline-block;
+                    padding: 12px 16px;
+                    border: 1px dashed #666666;
+                    border-radius: 8px;
+                    color: #aaaaaa;
+                    background-color: #323232;
+                }}
+                /* 回答信息头部 */
+                .answer-header {{
+                    display: -webkit-box;
+                    display: flex;
+                    -webkit-box-align: center;
+                    align-items: center;
+                    padding: 16px 20px;
+                    margin-bottom: 24px;
+                    background-color: #353535;
+                    border-radius: 8px;
+                    border-left: 4px solid #78a9ff;
+                }}
+                .answer-avatar {{
+                    width: 56px;
+                    height: 56px;
+                    border-radius: 50%;
+                    margin: 0 16px 0 0;
+                    box-shadow: none;
+                }}
+                .answer-header-body {{
+                    -webkit-box-flex: 1;
+                    flex: 1;
+                }}
+                .answer-question {{
+                    color: #ffffff;
+                    font-weight: 600;
+                    line-height: 1.5;
+                }}
+                .answer-meta {{
+                    margin-top: 6px;
+                    color: #aaaaaa;
+                    font-size: 0.75em;
+                }}
+                /* 页脚：服务器标识与分页页码 */
+                .page-footer {{
+                    margin-top: 30px;
+                    padding-top: 10px;
+                    border-top: 1px solid #555555;
+                    color: #999999;
+                    font-size: 0.8em;
+                    overflow: hidden;
+                }}
+                .footer-brand {{
+                    float: left;
+                }}
+                .footer-page {{
+                    float: right;
+                }}
             </style>
         </head>
         <body>
         "#,
             font_family = font_family,
-            padding = self.config.padding,
-            font_size = self.config.font_size,
-            code_font_size = self.config.font_size - 2,
-            font_path_for_css = font_path_for_css
+            line_height = profile.line_height,
+            padding = profile.padding,
+            font_size = profile.font_size,
+            width = profile.width,
+            code_font_size = profile.font_size - 2,
+            font_faces = font_faces
         );
 
-        // 使用pulldown-cmark解析Markdown
-        // 启用所有扩展功能
-        let mut options = Options::empty();
-        options.insert(Options::ENABLE_STRIKETHROUGH);
-        options.insert(Options::ENABLE_TABLES);
-        options.insert(Options::ENABLE_FOOTNOTES);
-        options.insert(Options::ENABLE_TASKLISTS);
+        let mut html_content = markdown_body_html(markdown, images);
 
-        let parser = Parser::new_ext(markdown, options);
+        // 第一页顶部显示回答信息
+        if self.config.image_header && page.map(|(current, _)| current).unwrap_or(1) == 1 {
+            if let Some(meta) = meta {
+                html_content.insert_str(0, &meta.header_html());
+            }
+        }
 
-        // 转换为HTML
-        let mut html_content = String::new();
-        html::push_html(&mut html_content, parser);
+        // 页脚：服务器标识与分页页码
+        let footer_text = self
+            .config
+            .image_footer_text
+            .as_deref()
+            .map(|template| match meta {
+                Some(meta) => meta.footer_text(template),
+                None => AnswerMeta::default().footer_text(template),
+            })
+            .filter(|text| !text.trim().is_empty());
+        if footer_text.is_some() || page.is_some() {
+            html_content.push_str(&format!(
+                "<div class=\"page-footer\"><span class=\"footer-brand\">{}</span><span class=\"footer-page\">{}</span></div>",
+                html_escape::encode_text(footer_text.as_deref().unwrap_or("")),
+                page.map(|(current, total)| format!("{}/{}", current, total))
+                    .unwrap_or_default()
+            ));
+        }
 
         // 构建完整的HTML
         let result = format!("{}{}</body></html>", html_header, html_content);
@@ -361,33 +663,49 @@ impl ImageGenerator {
     }
 
     /// 将HTML渲染为图片
-    fn render_markdown_to_image(&self, html_path: &Path, output_path: &Path) -> Result<PathBuf> {
+    fn render_markdown_to_image(
+        &self,
+        html_path: &Path,
+        output_path: &Path,
+        width: u32,
+    ) -> Result<PathBuf> {
         // 构建wkhtmltoimage命令
-        let wkhtmltoimage_path = match std::env::var("WKHTMLTOIMAGE_PATH") {
-            Ok(path) if !path.is_empty() => path,
-            _ => "wkhtmltoimage".to_string(),
-        };
+        let wkhtmltoimage_path = wkhtmltoimage_path();
 
         // 获取当前工作目录作为基础路径
         let current_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
         let _current_dir_str = current_dir.to_string_lossy();
 
+        // 只允许读取字体目录，防止模型输出引用服务器上的其他文件
+        let allowed_dirs = self.allowed_local_dirs();
+
+        // wkhtmltoimage 始终输出PNG，之后再按配置转换格式；先写入中间文件，完成后再重命名
+        let png_path = temp::partial_path(&output_path.with_extension("png"));
+
         // 使用wkhtmltoimage渲染HTML为图片
         let output = Command::new(&wkhtmltoimage_path)
+            .arg("--format")
+            .arg("png")
             .arg("--quality")
             .arg("95") // 提高图片质量
             .arg("--width")
-            .arg("1024") // 固定宽度
+            .arg(width.to_string()) // 与样式中的画布宽度一致
             .arg("--encoding")
             .arg("UTF-8") // 确保使用UTF-8编码
-            .arg("--enable-local-file-access") // 允许访问本地文件
+            .arg("--disable-local-file-access") // 禁止读取本地文件，仅允许字体目录
+            .args(
+                allowed_dirs
+                    .iter()
+                    .flat_map(|dir| [OsStr::new("--allow"), dir.as_os_str()]),
+            )
             .arg("--disable-javascript") // 禁用JavaScript以提高稳定性
             .arg(html_path.to_str().unwrap())
-            .arg(output_path.to_str().unwrap())
+            .arg(png_path.to_str().unwrap())
             .output()
             .context("运行wkhtmltoimage失败，请确保已安装")?;
 
         if !output.status.success() {
+            let _ = fs::remove_file(&png_path);
             error!("wkhtmltoimage命令执行失败");
             error!("错误输出: {}", String::from_utf8_lossy(&output.stderr));
             return Err(anyhow::anyhow!(
@@ -396,39 +714,252 @@ impl ImageGenerator {
             ));
         }
 
-        info!("图片渲染成功: {}", output_path.display());
-        Ok(output_path.to_path_buf())
+        // 转换为配置的输出格式
+        let converted = match encode::convert_rendered(&png_path, &self.encode_options()) {
+            Ok(converted) => converted,
+            Err(e) => {
+                let _ = fs::remove_file(&png_path);
+                return Err(e);
+            }
+        };
+        let image_path = temp::commit_partial(&converted, output_path)?;
+        info!("图片渲染成功: {}", image_path.display());
+        Ok(image_path)
+    }
+
+    /// 将HTML渲染为PDF
+    fn render_html_to_pdf(&self, html_path: &Path, output_path: &Path) -> Result<PathBuf> {
+        let wkhtmltopdf_path = wkhtmltopdf_path();
+
+        let allowed_dirs = self.allowed_local_dirs();
+        let partial_path = temp::partial_path(&output_path.with_extension("pdf"));
+
+        let output = Command::new(&wkhtmltopdf_path)
+            .arg("--quiet")
+            .arg("--page-size")
+            .arg("A4")
+            .args(["--margin-top", "0", "--margin-bottom", "0"])
+            .args(["--margin-left", "0", "--margin-right", "0"])
+            .arg("--encoding")
+            .arg("UTF-8")
+            .arg("--background") // 保留深色主题背景
+            .arg("--enable-external-links") // 保留可点击的链接
+            .arg("--disable-local-file-access") // 禁止读取本地文件，仅允许字体目录
+            .args(
+                allowed_dirs
+                    .iter()
+                    .flat_map(|dir| [OsStr::new("--allow"), dir.as_os_str()]),
+            )
+            .arg("--disable-javascript")
+            .arg(html_path.to_str().unwrap())
+            .arg(partial_path.to_str().unwrap())
+            .output()
+            .context("运行wkhtmltopdf失败，请确保已安装")?;
+
+        if !output.status.success() {
+            let _ = fs::remove_file(&partial_path);
+            error!("wkhtmltopdf命令执行失败");
+            error!("错误输出: {}", String::from_utf8_lossy(&output.stderr));
+            return Err(anyhow::anyhow!(
+                "wkhtmltopdf命令执行失败: {}",
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+
+        let pdf_path = temp::commit_partial(&partial_path, output_path)?;
+        info!("PDF渲染成功: {}", pdf_path.display());
+        Ok(pdf_path)
+    }
+
+    /// 渲染时允许读取的本地目录（仅字体所在目录）
+    fn allowed_local_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = self
+            .fonts
+            .iter()
+            .filter_map(|f| f.path.parent().map(Path::to_path_buf))
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+        dirs
+    }
+
+    /// 当前配置的编码参数
+    fn encode_options(&self) -> EncodeOptions {
+        EncodeOptions {
+            format: self.config.image_format,
+            jpeg_quality: self.config.jpeg_quality,
+            optimize_png: self.config.png_optimize,
+        }
+    }
+
+    /// 确保图片不超过上传限制，必要时重新编码或缩小尺寸
+    ///
+    /// 返回的路径扩展名可能与原图不同。
+    pub fn fit_to_upload_limit(&self, image_path: &Path, max_bytes: u64) -> Result<PathBuf> {
+        encode::fit_to_size(image_path, max_bytes, &self.encode_options())
+    }
+}
+
+/// 将Markdown转换为不含样式的HTML片段，用于网页分享
+///
+/// 与渲染图片时一样修复并过滤内容；远程图片不会被下载，显示为占位框。
+pub fn markdown_to_html_fragment(markdown: &str) -> String {
+    markdown_body_html(&repair(markdown), &InlineImages::default())
+}
+
+// 使用pulldown-cmark解析Markdown，过滤原始HTML与不安全的链接、图片
+fn markdown_body_html(markdown: &str, images: &InlineImages) -> String {
+    // 启用所有扩展功能
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_TASKLISTS);
+
+    let parser = Parser::new_ext(markdown, options);
+    let mut html_content = String::new();
+    html::push_html(&mut html_content, sanitize::sanitize_events(parser, images));
+    html_content
+}
+
+/// wkhtmltoimage 路径，可通过 WKHTMLTOIMAGE_PATH 指定
+fn wkhtmltoimage_path() -> String {
+    match std::env::var("WKHTMLTOIMAGE_PATH") {
+        Ok(path) if !path.is_empty() => path,
+        _ => "wkhtmltoimage".to_string(),
+    }
+}
+
+/// wkhtmltopdf 路径，可通过 WKHTMLTOPDF_PATH 指定
+fn wkhtmltopdf_path() -> String {
+    match std::env::var("WKHTMLTOPDF_PATH") {
+        Ok(path) if !path.is_empty() => path,
+        _ => "wkhtmltopdf".to_string(),
     }
 }
 
+/// 获取渲染器版本，升级渲染器后旧的缓存自动失效
+fn renderer_version(path: &str) -> String {
+    Command::new(path)
+        .arg("--version")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// 修复模型输出中的常见Markdown问题，并记录发现的问题
+fn repair(markdown: &str) -> String {
+    let report = repair_markdown(markdown);
+    if !report.defects.is_empty() {
+        debug!("已修复Markdown问题: {:?}", report.defects);
+    }
+    report.markdown
+}
+
 #[cfg(test)]
 mod tests {
-    use super::ImageGenerator;
+    use super::{ImageGenerator, InlineImages, RenderPreset};
     use crate::config::Config;
     use std::path::PathBuf;
 
-    #[test]
-    fn markdown_to_html_basic() {
-        // 构造简易配置
-        let config = Config {
+    /// 测试用的简易配置：不加载字体，输出PNG，保证HTML输出稳定
+    pub(super) fn test_config() -> Config {
+        Config {
             root_dir: PathBuf::from("."),
             data_dir: PathBuf::from("data"),
             fastgpt_api_url: String::new(),
             fastgpt_auth_token: String::new(),
+            fastgpt_app_name: None,
             image_output_dir: PathBuf::from("data/pic"),
             font_paths: vec![],
+            render_width: 1024,
             font_size: 24,
+            line_height: 1.8,
             padding: 30,
+            render_profile: crate::image::RenderPreset::Desktop,
+            max_page_height: 4000,
+            image_format: crate::image::OutputFormat::Png,
+            jpeg_quality: 90,
+            png_optimize: false,
+            allow_remote_resources: false,
+            remote_image_max_count: 5,
+            remote_image_max_bytes: 5 * 1024 * 1024,
+            remote_image_timeout_secs: 10,
+            image_header: true,
+            image_footer_text: None,
+            render_cache_max_mb: 0,
+            delivery_mode: crate::delivery::DeliveryMode::Image,
+            text_fallback: true,
+            text_use_embed: false,
+            attach_markdown: false,
+            code_attachments: crate::delivery::CodeAttachMode::Button,
             discord_token: String::new(),
             discord_channel_whitelist: vec![],
-            session_expiry: 0,
+            session_store: crate::session::SessionStoreKind::Fs,
+            retention: Default::default(),
+            cleanup_schedule: "6h".parse().unwrap(),
+            temp_sweep_schedule: "1h".parse().unwrap(),
+            temp_max_age_hours: 6,
+            job_jitter_secs: 0,
+            share_web_addr: None,
+            share_base_url: None,
             api_concurrency_limit: 1,
-        };
+        }
+    }
+
+    #[test]
+    fn fitting_stored_answer_image_keeps_it_in_session() {
+        use crate::session::{SessionManager, SessionStoreKind};
+
+        let temp = tempfile::tempdir().unwrap();
+        let manager = SessionManager::for_test(temp.path(), SessionStoreKind::Fs);
+        let session_id = manager.create_session("42").unwrap();
+        let source = manager.get_session_dir(&session_id).join("response_a.png");
+        // 噪点图片PNG压缩率低，超过上传限制
+        let mut seed: u32 = 1;
+        let img = image::RgbImage::from_fn(1000, 600, |_, _| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let [r, g, b, _] = seed.to_le_bytes();
+            image::Rgb([r, g, b])
+        });
+        img.save(&source).unwrap();
+        manager.record_artifacts(&session_id, &[&source]);
+
+        let gen = ImageGenerator::new(&test_config()).expect("创建 ImageGenerator 失败");
+        let max_bytes = 512 * 1024;
+        let fitted = gen.fit_to_upload_limit(&source, max_bytes).unwrap();
+        assert_ne!(fitted, source);
+        assert!(std::fs::metadata(&fitted).unwrap().len() <= max_bytes);
+        manager.record_artifacts(&session_id, &[&fitted]);
+
+        // 原图保留在会话中，再次查看时仍能找到
+        assert!(source.is_file());
+        assert_eq!(manager.answer_images(&session_id).unwrap(), [source]);
+    }
+
+    #[test]
+    fn markdown_to_html_basic() {
+        let config = test_config();
         let gen = ImageGenerator::new(&config).expect("创建 ImageGenerator 失败");
         let html = gen.markdown_to_html("# Hello\n\nWorld");
         assert!(html.contains("<h1>Hello</h1>"), "应包含 H1 标记");
         assert!(html.contains("<p>World</p>"), "应包含段落标记");
         // 检查样式片段
         assert!(html.contains("<style>"), "应包含样式标签");
+
+        let mobile = gen.render_profile(RenderPreset::Mobile);
+        let paged = gen.markdown_to_html_page(
+            "World",
+            Some((2, 3)),
+            None,
+            &InlineImages::default(),
+            &mobile,
+        );
+        assert!(paged.contains(">2/3</span>"), "应包含页码页脚");
+        // 手机布局使用较窄的画布
+        assert!(paged.contains(&format!("width: {}px;", mobile.width)));
+        assert!(!paged.contains("width: 1024px;"));
     }
 }
@@ -0,0 +1,218 @@
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use std::ops::Range;
+
+/// 单个顶层块的类型，用于估算渲染高度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlockKind {
+    Heading(u32),
+    CodeBlock,
+    Table,
+    Other,
+}
+
+/// 渲染宽度与字号等分页所需的参数
+#[derive(Debug, Clone, Copy)]
+pub struct PageLayout {
+    /// 画布宽度（像素）
+    pub width: u32,
+    /// 正文字号（像素）
+    pub font_size: u32,
+    /// 正文行高（字号的倍数）
+    pub line_height: f32,
+    /// 内边距（像素）
+    pub padding: u32,
+    /// 单页最大高度（像素）
+    pub max_page_height: u32,
+}
+
+impl PageLayout {
+    /// 去掉内边距后的内容宽度
+    pub fn content_width(&self) -> u32 {
+        self.width.saturating_sub(self.padding * 2).max(1)
+    }
+}
+
+// 内联图片按最大宽度估算的高度
+const IMAGE_HEIGHT_ESTIMATE: u32 = 400;
+
+/// 将Markdown按顶层块切分为多页
+///
+/// 只会在顶层块之间分页，代码块、表格等块永远不会被拆开；
+/// 单个块超过最大高度时独占一页。返回的每页内容首尾相接即为原文。
+pub fn split_markdown_pages(markdown: &str, layout: &PageLayout) -> Vec<String> {
+    let blocks = top_level_blocks(markdown);
+    if blocks.is_empty() {
+        return vec![markdown.to_string()];
+    }
+
+    // 记录每页第一个块的起始位置
+    let mut page_starts = vec![0usize];
+    let mut current_height = 0u32;
+    for (kind, range) in &blocks {
+        let height = estimate_block_height(*kind, &markdown[range.clone()], layout);
+        if current_height > 0 && current_height + height > layout.max_page_height {
+            page_starts.push(range.start);
+            current_height = 0;
+        }
+        current_height += height;
+    }
+
+    page_starts
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| {
+            let end = page_starts.get(i + 1).copied().unwrap_or(markdown.len());
+            markdown[start..end].to_string()
+        })
+        .collect()
+}
+
+/// 使用与渲染相同的解析选项提取顶层块及其源码范围
+fn top_level_blocks(markdown: &str) -> Vec<(BlockKind, Range<usize>)> {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_TASKLISTS);
+
+    let mut blocks = Vec::new();
+    let mut depth = 0usize;
+    for (event, range) in Parser::new_ext(markdown, options).into_offset_iter() {
+        match event {
+            Event::Start(tag) => {
+                if depth == 0 {
+                    let kind = match tag {
+                        Tag::Heading(level, _, _) => BlockKind::Heading(level as u32),
+                        Tag::CodeBlock(_) => BlockKind::CodeBlock,
+                        Tag::Table(_) => BlockKind::Table,
+                        _ => BlockKind::Other,
+                    };
+                    blocks.push((kind, range));
+                }
+                depth += 1;
+            }
+            Event::End(_) => {
+                depth = depth.saturating_sub(1);
+            }
+            // 顶层的分隔线和HTML块没有Start/End包裹
+            _ if depth == 0 => blocks.push((BlockKind::Other, range)),
+            _ => {}
+        }
+    }
+    blocks
+}
+
+/// 粗略估算一个块渲染后的高度（与 markdown_to_html 中的样式保持一致）
+fn estimate_block_height(kind: BlockKind, source: &str, layout: &PageLayout) -> u32 {
+    let content_width = layout.content_width() as f32;
+    let font_size = layout.font_size as f32;
+    let line_height = layout.line_height;
+
+    match kind {
+        BlockKind::Heading(level) => {
+            let size = match level {
+                1 => 32.0,
+                2 => 28.0,
+                _ => 24.0,
+            };
+            let lines = wrapped_lines(source.trim_start_matches('#').trim(), size, content_width);
+            (lines as f32 * size * 1.4) as u32 + 60
+        }
+        BlockKind::CodeBlock => {
+            let code_size = font_size - 2.0;
+            // 去掉首尾的围栏行
+            let body_lines = source
+                .lines()
+                .filter(|l| !l.trim_start().starts_with("```"));
+            let lines: u32 = body_lines
+                .map(|l| wrapped_lines(l, code_size, content_width - 32.0))
+                .sum();
+            (lines.max(1) as f32 * code_size * 1.8) as u32 + 72
+        }
+        BlockKind::Table => {
+            // 每行一个表格行，跳过分隔行
+            let rows = source
+                .lines()
+                .filter(|l| !l.trim().is_empty())
+                .filter(|l| !l.chars().all(|c| matches!(c, '|' | '-' | ':' | ' ')))
+                .count() as u32;
+            (rows as f32 * (font_size * line_height + 24.0)) as u32 + 50
+        }
+        BlockKind::Other => {
+            let lines: u32 = source
+                .lines()
+                .map(|l| wrapped_lines(l, font_size, content_width))
+                .sum();
+            let images = source.matches("![").count() as u32;
+            (lines.max(1) as f32 * font_size * line_height) as u32
+                + 36
+                + images * IMAGE_HEIGHT_ESTIMATE
+        }
+    }
+}
+
+/// 估算一行文本在给定宽度下折行后的行数
+fn wrapped_lines(line: &str, font_size: f32, width: f32) -> u32 {
+    let text_width: f32 = line
+        .chars()
+        .map(|c| {
+            if c.is_ascii() {
+                font_size * 0.55
+            } else {
+                font_size
+            }
+        })
+        .sum();
+    ((text_width / width).ceil() as u32).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(max_page_height: u32) -> PageLayout {
+        PageLayout {
+            width: 1024,
+            font_size: 24,
+            line_height: 1.8,
+            padding: 30,
+            max_page_height,
+        }
+    }
+
+    #[test]
+    fn short_markdown_single_page() {
+        let pages = split_markdown_pages("# 标题\n\n一段文字", &layout(4000));
+        assert_eq!(pages.len(), 1);
+    }
+
+    #[test]
+    fn pages_concatenate_to_original() {
+        let markdown = (0..50)
+            .map(|i| format!("第{}段，这是一段用于测试分页的文字。", i))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let pages = split_markdown_pages(&markdown, &layout(800));
+        assert!(pages.len() > 1, "长文本应分为多页");
+        assert_eq!(pages.concat(), markdown);
+    }
+
+    #[test]
+    fn code_block_never_split() {
+        let code = (0..100)
+            .map(|i| format!("let x{} = {};", i, i))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let markdown = format!("开头\n\n```rust\n{}\n```\n\n结尾", code);
+        let pages = split_markdown_pages(&markdown, &layout(600));
+        let fence_page = pages
+            .iter()
+            .find(|p| p.contains("```rust"))
+            .expect("应有页面包含代码块");
+        assert_eq!(
+            fence_page.matches("```").count(),
+            2,
+            "代码块应完整位于同一页"
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use super::paginate::PageLayout;
+use crate::config::Config;
+
+// 手机布局的画布宽度（像素）
+const MOBILE_WIDTH: u32 = 600;
+// 手机布局相对桌面布局的字号倍数
+const MOBILE_FONT_SCALE: f32 = 1.25;
+
+/// 图片布局预设
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderPreset {
+    /// 宽画布，使用配置中的宽度、字号与内边距
+    Desktop,
+    /// 窄画布、大字号，手机上无需放大即可阅读
+    Mobile,
+}
+
+impl FromStr for RenderPreset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "desktop" | "桌面" => Ok(RenderPreset::Desktop),
+            "mobile" | "手机" => Ok(RenderPreset::Mobile),
+            other => Err(anyhow!("不支持的图片布局: {}", other)),
+        }
+    }
+}
+
+/// 渲染参数：画布宽度、字号、行高与内边距
+///
+/// 同时用于生成HTML样式、wkhtmltoimage 的 `--width` 参数与分页时的高度估算。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderProfile {
+    /// 画布宽度（像素）
+    pub width: u32,
+    /// 正文字号（像素）
+    pub font_size: u32,
+    /// 正文行高（字号的倍数）
+    pub line_height: f32,
+    /// 内边距（像素）
+    pub padding: u32,
+}
+
+impl RenderProfile {
+    /// 根据预设与配置生成渲染参数
+    pub fn from_preset(preset: RenderPreset, config: &Config) -> Self {
+        let desktop = RenderProfile {
+            width: config.render_width,
+            font_size: config.font_size,
+            line_height: config.line_height,
+            padding: config.padding,
+        };
+        match preset {
+            RenderPreset::Desktop => desktop,
+            RenderPreset::Mobile => RenderProfile {
+                width: MOBILE_WIDTH.min(desktop.width),
+                font_size: (desktop.font_size as f32 * MOBILE_FONT_SCALE).round() as u32,
+                padding: (desktop.padding * 2 / 3).max(12),
+                ..desktop
+            },
+        }
+    }
+
+    /// 分页所需的布局参数
+    pub fn page_layout(&self, max_page_height: u32) -> PageLayout {
+        PageLayout {
+            width: self.width,
+            font_size: self.font_size,
+            line_height: self.line_height,
+            padding: self.padding,
+            max_page_height,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mobile_preset_is_narrower_with_larger_font() {
+        let config = crate::image::tests::test_config();
+        let desktop = RenderProfile::from_preset(RenderPreset::Desktop, &config);
+        let mobile = RenderProfile::from_preset(RenderPreset::Mobile, &config);
+        assert_eq!(desktop.width, config.render_width);
+        assert!(mobile.width < desktop.width);
+        assert!(mobile.font_size > desktop.font_size);
+        assert_eq!(
+            "手机".parse::<RenderPreset>().unwrap(),
+            RenderPreset::Mobile
+        );
+    }
+}
@@ -0,0 +1,349 @@
+use anyhow::{anyhow, Context, Result};
+use base64::Engine;
+use futures::StreamExt;
+use hyper::client::connect::dns::Name;
+use image::io::{Limits, Reader as ImageReader};
+use image::{DynamicImage, ImageOutputFormat};
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::redirect::Policy;
+use reqwest::Url;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// 预先下载并内联的远程图片，键为Markdown中的原始地址，值为 data URI
+#[derive(Debug, Clone, Default)]
+pub struct InlineImages {
+    images: HashMap<String, String>,
+}
+
+impl InlineImages {
+    /// 获取已内联图片的 data URI
+    pub fn get(&self, url: &str) -> Option<&str> {
+        self.images.get(url).map(String::as_str)
+    }
+}
+
+impl FromIterator<(String, String)> for InlineImages {
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(iter: I) -> Self {
+        Self {
+            images: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// 远程图片下载限制
+#[derive(Debug, Clone, Copy)]
+pub struct RemoteImageLimits {
+    /// 每个回答最多下载的图片数
+    pub max_count: usize,
+    /// 单张图片的最大字节数
+    pub max_bytes: u64,
+    /// 单张图片的下载超时
+    pub timeout: Duration,
+    /// 超过该宽度时缩小
+    pub max_width: u32,
+}
+
+// 解码时允许的最大尺寸，防止解压炸弹
+const MAX_DECODE_DIMENSION: u32 = 10000;
+
+// 下载图片时最多跟随的重定向次数
+const MAX_REDIRECTS: usize = 3;
+
+/// 创建下载远程图片用的HTTP客户端
+///
+/// 图片地址来自模型回答，不可信：只允许连接公网地址，每次重定向都重新检查目标，
+/// 域名解析结果中含有内网地址时拒绝连接，且不使用系统代理（代理会绕过地址检查）。
+pub fn build_client(timeout: Duration) -> Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .redirect(Policy::custom(|attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                return attempt.error(anyhow!("重定向次数过多"));
+            }
+            match check_url(attempt.url()) {
+                Ok(()) => attempt.follow(),
+                Err(e) => attempt.error(e),
+            }
+        }))
+        .dns_resolver(Arc::new(PublicOnlyResolver))
+        .no_proxy()
+        .build()
+        .context("创建图片下载客户端失败")
+}
+
+/// 只返回公网地址的域名解析器
+struct PublicOnlyResolver;
+
+impl Resolve for PublicOnlyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs = resolve_public(name.as_str()).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+// 解析域名，任何一个地址不是公网地址都拒绝，避免通过多条记录混入内网地址
+async fn resolve_public(host: &str) -> Result<Vec<SocketAddr>> {
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, 0))
+        .await
+        .with_context(|| format!("解析域名失败: {}", host))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(anyhow!("域名没有解析结果: {}", host));
+    }
+    if let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+        return Err(anyhow!("拒绝访问非公网地址: {} -> {}", host, addr.ip()));
+    }
+    Ok(addrs)
+}
+
+// 检查地址的协议，主机直接写成IP时检查是否为公网地址（域名由解析器检查）
+fn check_url(url: &Url) -> Result<()> {
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(anyhow!("不支持的协议: {}", url.scheme()));
+    }
+    let host = url.host_str().ok_or_else(|| anyhow!("地址缺少主机名"))?;
+    match host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+    {
+        Ok(ip) if !is_public_ip(ip) => Err(anyhow!("拒绝访问非公网地址: {}", ip)),
+        _ => Ok(()),
+    }
+}
+
+/// 是否为可以访问的公网地址
+///
+/// 排除回环、私有（RFC 1918）、链路本地、唯一本地（fc00::/7）、未指定、
+/// 运营商NAT、组播与文档示例地址；IPv4映射的IPv6地址按其IPv4地址判断。
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(v4));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// 收集Markdown中引用的 http(s) 图片地址（去重，保持出现顺序）
+pub fn collect_image_urls(markdown: &str) -> Vec<String> {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+
+    let mut urls: Vec<String> = Vec::new();
+    for event in Parser::new_ext(markdown, options) {
+        if let Event::Start(Tag::Image(_, url, _)) = event {
+            let lower = url.trim().to_ascii_lowercase();
+            let remote = lower.starts_with("http://") || lower.starts_with("https://");
+            if remote && !urls.iter().any(|u| u == url.as_ref()) {
+                urls.push(url.to_string());
+            }
+        }
+    }
+    urls
+}
+
+/// 并发下载Markdown中引用的图片，校验并缩小后转换为 data URI
+///
+/// 超出数量限制、下载失败或无法解码的图片不会出现在结果中，渲染时显示为占位框。
+pub async fn fetch_images(
+    client: &reqwest::Client,
+    markdown: &str,
+    limits: &RemoteImageLimits,
+) -> InlineImages {
+    let urls = collect_image_urls(markdown);
+    if urls.len() > limits.max_count {
+        warn!(
+            "回答引用了 {} 张图片，仅下载前 {} 张",
+            urls.len(),
+            limits.max_count
+        );
+    }
+
+    let tasks = urls
+        .into_iter()
+        .take(limits.max_count)
+        .map(|url| async move {
+            let result = tokio::time::timeout(limits.timeout, fetch_one(client, &url, limits))
+                .await
+                .unwrap_or_else(|_| Err(anyhow!("下载超时")));
+            (url, result)
+        });
+
+    let mut images = HashMap::new();
+    for (url, result) in futures::future::join_all(tasks).await {
+        match result {
+            Ok(data_uri) => {
+                images.insert(url, data_uri);
+            }
+            Err(e) => warn!("内联远程图片失败: {}: {}", url, e),
+        }
+    }
+    InlineImages { images }
+}
+
+// 下载单张图片并转换为 data URI
+async fn fetch_one(
+    client: &reqwest::Client,
+    url: &str,
+    limits: &RemoteImageLimits,
+) -> Result<String> {
+    let parsed = Url::parse(url).context("图片地址无效")?;
+    check_url(&parsed)?;
+    let response = client.get(parsed).send().await.context("请求失败")?;
+    if !response.status().is_success() {
+        return Err(anyhow!("状态码 {}", response.status()));
+    }
+    if let Some(length) = response.content_length() {
+        if length > limits.max_bytes {
+            return Err(anyhow!("图片过大: {} 字节", length));
+        }
+    }
+
+    // 边下载边检查大小，避免服务器不返回 Content-Length 时无限读取
+    let mut body = Vec::new();
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.context("读取图片数据失败")?;
+        body.extend_from_slice(&chunk);
+        if body.len() as u64 > limits.max_bytes {
+            return Err(anyhow!("图片超过 {} 字节限制", limits.max_bytes));
+        }
+    }
+
+    let img = decode_image(&body)?;
+    let data_uri = to_data_uri(img, limits.max_width)?;
+    debug!("已内联远程图片: {} ({} 字节)", url, data_uri.len());
+    Ok(data_uri)
+}
+
+/// 在尺寸限制下解码图片
+pub fn decode_image(bytes: &[u8]) -> Result<DynamicImage> {
+    let mut reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .context("无法识别图片格式")?;
+    let mut decode_limits = Limits::default();
+    decode_limits.max_image_width = Some(MAX_DECODE_DIMENSION);
+    decode_limits.max_image_height = Some(MAX_DECODE_DIMENSION);
+    reader.limits(decode_limits);
+    reader.decode().context("解码图片失败")
+}
+
+// 按需缩小并编码为 data URI：带透明通道用PNG，否则用JPEG
+fn to_data_uri(img: DynamicImage, max_width: u32) -> Result<String> {
+    let img = if img.width() > max_width {
+        let height = (img.height() as u64 * max_width as u64 / img.width() as u64).max(1) as u32;
+        img.resize(max_width, height, image::imageops::FilterType::Triangle)
+    } else {
+        img
+    };
+
+    let mut buf = Vec::new();
+    let mime = if img.color().has_alpha() {
+        img.write_to(&mut Cursor::new(&mut buf), ImageOutputFormat::Png)?;
+        "image/png"
+    } else {
+        img.write_to(&mut Cursor::new(&mut buf), ImageOutputFormat::Jpeg(85))?;
+        "image/jpeg"
+    };
+    Ok(format!(
+        "data:{};base64,{}",
+        mime,
+        base64::engine::general_purpose::STANDARD.encode(buf)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collect_urls_dedupes_and_skips_local() {
+        let markdown = "![a](https://example.com/a.png)\n\n![b](file:///etc/passwd)\n\n![c](https://example.com/a.png) ![d](http://example.com/d.jpg)";
+        assert_eq!(
+            collect_image_urls(markdown),
+            vec![
+                "https://example.com/a.png".to_string(),
+                "http://example.com/d.jpg".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_private_and_local_addresses() {
+        for url in [
+            "http://127.0.0.1/a.png",
+            "http://10.1.2.3/a.png",
+            "http://172.16.0.1/a.png",
+            "http://192.168.1.1/a.png",
+            "http://169.254.169.254/latest/meta-data",
+            "http://0.0.0.0/a.png",
+            "http://100.64.0.1/a.png",
+            "http://[::1]/a.png",
+            "http://[::]/a.png",
+            "http://[fe80::1]/a.png",
+            "http://[fd00::1]/a.png",
+            "http://[::ffff:127.0.0.1]/a.png",
+            "ftp://example.com/a.png",
+        ] {
+            let url = Url::parse(url).unwrap();
+            assert!(check_url(&url).is_err(), "{} 应被拒绝", url);
+        }
+        for url in [
+            "https://example.com/a.png",
+            "http://93.184.216.34/a.png",
+            "http://[2606:4700::1111]/a.png",
+        ] {
+            assert!(check_url(&Url::parse(url).unwrap()).is_ok(), "{}", url);
+        }
+
+        // 域名解析到回环地址时同样拒绝
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        assert!(runtime.block_on(resolve_public("localhost")).is_err());
+    }
+
+    #[test]
+    fn downscales_wide_images() {
+        let img = DynamicImage::new_rgb8(2000, 1000);
+        let uri = to_data_uri(img, 500).expect("编码失败");
+        assert!(uri.starts_with("data:image/jpeg;base64,"));
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(uri.trim_start_matches("data:image/jpeg;base64,"))
+            .unwrap();
+        let decoded = decode_image(&bytes).unwrap();
+        assert_eq!(decoded.width(), 500);
+    }
+
+    #[test]
+    fn rejects_non_image_data() {
+        assert!(decode_image(b"<html>not an image</html>").is_err());
+    }
+}
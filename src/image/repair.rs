@@ -0,0 +1,421 @@
+/// 修复过程中发现的Markdown问题
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkdownDefect {
+    /// 代码块没有闭合，后续内容会全部显示为代码
+    UnclosedFence,
+    /// HTML注释（包括没有闭合的注释标记）
+    HtmlComment,
+    /// 表格前缺少空行，被当作普通段落
+    TableWithoutBlankLine,
+    /// 嵌套列表缩进不足，无法正确嵌套
+    MisindentedList,
+    /// 标题的 # 后缺少空格
+    HeadingWithoutSpace,
+}
+
+/// 修复结果
+#[derive(Debug, Clone)]
+pub struct RepairReport {
+    pub markdown: String,
+    pub defects: Vec<MarkdownDefect>,
+}
+
+/// 修复模型输出中常见的Markdown问题
+///
+/// 修复是幂等的：对修复结果再次修复不会产生变化。代码块内的内容保持不变。
+pub fn repair_markdown(markdown: &str) -> RepairReport {
+    let mut defects = Vec::new();
+    let (text, removed_comments) = strip_html_comments(markdown);
+    if removed_comments {
+        defects.push(MarkdownDefect::HtmlComment);
+    }
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut fence: Option<Fence> = None;
+    // 打开代码块时所在列表项的缩进偏移，以及修复后开始围栏的缩进
+    let mut fence_shift = 0usize;
+    let mut fence_indent = 0usize;
+    let mut lists: Vec<ListItem> = Vec::new();
+
+    for raw_line in text.lines() {
+        let line = expand_leading_tabs(raw_line);
+
+        if let Some(open) = &fence {
+            if open.is_closed_by(&line) {
+                fence = None;
+            }
+            lines.push(shift_line(&line, fence_shift));
+            continue;
+        }
+
+        if line.trim().is_empty() {
+            lines.push(String::new());
+            continue;
+        }
+
+        let indent = leading_spaces(&line);
+        let current_shift = if indent > 0 {
+            lists.last().map(|item| item.shift).unwrap_or(0)
+        } else {
+            0
+        };
+
+        if let Some(open) = Fence::parse(&line) {
+            fence = Some(open);
+            fence_shift = current_shift;
+            fence_indent = indent + current_shift;
+            lines.push(shift_line(&line, current_shift));
+            continue;
+        }
+
+        if let Some(marker_width) = list_marker_width(&line[indent..]) {
+            // 缩进不足两格的视为同级或上级列表项
+            while lists
+                .last()
+                .map(|top| indent < top.raw_indent + 2)
+                .unwrap_or(false)
+            {
+                lists.pop();
+            }
+            let (new_indent, shift) = match lists.last() {
+                Some(parent) => {
+                    let adjusted = indent + parent.shift;
+                    if adjusted < parent.content_offset {
+                        push_defect(&mut defects, MarkdownDefect::MisindentedList);
+                        (parent.content_offset, parent.content_offset - indent)
+                    } else {
+                        (adjusted, parent.shift)
+                    }
+                }
+                None => (indent, 0),
+            };
+            lists.push(ListItem {
+                raw_indent: indent,
+                content_offset: new_indent + marker_width,
+                shift,
+            });
+            lines.push(format!("{}{}", " ".repeat(new_indent), &line[indent..]));
+            continue;
+        }
+
+        if indent == 0 {
+            lists.clear();
+        }
+
+        // 表格分隔行前是表头，表头前却紧跟着段落文字时补上空行
+        if indent == 0 && is_table_delimiter(&line) && lines.len() >= 2 {
+            let header = &lines[lines.len() - 1];
+            let before = &lines[lines.len() - 2];
+            if header.contains('|')
+                && !before.trim().is_empty()
+                && !before.contains('|')
+                && leading_spaces(header) == 0
+            {
+                let header = lines.pop().unwrap_or_default();
+                lines.push(String::new());
+                lines.push(header);
+                push_defect(&mut defects, MarkdownDefect::TableWithoutBlankLine);
+            }
+        }
+
+        if let Some(fixed) = fix_heading_space(&line) {
+            push_defect(&mut defects, MarkdownDefect::HeadingWithoutSpace);
+            lines.push(fixed);
+            continue;
+        }
+
+        lines.push(shift_line(&line, current_shift));
+    }
+
+    if let Some(open) = fence {
+        push_defect(&mut defects, MarkdownDefect::UnclosedFence);
+        lines.push(format!(
+            "{}{}",
+            " ".repeat(fence_indent),
+            open.marker.to_string().repeat(open.count)
+        ));
+    }
+
+    let mut markdown = lines.join("\n");
+    if text.ends_with('\n') {
+        markdown.push('\n');
+    }
+    RepairReport { markdown, defects }
+}
+
+fn push_defect(defects: &mut Vec<MarkdownDefect>, defect: MarkdownDefect) {
+    if !defects.contains(&defect) {
+        defects.push(defect);
+    }
+}
+
+/// 代码块围栏
+#[derive(Debug, Clone, Copy)]
+struct Fence {
+    marker: char,
+    count: usize,
+}
+
+impl Fence {
+    // 解析开始围栏，最多缩进三格（列表项内按相对缩进）
+    fn parse(line: &str) -> Option<Fence> {
+        let trimmed = line.trim_start();
+        let marker = trimmed.chars().next()?;
+        if marker != '`' && marker != '~' {
+            return None;
+        }
+        let count = trimmed.chars().take_while(|&c| c == marker).count();
+        // 反引号围栏的信息字符串中不能包含反引号
+        if count < 3 || (marker == '`' && trimmed[count..].contains('`')) {
+            return None;
+        }
+        Some(Fence { marker, count })
+    }
+
+    fn is_closed_by(&self, line: &str) -> bool {
+        let trimmed = line.trim();
+        trimmed.chars().take_while(|&c| c == self.marker).count() >= self.count
+            && trimmed.chars().all(|c| c == self.marker)
+    }
+}
+
+/// 列表项的缩进信息
+#[derive(Debug, Clone, Copy)]
+struct ListItem {
+    // 原文中的缩进
+    raw_indent: usize,
+    // 修复后内容开始的列
+    content_offset: usize,
+    // 修复时增加的缩进
+    shift: usize,
+}
+
+// 返回列表标记及其后空格的宽度，如 "- " 为 2，"10. " 为 4
+fn list_marker_width(text: &str) -> Option<usize> {
+    let marker_len = match text.chars().next()? {
+        '-' | '*' | '+' => 1,
+        c if c.is_ascii_digit() => {
+            let digits = text.chars().take_while(|c| c.is_ascii_digit()).count();
+            if digits > 9 || !matches!(text[digits..].chars().next(), Some('.') | Some(')')) {
+                return None;
+            }
+            digits + 1
+        }
+        _ => return None,
+    };
+    let rest = &text[marker_len..];
+    let spaces = rest.chars().take_while(|&c| c == ' ').count();
+    if spaces == 0 || rest.trim().is_empty() {
+        return None;
+    }
+    // 分隔线（如 "- - -"、"* * *"）不是列表
+    if marker_len == 1
+        && text
+            .chars()
+            .all(|c| c == text.as_bytes()[0] as char || c == ' ')
+    {
+        return None;
+    }
+    Some(marker_len + if spaces > 4 { 1 } else { spaces })
+}
+
+fn is_table_delimiter(line: &str) -> bool {
+    let trimmed = line.trim().trim_matches('|');
+    let cells: Vec<&str> = trimmed.split('|').map(str::trim).collect();
+    line.contains('-')
+        && !cells.is_empty()
+        && cells.iter().all(|cell| {
+            let inner = cell.trim_start_matches(':').trim_end_matches(':');
+            !inner.is_empty() && inner.chars().all(|c| c == '-')
+        })
+        && (line.contains('|') || cells.len() > 1)
+}
+
+// "##标题" 缺少空格时补上；只处理后面紧跟非ASCII字符的情况，避免误伤 #include 等内容
+fn fix_heading_space(line: &str) -> Option<String> {
+    if leading_spaces(line) > 3 {
+        return None;
+    }
+    let trimmed = line.trim_start();
+    let hashes = trimmed.chars().take_while(|&c| c == '#').count();
+    if hashes == 0 || hashes > 6 {
+        return None;
+    }
+    let next = trimmed[hashes..].chars().next()?;
+    if next.is_ascii() || next.is_whitespace() {
+        return None;
+    }
+    Some(format!("{} {}", &trimmed[..hashes], &trimmed[hashes..]))
+}
+
+fn leading_spaces(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+// 行首的制表符按四个空格计算
+fn expand_leading_tabs(line: &str) -> String {
+    let prefix_len = line.len() - line.trim_start_matches([' ', '\t']).len();
+    let mut expanded = String::with_capacity(line.len());
+    for c in line[..prefix_len].chars() {
+        if c == '\t' {
+            let pad = 4 - expanded.len() % 4;
+            expanded.push_str(&" ".repeat(pad));
+        } else {
+            expanded.push(c);
+        }
+    }
+    expanded.push_str(&line[prefix_len..]);
+    expanded
+}
+
+fn shift_line(line: &str, shift: usize) -> String {
+    if shift == 0 || line.trim().is_empty() {
+        line.to_string()
+    } else {
+        format!("{}{}", " ".repeat(shift), line)
+    }
+}
+
+// 删除代码以外的HTML注释；没有闭合的注释只删除开始标记，避免吞掉后面的内容
+fn strip_html_comments(markdown: &str) -> (String, bool) {
+    let mut output = String::with_capacity(markdown.len());
+    let mut fence: Option<Fence> = None;
+    let mut removed = false;
+    // 注释结束后的位置，之前的内容都在注释中
+    let mut skip_until = 0usize;
+    let mut offset = 0usize;
+
+    for line in markdown.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        if skip_until >= offset {
+            continue;
+        }
+
+        let mut cursor = skip_until.max(start);
+        if cursor == start {
+            let content = line.trim_end_matches(['\n', '\r']);
+            if let Some(open) = &fence {
+                if open.is_closed_by(content) {
+                    fence = None;
+                }
+                output.push_str(line);
+                continue;
+            }
+            if let Some(open) = Fence::parse(content) {
+                fence = Some(open);
+                output.push_str(line);
+                continue;
+            }
+        }
+
+        loop {
+            let rest = &markdown[cursor..offset];
+            match find_comment_start(rest) {
+                Some(i) => {
+                    output.push_str(&rest[..i]);
+                    removed = true;
+                    let after = cursor + i + 4;
+                    match markdown[after..].find("-->") {
+                        Some(j) => {
+                            skip_until = after + j + 3;
+                            if skip_until >= offset {
+                                break;
+                            }
+                            cursor = skip_until;
+                        }
+                        None => cursor = after,
+                    }
+                }
+                None => {
+                    output.push_str(rest);
+                    break;
+                }
+            }
+        }
+    }
+    (output, removed)
+}
+
+// 查找行内代码以外的注释开始标记
+fn find_comment_start(text: &str) -> Option<usize> {
+    let mut in_code = false;
+    for (i, c) in text.char_indices() {
+        if c == '`' {
+            in_code = !in_code;
+        } else if !in_code && text[i..].starts_with("<!--") {
+            return Some(i);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn closes_unclosed_fence() {
+        let report = repair_markdown("说明\n\n```python\nprint(1)\n");
+        assert_eq!(report.markdown, "说明\n\n```python\nprint(1)\n```\n");
+        assert_eq!(report.defects, vec![MarkdownDefect::UnclosedFence]);
+    }
+
+    #[test]
+    fn removes_comments_but_keeps_code() {
+        let markdown = "前<!-- 隐藏 -->后\n\n<!-- 多行\n注释 -->\n正文 `<!-- 行内代码 -->`\n\n```html\n<!-- 代码中的注释 -->\n```\n\n<!-- 未闭合\n结尾";
+        let report = repair_markdown(markdown);
+        assert!(report.markdown.contains("前后"));
+        assert!(!report.markdown.contains("多行"));
+        assert!(report.markdown.contains("`<!-- 行内代码 -->`"));
+        assert!(report.markdown.contains("<!-- 代码中的注释 -->"));
+        assert!(report.markdown.contains(" 未闭合\n结尾"));
+    }
+
+    #[test]
+    fn reindents_nested_list_under_ordered_item() {
+        let report = repair_markdown("1. 第一步\n  - 子项\n    - 更深\n2. 第二步\n");
+        assert_eq!(
+            report.markdown,
+            "1. 第一步\n   - 子项\n     - 更深\n2. 第二步\n"
+        );
+    }
+
+    /// 语料库：repair_corpus 目录下的 `名称.md` 为有问题的回答，
+    /// `名称.expected.md` 为期望的修复结果
+    ///
+    /// 现有用例是按线上回答中见过的问题手写的最小复现：会话数据包含用户内容，不随仓库提交。
+    /// 可用 collect_markdown_corpus 从会话中收集真实样本，去除可识别信息并人工检查后加入。
+    #[test]
+    fn repair_corpus() {
+        let dir = Path::new(file!()).with_file_name("repair_corpus");
+        let mut cases = 0;
+        for entry in fs::read_dir(&dir).expect("读取语料库目录失败") {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            if !name.ends_with(".md") || name.ends_with(".expected.md") {
+                continue;
+            }
+            let input = fs::read_to_string(&path).unwrap();
+            let expected =
+                fs::read_to_string(path.with_file_name(name.replace(".md", ".expected.md")))
+                    .unwrap_or_else(|_| panic!("缺少期望结果: {}", name));
+            let repaired = repair_markdown(&input);
+            assert_eq!(repaired.markdown, expected, "语料 {} 修复结果不符", name);
+            assert!(!repaired.defects.is_empty(), "语料 {} 应检测到问题", name);
+            // 修复应当是幂等的
+            let again = repair_markdown(&repaired.markdown);
+            assert_eq!(again.markdown, expected, "语料 {} 修复不幂等", name);
+            assert!(
+                again.defects.is_empty(),
+                "语料 {}: {:?}",
+                name,
+                again.defects
+            );
+            cases += 1;
+        }
+        assert!(cases > 0, "语料库为空");
+    }
+}
@@ -0,0 +1,220 @@
+use pulldown_cmark::{CowStr, Event, Tag};
+
+use super::remote::InlineImages;
+
+/// 允许原样保留的HTML标签（不允许携带任何属性）
+const ALLOWED_TAGS: &[&str] = &[
+    "b", "i", "em", "strong", "u", "s", "del", "ins", "sub", "sup", "br", "kbd", "mark", "small",
+    "code", "span", "details", "summary", "hr", "p",
+];
+
+/// 过滤模型输出中的不安全内容
+///
+/// - 原始HTML只保留白名单内且不带属性的标签，其余转义为文本，注释直接删除
+/// - 链接只允许 http(s) 与 mailto，其余协议保留文字、去掉链接
+/// - 图片只使用预先下载的 data URI，渲染器不会访问网络；
+///   未下载成功的图片显示为带替代文本的占位框
+pub fn sanitize_events<'a, 'b>(
+    events: impl Iterator<Item = Event<'a>> + 'b,
+    images: &'b InlineImages,
+) -> impl Iterator<Item = Event<'a>> + 'b
+where
+    'a: 'b,
+{
+    // 记录被移除的链接/图片，以便丢弃对应的结束标签
+    let mut dropped: Vec<bool> = Vec::new();
+    events.filter_map(move |event| match event {
+        Event::Html(html) => {
+            let cleaned = sanitize_html(&html);
+            if cleaned.is_empty() {
+                None
+            } else {
+                Some(Event::Html(CowStr::from(cleaned)))
+            }
+        }
+        Event::Start(Tag::Link(kind, url, title)) => {
+            if is_allowed_link(&url) {
+                dropped.push(false);
+                Some(Event::Start(Tag::Link(kind, url, title)))
+            } else {
+                dropped.push(true);
+                None
+            }
+        }
+        Event::End(Tag::Link(kind, url, title)) => {
+            if dropped.pop().unwrap_or(false) {
+                None
+            } else {
+                Some(Event::End(Tag::Link(kind, url, title)))
+            }
+        }
+        Event::Start(Tag::Image(kind, url, title)) => {
+            if let Some(data_uri) = images.get(&url) {
+                dropped.push(false);
+                Some(Event::Start(Tag::Image(
+                    kind,
+                    CowStr::from(data_uri.to_string()),
+                    title,
+                )))
+            } else {
+                dropped.push(true);
+                Some(Event::Html(CowStr::from(
+                    "<span class=\"image-placeholder\">🖼 ",
+                )))
+            }
+        }
+        Event::End(Tag::Image(kind, url, title)) => {
+            if dropped.pop().unwrap_or(false) {
+                Some(Event::Html(CowStr::from("</span>")))
+            } else {
+                Some(Event::End(Tag::Image(kind, url, title)))
+            }
+        }
+        other => Some(other),
+    })
+}
+
+/// 清理一段原始HTML：保留白名单标签，删除注释，其余内容转义
+pub fn sanitize_html(html: &str) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        output.push_str(&html_escape::encode_text(&rest[..start]));
+        rest = &rest[start..];
+
+        // 注释整体删除，未闭合的注释删除到末尾
+        if rest.starts_with("<!--") {
+            rest = match rest.find("-->") {
+                Some(end) => &rest[end + 3..],
+                None => "",
+            };
+            continue;
+        }
+
+        match rest.find('>') {
+            Some(end) => {
+                let tag = &rest[..=end];
+                if is_allowed_tag(tag) {
+                    output.push_str(tag);
+                } else {
+                    output.push_str(&html_escape::encode_text(tag));
+                }
+                rest = &rest[end + 1..];
+            }
+            None => {
+                output.push_str(&html_escape::encode_text(rest));
+                rest = "";
+            }
+        }
+    }
+    output.push_str(&html_escape::encode_text(rest));
+    output
+}
+
+// 判断是否为白名单内且不带属性的标签，如 <b>、</b>、<br/>
+fn is_allowed_tag(tag: &str) -> bool {
+    let inner = tag
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .trim_start_matches('/')
+        .trim_end_matches('/')
+        .trim();
+    !inner.is_empty()
+        && inner.chars().all(|c| c.is_ascii_alphanumeric())
+        && ALLOWED_TAGS.contains(&inner.to_ascii_lowercase().as_str())
+}
+
+fn is_remote_url(url: &str) -> bool {
+    let lower = url.trim().to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+fn is_allowed_link(url: &str) -> bool {
+    let lower = url.trim().to_ascii_lowercase();
+    is_remote_url(&lower) || lower.starts_with("mailto:") || lower.starts_with('#')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pulldown_cmark::{html, Options, Parser};
+
+    fn render_with(markdown: &str, images: &InlineImages) -> String {
+        let mut options = Options::empty();
+        options.insert(Options::ENABLE_TABLES);
+        let parser = Parser::new_ext(markdown, options);
+        let mut out = String::new();
+        html::push_html(&mut out, sanitize_events(parser, images));
+        out
+    }
+
+    fn render(markdown: &str) -> String {
+        render_with(markdown, &InlineImages::default())
+    }
+
+    #[test]
+    fn raw_img_with_local_file_is_escaped() {
+        let out = render("看这里 <img src=\"file:///etc/passwd\"> 结束");
+        assert!(!out.contains("<img"), "不应输出img标签: {}", out);
+        assert!(out.contains("&lt;img"));
+    }
+
+    #[test]
+    fn iframe_and_script_blocks_are_escaped() {
+        let out =
+            render("<iframe src=\"file:///etc/shadow\"></iframe>\n\n<script>alert(1)</script>");
+        assert!(!out.contains("<iframe"));
+        assert!(!out.contains("<script"));
+    }
+
+    #[test]
+    fn style_and_link_tags_are_escaped() {
+        let out = render(
+            "<link rel=\"stylesheet\" href=\"file:///etc/passwd\">\n<style>body{background:url(file:///etc/passwd)}</style>",
+        );
+        assert!(!out.contains("<link"));
+        assert!(!out.contains("<style"));
+    }
+
+    #[test]
+    fn markdown_image_with_file_scheme_becomes_placeholder() {
+        let out = render("![密码](file:///etc/passwd)");
+        assert!(!out.contains("file:///"), "不应包含本地路径: {}", out);
+        assert!(out.contains("image-placeholder"));
+        assert!(out.contains("密码"));
+    }
+
+    #[test]
+    fn remote_image_uses_inlined_data_only() {
+        let md = "![图](https://example.com/a.png)";
+        let images = InlineImages::from_iter([(
+            "https://example.com/a.png".to_string(),
+            "data:image/png;base64,AAAA".to_string(),
+        )]);
+        assert!(render_with(md, &images).contains("<img src=\"data:image/png;base64,AAAA\""));
+        let out = render(md);
+        assert!(!out.contains("<img"), "未下载的图片应显示占位框: {}", out);
+        assert!(out.contains("image-placeholder"));
+    }
+
+    #[test]
+    fn javascript_link_is_unwrapped() {
+        let out = render("[点我](javascript:alert(1))");
+        assert!(!out.contains("javascript:"));
+        assert!(out.contains("点我"));
+    }
+
+    #[test]
+    fn allowed_inline_tags_are_kept() {
+        let out = render("按 <kbd>Ctrl</kbd> + <b>C</b><!-- 注释 -->");
+        assert!(out.contains("<kbd>Ctrl</kbd>"));
+        assert!(out.contains("<b>C</b>"));
+        assert!(!out.contains("注释"));
+    }
+
+    #[test]
+    fn allowed_tag_with_attributes_is_escaped() {
+        let out = sanitize_html("<span style=\"background:url(file:///etc/passwd)\">x</span>");
+        assert!(!out.contains("<span style"));
+    }
+}
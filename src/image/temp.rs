@@ -0,0 +1,206 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::util::format_bytes;
+
+// 扫描其他目录时的最大深度（sessions/{session_id}/文件）
+const MAX_SCAN_DEPTH: usize = 2;
+
+/// 渲染中间文件的路径
+///
+/// 与目标文件位于同一目录的隐藏文件，扩展名与目标相同，渲染完成后由 [`commit_partial`] 重命名到目标路径，
+/// 避免会话目录中出现写了一半的图片。
+pub(crate) fn partial_path(output_path: &Path) -> PathBuf {
+    let stem = output_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = output_path
+        .extension()
+        .map(|e| e.to_string_lossy().to_string())
+        .unwrap_or_else(|| "tmp".to_string());
+    output_path.with_file_name(format!(".{}.{}.tmp.{}", stem, Uuid::new_v4(), extension))
+}
+
+/// 将渲染完成的中间文件移动到目标路径，扩展名沿用中间文件（格式转换后可能改变）
+pub(crate) fn commit_partial(partial: &Path, output_path: &Path) -> Result<PathBuf> {
+    let target = match partial.extension() {
+        Some(extension) => output_path.with_extension(extension),
+        None => output_path.to_path_buf(),
+    };
+    if let Err(e) = fs::rename(partial, &target) {
+        let _ = fs::remove_file(partial);
+        return Err(e).context("重命名渲染结果失败");
+    }
+    Ok(target)
+}
+
+// 写入中途留下的隐藏临时文件，如 `.response.<uuid>.tmp.png`、`.session.json.<uuid>.tmp`
+fn is_partial_file(name: &str) -> bool {
+    name.starts_with('.') && (name.ends_with(".tmp") || name.contains(".tmp."))
+}
+
+/// 一次临时文件清理的结果
+#[derive(Debug, Clone, Default)]
+pub struct SweepReport {
+    pub scanned: usize,
+    pub removed: usize,
+    pub reclaimed_bytes: u64,
+    pub failed: usize,
+}
+
+impl SweepReport {
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "检查 {} 个临时文件，删除 {} 个，释放 {}",
+            self.scanned,
+            self.removed,
+            format_bytes(self.reclaimed_bytes)
+        );
+        if self.failed > 0 {
+            summary.push_str(&format!("，失败 {} 个", self.failed));
+        }
+        summary
+    }
+}
+
+/// 删除修改时间早于 `max_age` 的临时文件
+///
+/// `temp_dir` 中的文件都视为临时文件；`other_dirs` 中只删除写入中途留下的隐藏临时文件，
+/// 用于清理渲染或进程崩溃后残留在会话目录与缓存目录中的文件。不存在的目录会被跳过。
+pub fn sweep_temp_files(
+    temp_dir: &Path,
+    other_dirs: &[PathBuf],
+    max_age: Duration,
+    now: SystemTime,
+) -> Result<SweepReport> {
+    let mut report = SweepReport::default();
+    sweep_dir(temp_dir, false, 0, max_age, now, &mut report)?;
+    for dir in other_dirs {
+        sweep_dir(dir, true, 0, max_age, now, &mut report)?;
+    }
+    Ok(report)
+}
+
+fn sweep_dir(
+    dir: &Path,
+    partial_only: bool,
+    depth: usize,
+    max_age: Duration,
+    now: SystemTime,
+    report: &mut SweepReport,
+) -> Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).with_context(|| format!("读取目录失败: {}", dir.display())),
+    };
+
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_dir() {
+            if depth + 1 < MAX_SCAN_DEPTH || !partial_only {
+                sweep_dir(&path, partial_only, depth + 1, max_age, now, report)?;
+            }
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        if partial_only && !is_partial_file(&name) {
+            continue;
+        }
+
+        report.scanned += 1;
+        let age = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .unwrap_or_default();
+        if age < max_age {
+            continue;
+        }
+        match fs::remove_file(&path) {
+            Ok(_) => {
+                debug!("已删除临时文件: {}", path.display());
+                report.removed += 1;
+                report.reclaimed_bytes += metadata.len();
+            }
+            Err(e) => {
+                warn!("删除临时文件失败 {}: {}", path.display(), e);
+                report.failed += 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweeps_old_temp_files_and_partial_renders() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        let temp_dir = root.join("temp");
+        let session_dir = root.join("sessions").join("abc");
+        fs::create_dir_all(&temp_dir).unwrap();
+        fs::create_dir_all(&session_dir).unwrap();
+
+        fs::write(temp_dir.join("temp_1.html"), "<html>").unwrap();
+        fs::write(temp_dir.join("response_1.png"), b"png").unwrap();
+        let partial = partial_path(&session_dir.join("response_1.png"));
+        fs::write(&partial, b"half").unwrap();
+        fs::write(session_dir.join("response_1.png"), b"image").unwrap();
+        fs::write(session_dir.join(".cleaned"), "").unwrap();
+
+        // 还未超过期限的文件不会被删除
+        let now = SystemTime::now();
+        let report = sweep_temp_files(
+            &temp_dir,
+            &[root.join("sessions")],
+            Duration::from_secs(3600),
+            now,
+        )
+        .unwrap();
+        assert_eq!((report.scanned, report.removed), (3, 0));
+
+        let later = now + Duration::from_secs(7200);
+        let report = sweep_temp_files(
+            &temp_dir,
+            &[root.join("sessions")],
+            Duration::from_secs(3600),
+            later,
+        )
+        .unwrap();
+        assert_eq!(report.removed, 3);
+        assert_eq!(report.reclaimed_bytes, 6 + 3 + 4);
+        assert!(!partial.exists());
+        assert!(session_dir.join("response_1.png").exists());
+        assert!(session_dir.join(".cleaned").exists());
+    }
+
+    #[test]
+    fn commits_partial_with_converted_extension() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let output_path = dir.join("response.png");
+        let partial = partial_path(&output_path);
+        assert!(is_partial_file(
+            &partial.file_name().unwrap().to_string_lossy()
+        ));
+
+        // 格式转换后中间文件的扩展名改变，目标路径随之改变
+        let converted = partial.with_extension("webp");
+        fs::write(&converted, b"webp").unwrap();
+        let committed = commit_partial(&converted, &output_path).unwrap();
+        assert_eq!(committed, dir.join("response.webp"));
+        assert!(!converted.exists());
+    }
+}
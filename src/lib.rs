@@ -1,8 +1,12 @@
 pub mod api;
 pub mod config;
+pub mod delivery;
 pub mod discord;
 pub mod image;
+pub mod scheduler;
 pub mod session;
+pub mod util;
+pub mod web;
 
 // 重新导出常用的类型
 pub use api::APIClient;
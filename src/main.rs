@@ -1,8 +1,12 @@
 mod api;
 mod config;
+mod delivery;
 mod discord;
 mod image;
+mod scheduler;
 mod session;
+mod util;
+mod web;
 
 use anyhow::Result;
 use chrono::Local;
@@ -28,7 +32,7 @@ async fn main() -> Result<()> {
     // 设置日志级别：INFO 为默认，项目模块启用 DEBUG，可通过 RUST_LOG 环境变量覆盖
     let default_filter = "info,rust_discord_bot=debug,rust_discord_bot::api=debug,rust_discord_bot::discord=debug,rust_discord_bot::image=debug";
     // 仅使用默认过滤，避免外部库的 DEBUG 日志
-    let env_filter = EnvFilter::new(default_filter.to_string());
+    let env_filter = EnvFilter::new(default_filter);
     fmt::fmt()
         .with_env_filter(env_filter)
         .with_timer(LocalOnlyTime) // 只输出日期和时分秒
@@ -39,16 +43,6 @@ async fn main() -> Result<()> {
 
     // 初始化配置
     let config = config::Config::init()?;
-    // 创建会话管理器并启动定期清理任务
-    let session_manager = session::SessionManager::new(&config);
-    tokio::spawn(async move {
-        let expiry_days = config.session_expiry;
-        let interval = std::time::Duration::from_secs(expiry_days * 24 * 60 * 60);
-        loop {
-            session_manager.periodic_cleanup(expiry_days).await;
-            tokio::time::sleep(interval).await;
-        }
-    });
 
     info!("配置已加载");
 
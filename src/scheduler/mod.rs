@@ -0,0 +1,339 @@
+mod schedule;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tracing::{error, info, warn};
+
+pub use self::schedule::Schedule;
+
+type JobFuture = Pin<Box<dyn Future<Output = Result<String>> + Send>>;
+type JobFn = Arc<dyn Fn() -> JobFuture + Send + Sync>;
+
+/// 后台任务：名称、执行计划与执行函数，执行成功时返回一行结果说明
+pub struct Job {
+    name: String,
+    description: String,
+    schedule: Schedule,
+    run: JobFn,
+}
+
+impl Job {
+    pub fn new<F, Fut>(name: &str, description: &str, schedule: Schedule, run: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String>> + Send + 'static,
+    {
+        Job {
+            name: name.to_string(),
+            description: description.to_string(),
+            schedule,
+            run: Arc::new(move || Box::pin(run())),
+        }
+    }
+}
+
+/// 任务最近一次执行的状态，保存在 data/jobs.json
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobStatus {
+    pub last_started: Option<DateTime<Utc>>,
+    pub last_finished: Option<DateTime<Utc>>,
+    pub last_success: Option<bool>,
+    pub last_message: Option<String>,
+    pub last_duration_ms: Option<u64>,
+    #[serde(default)]
+    pub runs: u64,
+    #[serde(default)]
+    pub failures: u64,
+}
+
+/// 用于列出任务的快照
+#[derive(Debug, Clone)]
+pub struct JobInfo {
+    pub name: String,
+    pub description: String,
+    pub schedule: String,
+    pub running: bool,
+    pub next_run: Option<DateTime<Utc>>,
+    pub status: JobStatus,
+}
+
+// 已注册的任务及其运行标记
+struct JobEntry {
+    job: Job,
+    running: AtomicBool,
+    // 手动执行后唤醒调度循环重新计算下次执行时间
+    wake: Notify,
+}
+
+/// 后台任务调度器
+///
+/// 每个任务一个调度循环，按执行计划加上随机抖动等待后执行。
+/// 同一任务同时只会运行一次，定时执行与手动执行重叠时后者直接返回错误。
+#[derive(Clone)]
+pub struct Scheduler {
+    path: PathBuf,
+    jitter: Duration,
+    jobs: Vec<Arc<JobEntry>>,
+    state: Arc<Mutex<HashMap<String, JobStatus>>>,
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("path", &self.path)
+            .field("jitter", &self.jitter)
+            .field(
+                "jobs",
+                &self.jobs.iter().map(|e| &e.job.name).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl Scheduler {
+    /// 创建调度器并读取 `data_dir/jobs.json` 中保存的执行状态
+    pub fn new(data_dir: &std::path::Path, jitter: Duration) -> Self {
+        let path = data_dir.join("jobs.json");
+        let state = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                error!("解析任务状态失败，将重新记录: {}", e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+
+        Scheduler {
+            path,
+            jitter,
+            jobs: Vec::new(),
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// 注册任务，需在 `start` 之前调用
+    pub fn register(&mut self, job: Job) {
+        if self.entry(&job.name).is_some() {
+            warn!("任务 {} 重复注册，已忽略", job.name);
+            return;
+        }
+        info!("已注册后台任务: {} ({})", job.name, job.schedule);
+        self.jobs.push(Arc::new(JobEntry {
+            job,
+            running: AtomicBool::new(false),
+            wake: Notify::new(),
+        }));
+    }
+
+    /// 为每个任务启动调度循环
+    pub fn start(&self) {
+        for entry in &self.jobs {
+            let scheduler = self.clone();
+            let entry = entry.clone();
+            tokio::spawn(async move { scheduler.run_loop(entry).await });
+        }
+    }
+
+    /// 列出全部任务及其状态
+    pub fn jobs(&self) -> Vec<JobInfo> {
+        let state = self.state.lock().unwrap();
+        let now = Utc::now();
+        self.jobs
+            .iter()
+            .map(|entry| {
+                let status = state.get(&entry.job.name).cloned().unwrap_or_default();
+                JobInfo {
+                    name: entry.job.name.clone(),
+                    description: entry.job.description.clone(),
+                    schedule: entry.job.schedule.to_string(),
+                    running: entry.running.load(Ordering::SeqCst),
+                    next_run: entry.job.schedule.next_run(status.last_started, now),
+                    status,
+                }
+            })
+            .collect()
+    }
+
+    /// 立即执行任务并等待结果，任务正在运行时返回错误
+    pub async fn run_now(&self, name: &str) -> Result<String> {
+        let entry = self
+            .entry(name)
+            .ok_or_else(|| anyhow!("未找到任务: {}", name))?;
+        let result = self.execute(&entry).await;
+        entry.wake.notify_one();
+        result
+    }
+
+    fn entry(&self, name: &str) -> Option<Arc<JobEntry>> {
+        self.jobs.iter().find(|e| e.job.name == name).cloned()
+    }
+
+    async fn run_loop(&self, entry: Arc<JobEntry>) {
+        loop {
+            let last_started = self
+                .state
+                .lock()
+                .unwrap()
+                .get(&entry.job.name)
+                .and_then(|s| s.last_started);
+            let now = Utc::now();
+            let Some(next) = entry.job.schedule.next_run(last_started, now) else {
+                warn!("任务 {} 没有后续执行时间，停止调度", entry.job.name);
+                return;
+            };
+            let delay = (next - now).to_std().unwrap_or_default() + self.random_jitter();
+
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                // 手动执行过，重新计算下次执行时间
+                _ = entry.wake.notified() => continue,
+            }
+
+            if let Err(e) = self.execute(&entry).await {
+                warn!("{:?}", e);
+            }
+        }
+    }
+
+    fn random_jitter(&self) -> Duration {
+        if self.jitter.is_zero() {
+            return Duration::ZERO;
+        }
+        rand::thread_rng().gen_range(Duration::ZERO..=self.jitter)
+    }
+
+    async fn execute(&self, entry: &JobEntry) -> Result<String> {
+        let name = &entry.job.name;
+        if entry.running.swap(true, Ordering::SeqCst) {
+            return Err(anyhow!("任务 {} 正在运行，跳过本次执行", name));
+        }
+
+        let started_at = Utc::now();
+        self.update_status(name, |status| status.last_started = Some(started_at));
+        info!("开始执行后台任务: {}", name);
+
+        // 在独立的任务中执行，任务panic时记为失败而不影响调度循环
+        let start = Instant::now();
+        let result = match tokio::spawn((entry.job.run)()).await {
+            Ok(result) => result,
+            Err(e) => Err(anyhow!("任务异常退出: {}", e)),
+        };
+        let elapsed = start.elapsed();
+        entry.running.store(false, Ordering::SeqCst);
+
+        match &result {
+            Ok(message) => info!(
+                "后台任务 {} 执行完成，耗时 {:.1}s: {}",
+                name,
+                elapsed.as_secs_f64(),
+                message
+            ),
+            Err(e) => error!("后台任务 {} 执行失败: {:?}", name, e),
+        }
+        self.update_status(name, |status| {
+            status.last_finished = Some(Utc::now());
+            status.last_success = Some(result.is_ok());
+            status.last_message = Some(match &result {
+                Ok(message) => message.clone(),
+                Err(e) => format!("{:#}", e),
+            });
+            status.last_duration_ms = Some(elapsed.as_millis() as u64);
+            status.runs += 1;
+            if result.is_err() {
+                status.failures += 1;
+            }
+        });
+
+        result
+    }
+
+    fn update_status(&self, name: &str, update: impl FnOnce(&mut JobStatus)) {
+        let mut state = self.state.lock().unwrap();
+        update(state.entry(name.to_string()).or_default());
+        if let Err(e) = self.save(&state) {
+            error!("保存任务状态失败: {:?}", e);
+        }
+    }
+
+    fn save(&self, state: &HashMap<String, JobStatus>) -> Result<()> {
+        let content = serde_json::to_string_pretty(state).context("序列化任务状态失败")?;
+        // 先写入临时文件再替换，避免中途退出导致文件损坏
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, content).context("写入任务状态失败")?;
+        fs::rename(&tmp_path, &self.path).context("保存任务状态失败")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_now_records_status_and_prevents_overlap() {
+        let temp = tempfile::tempdir().unwrap();
+        let data_dir = temp.path();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let mut scheduler = Scheduler::new(data_dir, Duration::ZERO);
+        let gate = Arc::new(Notify::new());
+        let job_gate = gate.clone();
+        scheduler.register(Job::new(
+            "slow",
+            "测试任务",
+            "1d".parse().unwrap(),
+            move || {
+                let gate = job_gate.clone();
+                async move {
+                    gate.notified().await;
+                    Ok("完成".to_string())
+                }
+            },
+        ));
+        scheduler.register(Job::new(
+            "broken",
+            "失败任务",
+            "1d".parse().unwrap(),
+            || async { Err(anyhow!("出错了")) },
+        ));
+
+        runtime.block_on(async {
+            let running = tokio::spawn({
+                let scheduler = scheduler.clone();
+                async move { scheduler.run_now("slow").await }
+            });
+            while !scheduler.jobs()[0].running {
+                tokio::task::yield_now().await;
+            }
+            assert!(scheduler.run_now("slow").await.is_err());
+            gate.notify_one();
+            assert_eq!(running.await.unwrap().unwrap(), "完成");
+
+            assert!(scheduler.run_now("broken").await.is_err());
+            assert!(scheduler.run_now("missing").await.is_err());
+        });
+
+        // 重新加载后仍能读到执行状态
+        let reloaded = Scheduler::new(data_dir, Duration::ZERO);
+        let state = reloaded.state.lock().unwrap();
+        let slow = &state["slow"];
+        assert_eq!(
+            (slow.runs, slow.failures, slow.last_success),
+            (1, 0, Some(true))
+        );
+        let broken = &state["broken"];
+        assert_eq!(broken.failures, 1);
+        assert_eq!(broken.last_message.as_deref(), Some("出错了"));
+    }
+}
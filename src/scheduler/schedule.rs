@@ -0,0 +1,248 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Duration, Local, Utc};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// 任务的执行计划
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    /// 固定间隔，从上次开始执行的时间算起
+    Interval(Duration),
+    /// cron 表达式，按本地时间计算
+    Cron {
+        expression: String,
+        schedule: Box<cron::Schedule>,
+    },
+}
+
+impl Schedule {
+    /// 计算下一次执行时间
+    ///
+    /// `last_run` 为上次开始执行的时间。从未执行过的间隔任务立即执行，
+    /// cron 任务等待 `now` 之后的第一个时间点；停机期间错过的执行只补一次。
+    pub fn next_run(
+        &self,
+        last_run: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        match self {
+            Schedule::Interval(interval) => Some(last_run.map_or(now, |last| last + *interval)),
+            Schedule::Cron { schedule, .. } => schedule
+                .after(&last_run.unwrap_or(now).with_timezone(&Local))
+                .next()
+                .map(|next| next.with_timezone(&Utc)),
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = anyhow::Error;
+
+    /// 支持 `30m`、`6h`、`1d` 形式的间隔，或5段（分 时 日 月 周）的 cron 表达式，
+    /// 也可以使用带秒的6段或7段写法
+    ///
+    /// 5段写法与 crontab 相同，星期为0-7（0与7都是周日）；6段与7段写法沿用 cron 库的规则，
+    /// 星期为1-7（1是周日）。
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Some(interval) = parse_interval(s)? {
+            return Ok(Schedule::Interval(interval));
+        }
+        let fields = s.split_whitespace().count();
+        let expression = match fields {
+            5 => {
+                let mut parts: Vec<String> = s.split_whitespace().map(str::to_string).collect();
+                parts[4] = translate_weekdays(&parts[4])?;
+                format!("0 {}", parts.join(" "))
+            }
+            6 | 7 => s.to_string(),
+            _ => return Err(anyhow!("无法识别的执行计划: {}", s)),
+        };
+        let schedule = cron::Schedule::from_str(&expression)
+            .map_err(|e| anyhow!("无效的cron表达式 {}: {}", s, e))?;
+        Ok(Schedule::Cron {
+            expression: s.to_string(),
+            schedule: Box::new(schedule),
+        })
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schedule::Interval(interval) => write!(f, "每 {}", format_duration(*interval)),
+            Schedule::Cron { expression, .. } => write!(f, "cron `{}`", expression),
+        }
+    }
+}
+
+// 解析 30s、30m、6h、1d 形式的间隔，不是这种形式时返回 None
+fn parse_interval(s: &str) -> Result<Option<Duration>> {
+    let Some(unit) = s.chars().last() else {
+        return Ok(None);
+    };
+    let number = &s[..s.len() - unit.len_utf8()];
+    if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+        return Ok(None);
+    }
+    let value: i64 = number.parse().context("间隔数值过大")?;
+    let interval = match unit {
+        's' => Duration::seconds(value),
+        'm' => Duration::minutes(value),
+        'h' => Duration::hours(value),
+        'd' => Duration::days(value),
+        _ => return Ok(None),
+    };
+    if interval <= Duration::zero() {
+        return Err(anyhow!("执行间隔必须大于0: {}", s));
+    }
+    Ok(Some(interval))
+}
+
+// 将 crontab 的星期字段（0-7，0与7都是周日）转换为 cron 库的写法（1-7，1是周日）
+//
+// 支持 `*`、列表、范围、步长与 sun-sat 英文缩写，转换结果为逗号分隔的星期列表。
+fn translate_weekdays(field: &str) -> Result<String> {
+    if field == "*" || field == "?" {
+        return Ok(field.to_string());
+    }
+    let mut days = BTreeSet::new();
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| anyhow!("无效的星期步长: {}", part))?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (0, 7)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_weekday(start)?, parse_weekday(end)?)
+        } else if part.contains('/') {
+            // 带步长的单个值表示从该值到周末
+            (parse_weekday(range)?, 7)
+        } else {
+            let day = parse_weekday(range)?;
+            (day, day)
+        };
+        if start > end {
+            return Err(anyhow!("无效的星期范围: {}", part));
+        }
+        days.extend((start..=end).step_by(step as usize).map(|day| day % 7));
+    }
+    Ok(days
+        .iter()
+        .map(|day| (day + 1).to_string())
+        .collect::<Vec<_>>()
+        .join(","))
+}
+
+// 解析 crontab 的星期值：0-7 或 sun-sat
+fn parse_weekday(value: &str) -> Result<u32> {
+    const NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+    if let Ok(day) = value.parse::<u32>() {
+        return if day <= 7 {
+            Ok(day)
+        } else {
+            Err(anyhow!("星期必须在0-7之间: {}", value))
+        };
+    }
+    NAMES
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(value))
+        .map(|day| day as u32)
+        .ok_or_else(|| anyhow!("无法识别的星期: {}", value))
+}
+
+// 以最大的整数单位显示时长
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.num_seconds();
+    if seconds % 86400 == 0 {
+        format!("{} 天", seconds / 86400)
+    } else if seconds % 3600 == 0 {
+        format!("{} 小时", seconds / 3600)
+    } else if seconds % 60 == 0 {
+        format!("{} 分钟", seconds / 60)
+    } else {
+        format!("{} 秒", seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Datelike, TimeZone, Timelike, Weekday};
+
+    #[test]
+    fn parses_intervals() {
+        let schedule: Schedule = "6h".parse().unwrap();
+        assert_eq!(schedule.to_string(), "每 6 小时");
+        let now = Utc::now();
+        assert_eq!(schedule.next_run(None, now), Some(now));
+        let last = now - Duration::hours(1);
+        assert_eq!(
+            schedule.next_run(Some(last), now),
+            Some(last + Duration::hours(6))
+        );
+
+        assert_eq!("90s".parse::<Schedule>().unwrap().to_string(), "每 90 秒");
+        assert!("0h".parse::<Schedule>().is_err());
+        assert!("6x".parse::<Schedule>().is_err());
+    }
+
+    #[test]
+    fn parses_five_field_cron_in_local_time() {
+        let schedule: Schedule = "30 3 * * *".parse().unwrap();
+        assert_eq!(schedule.to_string(), "cron `30 3 * * *`");
+        let now = Local
+            .with_ymd_and_hms(2024, 1, 31, 12, 0, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        let next = schedule.next_run(None, now).unwrap().with_timezone(&Local);
+        assert_eq!((next.hour(), next.minute()), (3, 30));
+        assert!(next.with_timezone(&Utc) > now);
+
+        // 停机期间错过的执行在启动后立即补上
+        let last = now - Duration::days(3);
+        assert!(schedule.next_run(Some(last), now).unwrap() < now);
+
+        assert!("0 0 3 * * *".parse::<Schedule>().is_ok());
+        assert!("0 9 * * 8".parse::<Schedule>().is_err());
+        assert!("61 * * * *".parse::<Schedule>().is_err());
+        assert!("每天".parse::<Schedule>().is_err());
+    }
+
+    #[test]
+    fn translates_crontab_weekdays() {
+        assert_eq!(translate_weekdays("0").unwrap(), "1");
+        assert_eq!(translate_weekdays("7").unwrap(), "1");
+        assert_eq!(translate_weekdays("1-5").unwrap(), "2,3,4,5,6");
+        assert_eq!(translate_weekdays("5-7").unwrap(), "1,6,7");
+        assert_eq!(translate_weekdays("*/2").unwrap(), "1,3,5,7");
+        assert_eq!(translate_weekdays("Sun,sat").unwrap(), "1,7");
+        assert_eq!(translate_weekdays("*").unwrap(), "*");
+        assert!(translate_weekdays("5-1").is_err());
+        assert!(translate_weekdays("mon/0").is_err());
+
+        // 2024-02-03 是周六，工作日任务在下周一执行，周日任务在次日执行
+        let now = Local
+            .with_ymd_and_hms(2024, 2, 3, 12, 0, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        let next_day = |expression: &str| {
+            let schedule: Schedule = expression.parse().unwrap();
+            let next = schedule.next_run(None, now).unwrap().with_timezone(&Local);
+            (next.weekday(), next.hour())
+        };
+        assert_eq!(next_day("0 9 * * 1-5"), (Weekday::Mon, 9));
+        assert_eq!(next_day("0 9 * * 0"), (Weekday::Sun, 9));
+        assert_eq!(next_day("0 9 * * 7"), (Weekday::Sun, 9));
+        assert_eq!(next_day("0 0 9 * * Mon"), (Weekday::Mon, 9));
+    }
+}
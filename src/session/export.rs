@@ -0,0 +1,120 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::PathBuf;
+use tracing::warn;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use super::meta::ArtifactKind;
+use super::SessionMeta;
+
+/// 将会话目录打包为zip，返回zip数据与打包的会话数
+///
+/// 每个会话一个目录。`include_images` 为 false 时跳过回答图片与PDF，用于压缩后仍超过上传限制的情况。
+/// 元数据中的用户与 `user_id` 不一致的会话会被跳过。
+pub(super) fn export_sessions(
+    user_id: &str,
+    sessions: &[(String, PathBuf)],
+    include_images: bool,
+) -> Result<(Vec<u8>, usize)> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let text_options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    // 图片本身已压缩，直接存储
+    let image_options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let mut exported = 0;
+
+    for (session_id, session_dir) in sessions {
+        let meta = match SessionMeta::load(session_dir) {
+            Ok(Some(meta)) => meta,
+            Ok(None) => SessionMeta::from_legacy_dir(session_dir)?,
+            Err(e) => {
+                warn!("导出时读取会话 {} 元数据失败: {:?}", session_id, e);
+                continue;
+            }
+        };
+        if meta.user_id != user_id {
+            continue;
+        }
+
+        let mut files: Vec<_> = fs::read_dir(session_dir)
+            .with_context(|| format!("读取会话目录失败: {}", session_id))?
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().map(|t| t.is_file()).unwrap_or(false))
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            // 跳过写入元数据时的临时文件
+            .filter(|name| !name.starts_with('.'))
+            .collect();
+        files.sort();
+
+        for name in files {
+            let kind = ArtifactKind::from_file_name(&name);
+            let is_image = matches!(kind, ArtifactKind::Image | ArtifactKind::Pdf);
+            if is_image && !include_images {
+                continue;
+            }
+            let data = fs::read(session_dir.join(&name))
+                .with_context(|| format!("读取会话文件失败: {}/{}", session_id, name))?;
+            let options = if is_image {
+                image_options
+            } else {
+                text_options
+            };
+            zip.start_file(format!("{}/{}", session_id, name), options)?;
+            zip.write_all(&data)?;
+        }
+        exported += 1;
+    }
+
+    let cursor = zip.finish().context("生成zip文件失败")?;
+    Ok((cursor.into_inner(), exported))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use uuid::Uuid;
+    use zip::ZipArchive;
+
+    #[test]
+    fn exports_only_own_sessions() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        let mut sessions = Vec::new();
+        for (user_id, question) in [("42", "我的问题"), ("7", "别人的问题")] {
+            let session_id = Uuid::new_v4().to_string();
+            let session_dir = root.join(&session_id);
+            fs::create_dir_all(&session_dir).unwrap();
+            SessionMeta::new(user_id).save(&session_dir).unwrap();
+            fs::write(session_dir.join("input.txt"), question).unwrap();
+            fs::write(session_dir.join("response.md"), "# 回答").unwrap();
+            fs::write(session_dir.join("response_1.png"), b"png").unwrap();
+            sessions.push((session_id, session_dir));
+        }
+
+        let (data, count) = export_sessions("42", &sessions, true).unwrap();
+        assert_eq!(count, 1);
+        let mut archive = ZipArchive::new(Cursor::new(data)).unwrap();
+        let own = &sessions[0].0;
+        let mut names: Vec<String> = archive.file_names().map(str::to_string).collect();
+        names.sort();
+        assert_eq!(
+            names,
+            ["input.txt", "response.md", "response_1.png", "session.json"]
+                .map(|name| format!("{}/{}", own, name))
+        );
+        let mut input = String::new();
+        archive
+            .by_name(&format!("{}/input.txt", own))
+            .unwrap()
+            .read_to_string(&mut input)
+            .unwrap();
+        assert_eq!(input, "我的问题");
+
+        // 不含图片时只导出文字内容
+        let (data, _) = export_sessions("42", &sessions, false).unwrap();
+        let archive = ZipArchive::new(Cursor::new(data)).unwrap();
+        assert!(archive.file_names().all(|name| !name.ends_with(".png")));
+    }
+}
@@ -0,0 +1,356 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+use super::transcript::TRANSCRIPT_FILE;
+
+/// 当前 session.json 的格式版本
+///
+/// 2: 增加对话消息数 `turns`
+pub const SESSION_META_VERSION: u32 = 2;
+/// 元数据文件名
+pub const META_FILE: &str = "session.json";
+/// 目前唯一的对话后端
+pub const DEFAULT_BACKEND: &str = "fastgpt";
+
+/// 会话状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionStatus {
+    /// 已创建，尚未得到回答
+    #[default]
+    Pending,
+    /// 已成功回答
+    Ok,
+    /// 请求或渲染失败
+    Error,
+    /// 未得到有效回答，已取消后续处理
+    Cancelled,
+}
+
+/// 会话产物的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtifactKind {
+    /// 用户输入 input.txt
+    Input,
+    /// 回答Markdown response.md
+    Response,
+    /// 用户提供的图片链接 image_urls.txt
+    ImageUrls,
+    /// 多轮对话记录 transcript.jsonl
+    Transcript,
+    /// 渲染出的回答图片
+    Image,
+    /// 导出的PDF
+    Pdf,
+    /// 其他文件
+    Other,
+}
+
+impl ArtifactKind {
+    /// 根据文件名判断产物类型
+    pub fn from_file_name(name: &str) -> Self {
+        let extension = Path::new(name)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match (name, extension.as_str()) {
+            ("input.txt", _) => ArtifactKind::Input,
+            ("response.md", _) => ArtifactKind::Response,
+            ("image_urls.txt", _) => ArtifactKind::ImageUrls,
+            (TRANSCRIPT_FILE, _) => ArtifactKind::Transcript,
+            (_, "png" | "jpg" | "jpeg" | "webp") => ArtifactKind::Image,
+            (_, "pdf") => ArtifactKind::Pdf,
+            _ => ArtifactKind::Other,
+        }
+    }
+}
+
+/// 会话目录中的一个文件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Artifact {
+    pub kind: ArtifactKind,
+    /// 会话目录内的文件名
+    pub file: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// 一次回答消耗的token数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+}
+
+/// 公开网页分享链接
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShareLink {
+    /// 链接中的随机令牌，撤销后失效
+    pub token: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// 会话元数据，保存在会话目录的 session.json 中
+///
+/// 旧版会话只有零散的文件，首次读取时由 [`SessionMeta::from_legacy_dir`] 推断生成。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionMeta {
+    pub version: u32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub user_id: String,
+    #[serde(default)]
+    pub guild_id: Option<String>,
+    #[serde(default)]
+    pub channel_id: Option<String>,
+    /// 触发本次问答的消息或交互ID
+    #[serde(default)]
+    pub message_id: Option<String>,
+    /// 对话后端，目前固定为 fastgpt
+    pub backend: String,
+    /// 后端应用名称
+    #[serde(default)]
+    pub app_name: Option<String>,
+    /// 后端的对话ID，未使用上下文时为空
+    #[serde(default)]
+    pub chat_id: Option<String>,
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+    /// 从发送请求到收到完整回答的耗时（毫秒）
+    #[serde(default)]
+    pub latency_ms: Option<u64>,
+    #[serde(default)]
+    pub status: SessionStatus,
+    /// 失败时的错误信息
+    #[serde(default)]
+    pub error: Option<String>,
+    /// 用户评分
+    #[serde(default)]
+    pub rating: Option<i8>,
+    #[serde(default)]
+    pub artifacts: Vec<Artifact>,
+    /// 对话中的消息数（提问与回答各算一条）
+    #[serde(default)]
+    pub turns: u32,
+    /// 网页分享链接，未分享或已撤销时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub share: Option<ShareLink>,
+}
+
+impl SessionMeta {
+    pub fn new(user_id: &str) -> Self {
+        let now = Utc::now();
+        SessionMeta {
+            version: SESSION_META_VERSION,
+            created_at: now,
+            updated_at: now,
+            user_id: user_id.to_string(),
+            guild_id: None,
+            channel_id: None,
+            message_id: None,
+            backend: DEFAULT_BACKEND.to_string(),
+            app_name: None,
+            chat_id: None,
+            usage: None,
+            latency_ms: None,
+            status: SessionStatus::Pending,
+            error: None,
+            rating: None,
+            artifacts: Vec::new(),
+            turns: 0,
+            share: None,
+        }
+    }
+
+    /// 根据旧版会话目录中的文件推断元数据
+    ///
+    /// 创建时间取 user_id.txt 的修改时间，更新时间取目录的修改时间；
+    /// 有 response.md 视为回答成功，否则视为失败。
+    pub fn from_legacy_dir(dir: &Path) -> Result<Self> {
+        let dir_modified = modified_time(dir).context("读取会话目录失败")?;
+        let user_id_path = dir.join("user_id.txt");
+        let user_id = fs::read_to_string(&user_id_path).unwrap_or_default();
+        let created_at = modified_time(&user_id_path).unwrap_or(dir_modified);
+
+        let mut meta = SessionMeta::new(user_id.trim());
+        meta.created_at = created_at;
+        meta.updated_at = dir_modified;
+
+        let mut files: Vec<(String, DateTime<Utc>)> = fs::read_dir(dir)
+            .context("读取会话目录失败")?
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().map(|t| t.is_file()).unwrap_or(false))
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if name.starts_with('.') || name == "user_id.txt" || name == META_FILE {
+                    return None;
+                }
+                let modified = modified_time(&entry.path()).unwrap_or(dir_modified);
+                Some((name, modified))
+            })
+            .collect();
+        files.sort();
+        for (file, created_at) in files {
+            meta.artifacts.push(Artifact {
+                kind: ArtifactKind::from_file_name(&file),
+                file,
+                created_at,
+            });
+        }
+
+        meta.status = if meta.has_artifact(ArtifactKind::Response) {
+            SessionStatus::Ok
+        } else {
+            SessionStatus::Error
+        };
+        meta.turns = meta.legacy_turns();
+        Ok(meta)
+    }
+
+    /// 读取目录中的 session.json，不存在时返回 `None`
+    pub fn load(dir: &Path) -> Result<Option<Self>> {
+        let path = dir.join(META_FILE);
+        let data = match fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).context("读取会话元数据失败"),
+        };
+        let mut meta: SessionMeta = serde_json::from_str(&data).context("解析会话元数据失败")?;
+        if meta.version > SESSION_META_VERSION {
+            bail!("不支持的会话元数据版本: {}", meta.version);
+        }
+        if meta.version < 2 {
+            // 此前的会话只有一问一答，下次写入时保存为新版本
+            meta.turns = meta.legacy_turns();
+            meta.version = SESSION_META_VERSION;
+        }
+        Ok(Some(meta))
+    }
+
+    /// 写入 session.json，先写临时文件再重命名，避免读取到不完整的内容
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let data = serde_json::to_string_pretty(self).context("序列化会话元数据失败")?;
+        let temp_path = dir.join(format!(".{}.{}.tmp", META_FILE, Uuid::new_v4()));
+        fs::write(&temp_path, data).context("写入会话元数据失败")?;
+        if let Err(e) = fs::rename(&temp_path, dir.join(META_FILE)) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e).context("重命名会话元数据失败");
+        }
+        Ok(())
+    }
+
+    /// 记录产物，同名文件只保留一条
+    pub fn add_artifact(&mut self, file: &str) {
+        let artifact = Artifact {
+            kind: ArtifactKind::from_file_name(file),
+            file: file.to_string(),
+            created_at: Utc::now(),
+        };
+        match self.artifacts.iter_mut().find(|a| a.file == file) {
+            Some(existing) => *existing = artifact,
+            None => self.artifacts.push(artifact),
+        }
+    }
+
+    pub fn has_artifact(&self, kind: ArtifactKind) -> bool {
+        self.artifacts.iter().any(|a| a.kind == kind)
+    }
+
+    // 没有对话记录的会话按是否保存了问题与回答计算消息数
+    fn legacy_turns(&self) -> u32 {
+        self.has_artifact(ArtifactKind::Input) as u32
+            + self.has_artifact(ArtifactKind::Response) as u32
+    }
+
+    /// 回答图片的数量
+    pub fn image_count(&self) -> u32 {
+        self.artifacts
+            .iter()
+            .filter(|a| a.kind == ArtifactKind::Image)
+            .count() as u32
+    }
+}
+
+fn modified_time(path: &Path) -> Result<DateTime<Utc>> {
+    let modified: SystemTime = fs::metadata(path)?.modified()?;
+    Ok(DateTime::<Utc>::from(modified))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_load_roundtrip() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        assert!(SessionMeta::load(dir).unwrap().is_none());
+
+        let mut meta = SessionMeta::new("42");
+        meta.guild_id = Some("1".into());
+        meta.usage = Some(TokenUsage {
+            prompt_tokens: 10,
+            completion_tokens: 20,
+            total_tokens: 30,
+        });
+        meta.add_artifact("response_1.png");
+        meta.add_artifact("response_1.png");
+        meta.share = Some(ShareLink {
+            token: "token".into(),
+            created_at: Utc::now(),
+        });
+        meta.save(dir).unwrap();
+
+        let loaded = SessionMeta::load(dir).unwrap().unwrap();
+        assert_eq!(loaded, meta);
+        assert_eq!(loaded.image_count(), 1);
+        // 只留下正式文件，不残留临时文件
+        assert_eq!(fs::read_dir(dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn infers_meta_from_legacy_dir() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        fs::write(dir.join("user_id.txt"), "42\n").unwrap();
+        fs::write(dir.join("input.txt"), "问题").unwrap();
+        fs::write(dir.join("response.md"), "# 回答").unwrap();
+        fs::write(dir.join("response_1700000000.png"), b"png").unwrap();
+        fs::write(dir.join(".cleaned"), "").unwrap();
+
+        let meta = SessionMeta::from_legacy_dir(dir).unwrap();
+        assert_eq!(meta.version, SESSION_META_VERSION);
+        assert_eq!(meta.user_id, "42");
+        assert_eq!(meta.status, SessionStatus::Ok);
+        assert_eq!(meta.turns, 2);
+        let kinds: Vec<ArtifactKind> = meta.artifacts.iter().map(|a| a.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ArtifactKind::Input,
+                ArtifactKind::Response,
+                ArtifactKind::Image
+            ]
+        );
+
+        fs::remove_file(dir.join("response.md")).unwrap();
+        let meta = SessionMeta::from_legacy_dir(dir).unwrap();
+        assert_eq!(meta.status, SessionStatus::Error);
+    }
+
+    #[test]
+    fn rejects_newer_version() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let mut meta = SessionMeta::new("42");
+        meta.version = SESSION_META_VERSION + 1;
+        meta.save(dir).unwrap();
+        assert!(SessionMeta::load(dir).is_err());
+    }
+}
@@ -1,16 +1,43 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use chrono::{DateTime, Utc};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
 use crate::config::Config;
+use crate::image::{commit_partial, partial_path, remove_cached_copies};
+
+mod export;
+mod meta;
+mod retention;
+mod search;
+mod share;
+mod sqlite;
+mod store;
+mod transcript;
+
+pub use self::meta::{SessionMeta, SessionStatus, ShareLink, TokenUsage};
+pub use self::retention::{RetentionAction, RetentionConfig, RetentionPolicy, RetentionReport};
+pub use self::search::{SearchPage, SearchQuery};
+pub use self::sqlite::SqliteSessionStore;
+pub use self::store::{FsSessionStore, SessionRecord, SessionStore, SessionStoreKind};
+pub use self::transcript::{markdown_with_context, Turn, TurnRole};
+
+use self::meta::ArtifactKind;
+use self::retention::SessionUsage;
 
 #[derive(Debug, Clone)]
 pub struct SessionManager {
     sessions_dir: PathBuf,
+    // 读写 session.json 时加锁，避免并发更新互相覆盖
+    meta_lock: Arc<Mutex<()>>,
+    // 会话索引，用于按用户查找会话
+    store: Arc<dyn SessionStore>,
+    // 渲染缓存目录，删除会话时一并删除其中的图片副本
+    render_cache_dir: PathBuf,
 }
 
 impl SessionManager {
@@ -24,7 +51,114 @@ impl SessionManager {
             }
         }
 
-        SessionManager { sessions_dir }
+        let store: Arc<dyn SessionStore> = match config.session_store {
+            SessionStoreKind::Fs => Arc::new(FsSessionStore::new(sessions_dir.clone())),
+            SessionStoreKind::Sqlite => {
+                match SqliteSessionStore::open(&config.data_dir.join("sessions.db")) {
+                    Ok(store) => Arc::new(store),
+                    Err(e) => {
+                        error!("打开SQLite会话索引失败，改为遍历会话目录: {:?}", e);
+                        Arc::new(FsSessionStore::new(sessions_dir.clone()))
+                    }
+                }
+            }
+        };
+
+        info!("会话索引后端: {}", store.name());
+
+        let manager = SessionManager {
+            sessions_dir,
+            meta_lock: Arc::new(Mutex::new(())),
+            store,
+            render_cache_dir: config.image_output_dir.join("cache"),
+        };
+
+        // 首次使用索引时导入已有的会话目录
+        match manager.store.needs_import() {
+            Ok(true) => match manager.import_sessions() {
+                Ok(count) => info!("已将 {} 个会话导入会话索引", count),
+                Err(e) => error!("导入会话索引失败: {:?}", e),
+            },
+            Ok(false) => {}
+            Err(e) => error!("检查会话索引状态失败: {:?}", e),
+        }
+
+        manager
+    }
+
+    /// 测试用：在 `dir` 下创建会话目录与指定后端的索引，不导入已有会话
+    #[cfg(test)]
+    pub(crate) fn for_test(dir: &Path, store: SessionStoreKind) -> Self {
+        let sessions_dir = dir.join("sessions");
+        fs::create_dir_all(&sessions_dir).unwrap();
+        let store: Arc<dyn SessionStore> = match store {
+            SessionStoreKind::Fs => Arc::new(FsSessionStore::new(sessions_dir.clone())),
+            SessionStoreKind::Sqlite => {
+                Arc::new(SqliteSessionStore::open(&dir.join("sessions.db")).unwrap())
+            }
+        };
+        SessionManager {
+            sessions_dir,
+            meta_lock: Arc::new(Mutex::new(())),
+            store,
+            render_cache_dir: dir.join("cache"),
+        }
+    }
+
+    /// 将会话目录中的所有会话写入索引，旧版会话同时迁移
+    ///
+    /// 全部导入成功后才标记为已导入，失败的会话会在下次启动时重试。
+    pub fn import_sessions(&self) -> Result<usize> {
+        let mut imported = 0;
+        let mut failed = 0;
+        for entry in fs::read_dir(&self.sessions_dir)?.filter_map(Result::ok) {
+            if !entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            if Uuid::parse_str(&name).is_err() {
+                continue;
+            }
+            // read_meta 会为旧版会话生成元数据并写入索引
+            match self.read_meta(&name) {
+                Ok(meta) => match self
+                    .index(&name, &meta)
+                    .and_then(|_| self.index_content(&name))
+                {
+                    Ok(()) => imported += 1,
+                    Err(e) => {
+                        failed += 1;
+                        warn!("导入会话 {} 失败: {:?}", name, e);
+                    }
+                },
+                Err(e) => {
+                    failed += 1;
+                    warn!("读取会话 {} 元数据失败: {:?}", name, e);
+                }
+            }
+        }
+        if failed == 0 {
+            self.store.finish_import()?;
+        } else {
+            warn!("{} 个会话导入失败，将在下次启动时重试", failed);
+        }
+        Ok(imported)
+    }
+
+    // 将会话的问题与回答写入全文索引
+    fn index_content(&self, session_id: &str) -> Result<()> {
+        let (input, response) = store::read_content(&self.get_session_dir(session_id));
+        self.store.index_content(session_id, &input, &response)
+    }
+
+    // 将会话写入索引
+    fn index(&self, session_id: &str, meta: &SessionMeta) -> Result<()> {
+        let input_preview = store::read_input_preview(&self.get_session_dir(session_id));
+        self.store.upsert(&SessionRecord {
+            id: session_id,
+            meta,
+            input_preview: &input_preview,
+        })
     }
 
     /// 创建新的会话
@@ -43,6 +177,13 @@ impl SessionManager {
             error!("保存用户ID失败: {}", e);
         }
 
+        // 写入会话元数据与索引
+        let meta = SessionMeta::new(user_id);
+        meta.save(&session_dir).context("保存会话元数据失败")?;
+        if let Err(e) = self.index(&session_id, &meta) {
+            warn!("写入会话 {} 索引失败: {:?}", session_id, e);
+        }
+
         Ok(session_id)
     }
 
@@ -51,50 +192,162 @@ impl SessionManager {
         self.sessions_dir.join(session_id)
     }
 
-    /// 保存用户输入到会话
-    pub async fn save_user_input(&self, session_id: &str, input: &str) -> Result<()> {
-        let session_dir = self.get_session_dir(session_id);
-        let input = input.to_string();
-        tokio::task::spawn_blocking(move || {
-            // Ensure session directory exists
-            fs::create_dir_all(&session_dir).context("创建会话目录失败")?;
-            let input_file = session_dir.join("input.txt");
-            fs::write(&input_file, input).context("保存用户输入失败")
+    /// 读取会话元数据
+    ///
+    /// 没有 session.json 的旧版会话会根据目录中的文件推断元数据并写入，之后直接读取。
+    pub fn read_meta(&self, session_id: &str) -> Result<SessionMeta> {
+        let _guard = self.meta_lock.lock().unwrap();
+        self.load_or_migrate_meta(session_id)
+    }
+
+    /// 覆盖写入会话元数据
+    #[allow(dead_code)]
+    pub fn write_meta(&self, session_id: &str, meta: &SessionMeta) -> Result<()> {
+        Uuid::parse_str(session_id).context("无效的会话ID")?;
+        let _guard = self.meta_lock.lock().unwrap();
+        meta.save(&self.get_session_dir(session_id))?;
+        self.index(session_id, meta)
+    }
+
+    /// 修改会话元数据并刷新更新时间，返回修改后的元数据
+    pub fn update_meta(
+        &self,
+        session_id: &str,
+        update: impl FnOnce(&mut SessionMeta),
+    ) -> Result<SessionMeta> {
+        self.modify_meta(session_id, |meta| {
+            update(meta);
+            meta.updated_at = Utc::now();
         })
-        .await
-        .context("保存用户输入任务失败")??;
-        Ok(())
     }
 
-    /// 保存API响应到会话
-    pub async fn save_response_markdown(&self, session_id: &str, markdown: &str) -> Result<()> {
+    /// 将会话目录中的文件记录为产物
+    ///
+    /// 元数据只是辅助记录，写入失败时仅记录警告。
+    pub fn record_artifacts<P: AsRef<Path>>(&self, session_id: &str, files: &[P]) {
+        let result = self.update_meta(session_id, |meta| {
+            for file in files {
+                if let Some(name) = file.as_ref().file_name() {
+                    meta.add_artifact(&name.to_string_lossy());
+                }
+            }
+        });
+        if let Err(e) = result {
+            warn!("记录会话 {} 的产物失败: {:?}", session_id, e);
+        }
+    }
+
+    // 修改元数据但不刷新更新时间，用于清理等非用户操作
+    fn modify_meta(
+        &self,
+        session_id: &str,
+        modify: impl FnOnce(&mut SessionMeta),
+    ) -> Result<SessionMeta> {
+        let _guard = self.meta_lock.lock().unwrap();
+        let mut meta = self.load_or_migrate_meta(session_id)?;
+        modify(&mut meta);
+        meta.save(&self.get_session_dir(session_id))?;
+        if let Err(e) = self.index(session_id, &meta) {
+            warn!("写入会话 {} 索引失败: {:?}", session_id, e);
+        }
+        Ok(meta)
+    }
+
+    // 调用方需持有 meta_lock
+    fn load_or_migrate_meta(&self, session_id: &str) -> Result<SessionMeta> {
+        Uuid::parse_str(session_id).context("无效的会话ID")?;
         let session_dir = self.get_session_dir(session_id);
-        let markdown = markdown.to_string();
-        tokio::task::spawn_blocking(move || {
-            // Ensure session directory exists
+        if let Some(meta) = SessionMeta::load(&session_dir)? {
+            return Ok(meta);
+        }
+        if !session_dir.is_dir() {
+            return Err(anyhow!("会话不存在: {}", session_id));
+        }
+        let meta = SessionMeta::from_legacy_dir(&session_dir)?;
+        meta.save(&session_dir)?;
+        if let Err(e) = self.index(session_id, &meta) {
+            warn!("写入会话 {} 索引失败: {:?}", session_id, e);
+        }
+        info!("已为旧版会话 {} 生成元数据", session_id);
+        Ok(meta)
+    }
+
+    /// 在会话的对话记录末尾追加一条消息
+    ///
+    /// 对话记录只追加不覆盖；同时将最新的问题、图片链接与回答写入 input.txt、image_urls.txt 与 response.md，
+    /// 供导出PDF、获取代码等只需要最新回答的功能读取。回答的 `attachments` 为渲染出的图片文件名。
+    pub async fn append_turn(&self, session_id: &str, turn: Turn) -> Result<()> {
+        Uuid::parse_str(session_id).context("无效的会话ID")?;
+        let session_dir = self.get_session_dir(session_id);
+        let (turns, files) = tokio::task::spawn_blocking(move || -> Result<_> {
             fs::create_dir_all(&session_dir).context("创建会话目录失败")?;
-            let response_file = session_dir.join("response.md");
-            fs::write(&response_file, markdown).context("保存API响应失败")
+            let turns = transcript::append_turn(&session_dir, &turn)?;
+            let files = match turn.role {
+                TurnRole::User => {
+                    fs::write(session_dir.join("input.txt"), &turn.content)
+                        .context("保存用户输入失败")?;
+                    // 没有提供图片的追问不覆盖之前的图片链接
+                    if turn.attachments.is_empty() {
+                        vec!["input.txt"]
+                    } else {
+                        fs::write(
+                            session_dir.join("image_urls.txt"),
+                            turn.attachments.join("\n"),
+                        )
+                        .context("保存用户图片URL失败")?;
+                        vec!["input.txt", "image_urls.txt"]
+                    }
+                }
+                TurnRole::Assistant => {
+                    fs::write(session_dir.join("response.md"), &turn.content)
+                        .context("保存API响应失败")?;
+                    vec!["response.md"]
+                }
+            };
+            Ok((turns, files))
         })
         .await
-        .context("保存API响应任务失败")??;
+        .context("保存对话记录任务失败")??;
+        // 对话记录已经保存，元数据写入失败时仅记录警告
+        let result = self.update_meta(session_id, |meta| {
+            meta.turns = turns as u32;
+            meta.add_artifact(transcript::TRANSCRIPT_FILE);
+            for file in &files {
+                meta.add_artifact(file);
+            }
+        });
+        if let Err(e) = result {
+            warn!("更新会话 {} 元数据失败: {:?}", session_id, e);
+        }
+        if let Err(e) = self.index_content(session_id) {
+            warn!("更新会话 {} 全文索引失败: {:?}", session_id, e);
+        }
         Ok(())
     }
 
-    /// 保存用户提供的图片链接到会话
-    pub async fn save_user_images(&self, session_id: &str, images: &[String]) -> Result<()> {
+    /// 读取会话的全部对话消息，按时间顺序排列
+    pub async fn load_transcript(&self, session_id: &str) -> Result<Vec<Turn>> {
+        Uuid::parse_str(session_id).context("无效的会话ID")?;
         let session_dir = self.get_session_dir(session_id);
-        let data = images.join("\n");
-        tokio::task::spawn_blocking(move || -> Result<()> {
-            // 确保会话目录存在
-            fs::create_dir_all(&session_dir).context("创建会话目录失败")?;
-            let file = session_dir.join("image_urls.txt");
-            fs::write(&file, data).context("保存用户图片URL失败")?;
-            Ok(())
+        tokio::task::spawn_blocking(move || transcript::load_turns(&session_dir))
+            .await
+            .context("读取对话记录任务失败")?
+    }
+
+    /// 读取会话保存的用户输入与回答Markdown
+    ///
+    /// 会话ID必须是合法的UUID，防止通过按钮数据访问会话目录以外的文件。
+    pub async fn load_response(&self, session_id: &str) -> Result<(String, String)> {
+        Uuid::parse_str(session_id).context("无效的会话ID")?;
+        let session_dir = self.get_session_dir(session_id);
+        tokio::task::spawn_blocking(move || -> Result<(String, String)> {
+            let input = fs::read_to_string(session_dir.join("input.txt")).unwrap_or_default();
+            let markdown =
+                fs::read_to_string(session_dir.join("response.md")).context("读取会话回答失败")?;
+            Ok((input, markdown))
         })
         .await
-        .context("保存用户图片URL任务失败")??;
-        Ok(())
+        .context("读取会话回答任务失败")?
     }
 
     /// 保存响应图片到会话
@@ -105,7 +358,7 @@ impl SessionManager {
     ) -> Result<PathBuf> {
         let session_dir = self.get_session_dir(session_id);
         let original = original_image_path.to_path_buf();
-        tokio::task::spawn_blocking(move || -> Result<PathBuf> {
+        let target_path = tokio::task::spawn_blocking(move || -> Result<PathBuf> {
             // Ensure session directory exists
             fs::create_dir_all(&session_dir).context("创建会话目录失败")?;
             // 验证目录是否已创建，并记录日志
@@ -119,109 +372,255 @@ impl SessionManager {
                 .unwrap_or_default()
                 .as_secs();
 
-            let filename = format!("response_{}.png", now);
-            let target_path = session_dir.join(&filename);
-            fs::copy(&original, &target_path).context("复制响应图片到会话目录失败")?;
-            Ok(target_path)
+            let target_path = session_dir.join(format!("response_{}.png", now));
+            // 先复制为隐藏的中间文件再重命名，避免会话中出现写了一半的图片
+            let partial = partial_path(&target_path);
+            if let Err(e) = fs::copy(&original, &partial) {
+                let _ = fs::remove_file(&partial);
+                return Err(e).context("复制响应图片到会话目录失败");
+            }
+            commit_partial(&partial, &target_path)
         })
         .await
-        .context("保存响应图片任务失败")?
+        .context("保存响应图片任务失败")??;
+        self.record_artifacts(session_id, &[&target_path]);
+        Ok(target_path)
     }
 
-    /// 获取会话列表
-    pub fn get_user_sessions(&self, user_id: &str) -> Vec<SessionInfo> {
-        let mut sessions = Vec::new();
+    /// 获取会话列表，按最后修改时间从新到旧排序
+    pub async fn get_user_sessions(&self, user_id: &str) -> Vec<SessionInfo> {
+        match self.user_sessions(user_id).await {
+            Ok(sessions) => sessions,
+            Err(e) => {
+                error!("查询会话列表失败: {:?}", e);
+                Vec::new()
+            }
+        }
+    }
 
-        // 遍历会话目录
-        if let Ok(entries) = fs::read_dir(&self.sessions_dir) {
-            for entry in entries.filter_map(Result::ok) {
-                if let Ok(file_type) = entry.file_type() {
-                    if file_type.is_dir() {
-                        // 检查这个会话是否属于该用户
-                        let session_path = entry.path();
-                        let user_id_file = session_path.join("user_id.txt");
-
-                        if let Ok(stored_user_id) = fs::read_to_string(&user_id_file) {
-                            if stored_user_id.trim() == user_id {
-                                // 提取会话信息
-                                if let Some(session_id) = session_path.file_name() {
-                                    if let Some(session_id) = session_id.to_str() {
-                                        if let Some(info) = self.get_session_info(session_id) {
-                                            sessions.push(info);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+    // 查询用户的会话列表，查询失败时返回错误
+    async fn user_sessions(&self, user_id: &str) -> Result<Vec<SessionInfo>> {
+        let store = Arc::clone(&self.store);
+        let user_id = user_id.to_string();
+        tokio::task::spawn_blocking(move || store.user_sessions(&user_id))
+            .await
+            .context("查询会话列表任务失败")?
+    }
+
+    /// 搜索用户的历史问答
+    pub async fn search(
+        &self,
+        query: SearchQuery,
+        offset: usize,
+        limit: usize,
+    ) -> Result<SearchPage> {
+        let store = Arc::clone(&self.store);
+        tokio::task::spawn_blocking(move || store.search(&query, offset, limit))
+            .await
+            .context("搜索历史问答任务失败")?
+    }
+
+    /// 根据完整会话ID或ID前缀查找用户自己的会话
+    ///
+    /// 只在该用户的会话中查找，其他用户的会话视为不存在。
+    pub async fn find_user_session(&self, user_id: &str, id: &str) -> Result<String> {
+        let sessions = self.get_user_sessions(user_id).await;
+        match_session_id(&sessions, id)
+    }
+
+    /// 为用户自己的会话创建网页分享链接，返回链接令牌
+    ///
+    /// 已经分享过的会话沿用原有的令牌，撤销后再次分享会生成新的令牌。
+    pub fn share_session(&self, user_id: &str, session_id: &str) -> Result<String> {
+        if self.read_meta(session_id)?.user_id != user_id {
+            return Err(anyhow!("会话不存在: {}", session_id));
+        }
+        // 分享不视为会话活动，不刷新更新时间
+        let meta = self.modify_meta(session_id, |meta| {
+            if meta.share.is_none() {
+                meta.share = Some(ShareLink {
+                    token: share::generate_token(),
+                    created_at: Utc::now(),
+                });
             }
+        })?;
+        let token = meta
+            .share
+            .map(|share| share.token)
+            .ok_or_else(|| anyhow!("创建分享链接失败"))?;
+        info!("用户 {} 创建了会话 {} 的分享链接", user_id, session_id);
+        Ok(token)
+    }
+
+    /// 撤销用户自己会话的网页分享链接，返回撤销前是否存在链接
+    pub fn revoke_share(&self, user_id: &str, session_id: &str) -> Result<bool> {
+        let meta = self.read_meta(session_id)?;
+        if meta.user_id != user_id {
+            return Err(anyhow!("会话不存在: {}", session_id));
+        }
+        if meta.share.is_none() {
+            return Ok(false);
         }
+        self.modify_meta(session_id, |meta| meta.share = None)?;
+        info!("用户 {} 撤销了会话 {} 的分享链接", user_id, session_id);
+        Ok(true)
+    }
 
-        // 按最后修改时间排序
-        sessions.sort_by(|a, b| b.last_modified.cmp(&a.last_modified));
+    /// 根据分享令牌读取会话的元数据与对话记录，令牌无效或已撤销时返回 `None`
+    ///
+    /// 会进行阻塞的文件读取（文件系统索引还会遍历全部会话），需在 `spawn_blocking` 中调用。
+    pub fn load_shared_session(&self, token: &str) -> Result<Option<(SessionMeta, Vec<Turn>)>> {
+        if !share::is_valid_token(token) {
+            return Ok(None);
+        }
+        let Some(session_id) = self.store.find_shared(token)? else {
+            return Ok(None);
+        };
+        // 以会话目录中的元数据为准，索引未及时更新时也不会访问到已撤销的会话
+        let meta = self.read_meta(&session_id)?;
+        if meta.share.as_ref().is_none_or(|share| share.token != token) {
+            return Ok(None);
+        }
+        let turns = transcript::load_turns(&self.get_session_dir(&session_id))?;
+        Ok(Some((meta, turns)))
+    }
 
-        sessions
+    /// 读取会话中最近一次提供的用户图片链接
+    pub async fn load_user_images(&self, session_id: &str) -> Result<Vec<String>> {
+        let turns = self.load_transcript(session_id).await?;
+        Ok(turns
+            .into_iter()
+            .rev()
+            .find(|turn| turn.role == TurnRole::User && !turn.attachments.is_empty())
+            .map(|turn| turn.attachments)
+            .unwrap_or_default())
     }
 
-    /// 获取会话信息
-    fn get_session_info(&self, session_id: &str) -> Option<SessionInfo> {
+    /// 删除用户自己的会话，包括会话目录、索引记录与渲染缓存中的副本
+    ///
+    /// 会话不属于该用户时视为不存在。
+    pub async fn delete_session(&self, user_id: &str, session_id: &str) -> Result<()> {
+        let manager = self.clone();
+        let owner = user_id.to_string();
+        let id = session_id.to_string();
+        tokio::task::spawn_blocking(move || {
+            if manager.read_meta(&id)?.user_id != owner {
+                return Err(anyhow!("会话不存在: {}", id));
+            }
+            manager.remove_session(&id)
+        })
+        .await
+        .context("删除会话任务失败")??;
+        info!("用户 {} 删除了会话 {}", user_id, session_id);
+        Ok(())
+    }
+
+    // 删除会话目录、索引记录与渲染缓存中的副本
+    fn remove_session(&self, session_id: &str) -> Result<()> {
+        Uuid::parse_str(session_id).context("无效的会话ID")?;
         let session_dir = self.get_session_dir(session_id);
+        // 渲染缓存中保存着会话图片与PDF的副本，删除会话后不应再保留
+        let files: Vec<PathBuf> = fs::read_dir(&session_dir)
+            .map(|entries| entries.filter_map(Result::ok).map(|e| e.path()).collect())
+            .unwrap_or_default();
+        match remove_cached_copies(&self.render_cache_dir, &files) {
+            Ok(0) => {}
+            Ok(count) => info!("已删除会话 {} 的 {} 个渲染缓存", session_id, count),
+            Err(e) => warn!("删除会话 {} 的渲染缓存失败: {:?}", session_id, e),
+        }
+        // 先删除索引，目录删除失败时会话也不会再出现在列表中
+        self.store.remove(session_id)?;
+        fs::remove_dir_all(session_dir).context("删除会话目录失败")
+    }
 
-        // 读取用户输入
-        let input_path = session_dir.join("input.txt");
-        let input_preview = match fs::read_to_string(&input_path) {
-            Ok(content) => {
-                // 提取前30个字符作为预览，使用字符截断避免多字节边界错误
-                let preview: String = content.chars().take(30).collect();
-                if content.chars().count() > 30 {
-                    format!("{}...", preview)
-                } else {
-                    preview
-                }
+    // 删除会话的问题、回答与图片链接，保留元数据
+    fn remove_session_text(&self, session_id: &str) -> Result<()> {
+        let session_dir = self.get_session_dir(session_id);
+        for file in [
+            "input.txt",
+            "response.md",
+            "image_urls.txt",
+            transcript::TRANSCRIPT_FILE,
+        ] {
+            let path = session_dir.join(file);
+            if path.exists() {
+                fs::remove_file(&path).with_context(|| format!("删除 {} 失败", file))?;
             }
-            Err(_) => String::from("无法读取输入"),
-        };
+        }
+        self.modify_meta(session_id, |meta| {
+            meta.artifacts
+                .retain(|a| session_dir.join(&a.file).exists());
+        })?;
+        self.store.index_content(session_id, "", "")
+    }
 
-        // 获取目录的最后修改时间
-        let modified = fs::metadata(&session_dir)
-            .ok()
-            .and_then(|m| m.modified().ok())
-            .unwrap_or(SystemTime::now());
-
-        let datetime = DateTime::<Utc>::from(modified);
-
-        let images = fs::read_dir(&session_dir)
-            .map(|entries| {
-                entries
-                    .filter_map(Result::ok)
-                    .filter(|e| {
-                        if let Ok(file_type) = e.file_type() {
-                            if file_type.is_file() {
-                                if let Some(name) = e.path().file_name() {
-                                    if let Some(name_str) = name.to_str() {
-                                        return name_str.ends_with(".png")
-                                            || name_str.ends_with(".jpg")
-                                            || name_str.ends_with(".jpeg");
-                                    }
-                                }
-                            }
-                        }
-                        false
-                    })
-                    .count()
-            })
-            .unwrap_or(0);
+    /// 删除用户的全部会话，返回删除的会话数
+    pub async fn delete_user_sessions(&self, user_id: &str) -> Result<usize> {
+        let sessions = self.user_sessions(user_id).await?;
+        let mut deleted = 0;
+        for session in &sessions {
+            self.delete_session(user_id, &session.id).await?;
+            deleted += 1;
+        }
+        Ok(deleted)
+    }
 
-        Some(SessionInfo {
-            id: session_id.to_string(),
-            input_preview,
-            last_modified: datetime,
-            images: images as u32,
+    /// 将用户的全部会话打包为zip，返回zip数据与会话数
+    ///
+    /// `include_images` 为 false 时只导出文字内容与元数据。
+    pub async fn export_user_sessions(
+        &self,
+        user_id: &str,
+        include_images: bool,
+    ) -> Result<(Vec<u8>, usize)> {
+        let sessions: Vec<(String, PathBuf)> = self
+            .user_sessions(user_id)
+            .await?
+            .into_iter()
+            .map(|s| {
+                let session_dir = self.get_session_dir(&s.id);
+                (s.id, session_dir)
+            })
+            .collect();
+        let user_id = user_id.to_string();
+        tokio::task::spawn_blocking(move || {
+            export::export_sessions(&user_id, &sessions, include_images)
         })
+        .await
+        .context("导出会话任务失败")?
+    }
+
+    /// 会话中最近一次回答仍然存在的图片
+    ///
+    /// 属于该回答的图片为对话记录中随回答保存的文件，以及回答之后重新渲染的图片，
+    /// 多轮会话中之前回答的图片不会返回。
+    /// 同一页有原图与压缩版本时只返回先记录的原图，发送前按当前的上传限制重新压缩；
+    /// 结果按文件名中的页码排序。图片已被清理时返回空列表。
+    pub fn answer_images(&self, session_id: &str) -> Result<Vec<PathBuf>> {
+        let meta = self.read_meta(session_id)?;
+        let session_dir = self.get_session_dir(session_id);
+        let turns = transcript::load_turns(&session_dir)?;
+        let last_answer = turns
+            .iter()
+            .rev()
+            .find(|turn| turn.role == TurnRole::Assistant);
+        let mut images: Vec<PathBuf> = Vec::new();
+        for artifact in meta.artifacts.iter().filter(|a| {
+            a.kind == ArtifactKind::Image
+                && last_answer.is_none_or(|turn| {
+                    turn.attachments.contains(&a.file) || a.created_at > turn.timestamp
+                })
+        }) {
+            let path = session_dir.join(&artifact.file);
+            if path.is_file() && !images.iter().any(|p| p.file_stem() == path.file_stem()) {
+                images.push(path);
+            }
+        }
+        images.sort_by_key(|path| page_index(path));
+        Ok(images)
     }
 
-    /// 清理会话中的图片
+    /// 清理会话中的图片与导出的PDF
     pub fn cleanup_session_images(&self, session_id: &str) -> Result<usize> {
         let session_dir = self.get_session_dir(session_id);
 
@@ -238,7 +637,11 @@ impl SessionManager {
                         let path = entry.path();
                         if let Some(ext) = path.extension() {
                             if let Some(ext_str) = ext.to_str() {
-                                if (ext_str == "png" || ext_str == "jpg" || ext_str == "jpeg")
+                                if (ext_str == "png"
+                                    || ext_str == "jpg"
+                                    || ext_str == "jpeg"
+                                    || ext_str == "webp"
+                                    || ext_str == "pdf")
                                     && fs::remove_file(&path).is_ok()
                                 {
                                     removed += 1;
@@ -250,6 +653,17 @@ impl SessionManager {
             }
         }
 
+        // 从元数据中移除已删除的文件，不视为会话活动
+        if Uuid::parse_str(session_id).is_ok() {
+            let result = self.modify_meta(session_id, |meta| {
+                meta.artifacts
+                    .retain(|a| session_dir.join(&a.file).exists());
+            });
+            if let Err(e) = result {
+                warn!("更新会话 {} 元数据失败: {:?}", session_id, e);
+            }
+        }
+
         // 创建清理标记
         let cleaned_marker = session_dir.join(".cleaned");
         let timestamp = Utc::now().to_rfc3339();
@@ -258,78 +672,104 @@ impl SessionManager {
         Ok(removed)
     }
 
-    /// 定期清理旧会话
-    pub async fn periodic_cleanup(&self, expiry_days: u64) {
-        let seconds_in_day = 24 * 60 * 60;
-        let expiry_seconds = expiry_days * seconds_in_day;
+    /// 按保留策略定期清理会话，演练模式下只记录报告，返回报告摘要
+    pub async fn periodic_cleanup(&self, config: &RetentionConfig) -> Result<String> {
+        let report = self.apply_retention(config, config.dry_run).await?;
+        let summary = report.summary().replace('\n', "；");
+        Ok(if report.dry_run {
+            format!("演练模式，未删除文件；{}", summary)
+        } else {
+            summary
+        })
+    }
 
-        if let Ok(entries) = fs::read_dir(&self.sessions_dir) {
-            let now = SystemTime::now();
-            let mut cleaned_sessions = 0;
-            let mut cleaned_files = 0;
+    /// 按保留策略清理会话，`dry_run` 为 true 时只列出将要执行的操作
+    pub async fn apply_retention(
+        &self,
+        config: &RetentionConfig,
+        dry_run: bool,
+    ) -> Result<RetentionReport> {
+        let manager = self.clone();
+        let config = config.clone();
+        tokio::task::spawn_blocking(move || manager.run_retention(&config, dry_run))
+            .await
+            .context("执行保留策略任务失败")?
+    }
 
-            for entry in entries.filter_map(Result::ok) {
-                let session_path = entry.path();
-                // 如果缺少user_id.txt，视为脱离管理的会话，清理其图片
-                let user_file = session_path.join("user_id.txt");
-                if !user_file.exists() {
-                    if let Some(session_id) = session_path.file_name().and_then(|n| n.to_str()) {
-                        match self.cleanup_session_images(session_id) {
-                            Ok(count) => {
-                                if count > 0 {
-                                    cleaned_sessions += 1;
-                                    cleaned_files += count;
-                                    info!(
-                                        "已清理脱离管理会话 {} 中的 {} 个图片文件",
-                                        session_id, count
-                                    );
-                                }
-                            }
-                            Err(e) => error!("清理脱离管理会话 {} 图片时出错: {}", session_id, e),
-                        }
+    fn run_retention(&self, config: &RetentionConfig, dry_run: bool) -> Result<RetentionReport> {
+        let sessions = self.session_usage()?;
+        let mut report = RetentionReport {
+            dry_run,
+            scanned: sessions.len(),
+            total_bytes: sessions.iter().map(|s| s.total_bytes()).sum(),
+            ..Default::default()
+        };
+        for action in retention::plan(&sessions, config, Utc::now()) {
+            if !dry_run {
+                let result = match action.action {
+                    RetentionAction::RemoveImages => {
+                        self.cleanup_session_images(&action.session_id).map(|_| ())
                     }
+                    RetentionAction::RemoveMarkdown => self.remove_session_text(&action.session_id),
+                    _ => self.remove_session(&action.session_id),
+                };
+                if let Err(e) = result {
+                    warn!(
+                        "会话 {} {}失败: {:?}",
+                        action.session_id,
+                        action.action.label(),
+                        e
+                    );
+                    report.failed += 1;
                     continue;
                 }
-
-                if let Ok(metadata) = entry.metadata() {
-                    if metadata.is_dir() {
-                        if let Ok(modified) = metadata.modified() {
-                            if let Ok(duration) = now.duration_since(modified) {
-                                if duration.as_secs() > expiry_seconds {
-                                    // 会话已过期，清理图片
-                                    if let Some(filename) =
-                                        session_path.file_name().and_then(|n| n.to_str())
-                                    {
-                                        match self.cleanup_session_images(filename) {
-                                            Ok(count) => {
-                                                if count > 0 {
-                                                    cleaned_sessions += 1;
-                                                    cleaned_files += count;
-                                                    info!(
-                                                        "已清理会话 {} 中的 {} 个图片文件",
-                                                        filename, count
-                                                    );
-                                                }
-                                            }
-                                            Err(e) => {
-                                                error!("清理会话 {} 图片时出错: {}", filename, e)
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
             }
+            report.actions.push(action);
+        }
+        Ok(report)
+    }
 
-            if cleaned_sessions > 0 {
-                info!(
-                    "定期清理完成: 已处理 {} 个会话，删除 {} 个图片文件",
-                    cleaned_sessions, cleaned_files
-                );
+    // 统计每个会话各类文件的大小
+    fn session_usage(&self) -> Result<Vec<SessionUsage>> {
+        let mut sessions = Vec::new();
+        for entry in fs::read_dir(&self.sessions_dir)?.filter_map(Result::ok) {
+            if !entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                continue;
             }
+            let session_id = entry.file_name().to_string_lossy().to_string();
+            if Uuid::parse_str(&session_id).is_err() {
+                continue;
+            }
+            let meta = match self.read_meta(&session_id) {
+                Ok(meta) => meta,
+                Err(e) => {
+                    warn!("读取会话 {} 元数据失败，跳过清理: {:?}", session_id, e);
+                    continue;
+                }
+            };
+            let mut usage = SessionUsage {
+                session_id,
+                user_id: meta.user_id,
+                guild_id: meta.guild_id,
+                last_active: meta.updated_at,
+                image_bytes: 0,
+                markdown_bytes: 0,
+                other_bytes: 0,
+            };
+            for file in fs::read_dir(entry.path())?.filter_map(Result::ok) {
+                let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+                match ArtifactKind::from_file_name(&file.file_name().to_string_lossy()) {
+                    ArtifactKind::Image | ArtifactKind::Pdf => usage.image_bytes += size,
+                    ArtifactKind::Input
+                    | ArtifactKind::Response
+                    | ArtifactKind::ImageUrls
+                    | ArtifactKind::Transcript => usage.markdown_bytes += size,
+                    ArtifactKind::Other => usage.other_bytes += size,
+                }
+            }
+            sessions.push(usage);
         }
+        Ok(sessions)
     }
 }
 
@@ -339,4 +779,281 @@ pub struct SessionInfo {
     pub input_preview: String,
     pub last_modified: DateTime<Utc>,
     pub images: u32,
+    /// 对话中的消息数
+    pub turns: u32,
+}
+
+// 分页图片文件名末尾的页码，如 response_{id}_2.png 为第2页，单页图片视为第0页
+fn page_index(path: &Path) -> usize {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.rsplit_once('_'))
+        .and_then(|(_, page)| page.parse().ok())
+        .unwrap_or(0)
+}
+
+// 在会话列表中按完整ID或唯一前缀查找会话
+fn match_session_id(sessions: &[SessionInfo], id: &str) -> Result<String> {
+    let id = id.trim().to_lowercase();
+    if id.is_empty() {
+        return Err(anyhow!("请输入会话ID"));
+    }
+    if let Some(session) = sessions.iter().find(|s| s.id == id) {
+        return Ok(session.id.clone());
+    }
+    let matched: Vec<&SessionInfo> = sessions.iter().filter(|s| s.id.starts_with(&id)).collect();
+    match matched.as_slice() {
+        [] => Err(anyhow!("找不到会话: {}", id)),
+        [session] => Ok(session.id.clone()),
+        _ => Err(anyhow!("有多个会话以 {} 开头，请输入更长的会话ID", id)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_sessions_are_imported_into_sqlite_index() {
+        let temp = tempfile::tempdir().unwrap();
+        let manager = SessionManager::for_test(temp.path(), SessionStoreKind::Sqlite);
+
+        // 旧版会话只有零散文件
+        let legacy_id = Uuid::new_v4().to_string();
+        let legacy_dir = manager.get_session_dir(&legacy_id);
+        fs::create_dir_all(&legacy_dir).unwrap();
+        fs::write(legacy_dir.join("user_id.txt"), "42").unwrap();
+        fs::write(legacy_dir.join("input.txt"), "旧问题").unwrap();
+        fs::write(legacy_dir.join("response.md"), "旧回答").unwrap();
+
+        assert!(manager.store.needs_import().unwrap());
+        assert_eq!(manager.import_sessions().unwrap(), 1);
+        assert!(!manager.store.needs_import().unwrap());
+        assert!(legacy_dir.join("session.json").exists());
+
+        let sessions = manager.store.user_sessions("42").unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].input_preview, "旧问题");
+
+        // 新会话创建与更新时同步写入索引
+        let session_id = manager.create_session("42").unwrap();
+        let meta = manager
+            .update_meta(&session_id, |meta| meta.status = SessionStatus::Cancelled)
+            .unwrap();
+        assert_eq!(manager.read_meta(&session_id).unwrap(), meta);
+        let sessions = manager.store.user_sessions("42").unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].id, session_id);
+        assert!(manager.store.user_sessions("7").unwrap().is_empty());
+        assert!(manager.read_meta("../etc").is_err());
+
+        // 文件系统后端得到相同的结果
+        let fs_store = FsSessionStore::new(manager.sessions_dir.clone());
+        let fs_ids: Vec<String> = fs_store
+            .user_sessions("42")
+            .unwrap()
+            .into_iter()
+            .map(|s| s.id)
+            .collect();
+        let ids: Vec<String> = sessions.into_iter().map(|s| s.id).collect();
+        assert_eq!(fs_ids, ids);
+    }
+
+    #[test]
+    fn finds_sessions_by_unique_prefix() {
+        let session = |id: &str| SessionInfo {
+            id: id.to_string(),
+            input_preview: String::new(),
+            last_modified: Utc::now(),
+            images: 0,
+            turns: 0,
+        };
+        let sessions = vec![session("abcd1234-0001"), session("abcd5678-0002")];
+        assert_eq!(
+            match_session_id(&sessions, "ABCD1").unwrap(),
+            "abcd1234-0001"
+        );
+        assert_eq!(
+            match_session_id(&sessions, " abcd5678-0002 ").unwrap(),
+            "abcd5678-0002"
+        );
+        assert!(match_session_id(&sessions, "abcd").is_err());
+        assert!(match_session_id(&sessions, "ffff").is_err());
+        assert!(match_session_id(&sessions, "").is_err());
+    }
+
+    #[test]
+    fn answer_images_skip_removed_and_compressed_copies() {
+        let temp = tempfile::tempdir().unwrap();
+        let manager = SessionManager::for_test(temp.path(), SessionStoreKind::Fs);
+        let session_id = manager.create_session("42").unwrap();
+        let session_dir = manager.get_session_dir(&session_id);
+        let files = ["response_a_1.png", "response_a_2.png", "response_a_1.jpg"];
+        for file in files {
+            fs::write(session_dir.join(file), b"image").unwrap();
+        }
+        manager.record_artifacts(&session_id, &files);
+
+        let names = |images: Vec<PathBuf>| -> Vec<String> {
+            images
+                .iter()
+                .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+                .collect()
+        };
+        let images = manager.answer_images(&session_id).unwrap();
+        assert_eq!(names(images), ["response_a_1.png", "response_a_2.png"]);
+
+        // 原图被删除后使用压缩版本
+        fs::remove_file(session_dir.join("response_a_1.png")).unwrap();
+        let images = manager.answer_images(&session_id).unwrap();
+        assert_eq!(names(images), ["response_a_1.jpg", "response_a_2.png"]);
+    }
+
+    #[test]
+    fn answer_images_only_include_latest_answer() {
+        let temp = tempfile::tempdir().unwrap();
+        let manager = SessionManager::for_test(temp.path(), SessionStoreKind::Fs);
+        let session_id = manager.create_session("42").unwrap();
+        let session_dir = manager.get_session_dir(&session_id);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        // 每轮回答先渲染图片，再与图片文件名一起追加到对话记录
+        let answer = |question: &str, urls: &[String], files: &[&str]| {
+            for file in files {
+                fs::write(session_dir.join(file), b"image").unwrap();
+            }
+            manager.record_artifacts(&session_id, files);
+            let attachments = files.iter().map(|f| f.to_string()).collect();
+            runtime.block_on(async {
+                manager
+                    .append_turn(&session_id, Turn::user(question, urls, None))
+                    .await
+                    .unwrap();
+                manager
+                    .append_turn(
+                        &session_id,
+                        Turn::assistant("回答").with_attachments(attachments),
+                    )
+                    .await
+                    .unwrap();
+            });
+        };
+        let urls = vec!["https://example.com/a.png".to_string()];
+        answer("问题", &urls, &["response_a_1.png", "response_a_2.png"]);
+        answer("追问", &[], &["response_b.png"]);
+
+        let images = manager.answer_images(&session_id).unwrap();
+        assert_eq!(images, [session_dir.join("response_b.png")]);
+        // 没有提供图片的追问保留之前的图片链接
+        assert_eq!(
+            runtime
+                .block_on(manager.load_user_images(&session_id))
+                .unwrap(),
+            urls
+        );
+        assert_eq!(
+            fs::read_to_string(session_dir.join("image_urls.txt")).unwrap(),
+            urls[0]
+        );
+
+        // 图片被清理后重新渲染的图片属于最近一次回答
+        fs::remove_file(session_dir.join("response_b.png")).unwrap();
+        assert!(manager.answer_images(&session_id).unwrap().is_empty());
+        fs::write(session_dir.join("response_c.png"), b"image").unwrap();
+        manager.record_artifacts(&session_id, &["response_c.png"]);
+        let images = manager.answer_images(&session_id).unwrap();
+        assert_eq!(images, [session_dir.join("response_c.png")]);
+    }
+
+    #[test]
+    fn deleting_session_removes_render_cache_copies() {
+        let temp = tempfile::tempdir().unwrap();
+        let manager = SessionManager::for_test(temp.path(), SessionStoreKind::Sqlite);
+        let session_id = manager.create_session("42").unwrap();
+        let session_dir = manager.get_session_dir(&session_id);
+        fs::write(session_dir.join("response_1.png"), b"rendered page").unwrap();
+        let cache_dir = temp.path().join("cache");
+        fs::create_dir_all(&cache_dir).unwrap();
+        fs::write(cache_dir.join("aaaa.png"), b"rendered page").unwrap();
+        fs::write(cache_dir.join("bbbb.png"), b"other session").unwrap();
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            assert!(manager.delete_session("7", &session_id).await.is_err());
+            assert!(session_dir.exists());
+            manager.delete_session("42", &session_id).await.unwrap();
+        });
+        assert!(!session_dir.exists());
+        assert!(manager.store.user_sessions("42").unwrap().is_empty());
+        assert!(!cache_dir.join("aaaa.png").exists());
+        assert!(cache_dir.join("bbbb.png").exists());
+    }
+
+    #[test]
+    fn share_links_can_be_found_and_revoked() {
+        let temp = tempfile::tempdir().unwrap();
+        let manager = SessionManager::for_test(temp.path(), SessionStoreKind::Sqlite);
+        let fs_manager = SessionManager::for_test(temp.path(), SessionStoreKind::Fs);
+        let session_id = manager.create_session("42").unwrap();
+        let updated_at = manager.read_meta(&session_id).unwrap().updated_at;
+        assert!(manager.share_session("7", &session_id).is_err());
+        let token = manager.share_session("42", &session_id).unwrap();
+        assert_eq!(manager.share_session("42", &session_id).unwrap(), token);
+        assert_eq!(
+            manager.read_meta(&session_id).unwrap().updated_at,
+            updated_at
+        );
+
+        for manager in [&manager, &fs_manager] {
+            let (meta, _) = manager.load_shared_session(&token).unwrap().unwrap();
+            assert_eq!(meta.user_id, "42");
+            assert!(manager.load_shared_session("bad").unwrap().is_none());
+        }
+
+        assert!(manager.revoke_share("7", &session_id).is_err());
+        assert!(manager.revoke_share("42", &session_id).unwrap());
+        assert!(!manager.revoke_share("42", &session_id).unwrap());
+        for manager in [&manager, &fs_manager] {
+            assert!(manager.load_shared_session(&token).unwrap().is_none());
+        }
+
+        // 撤销后再次分享生成新的令牌
+        assert_ne!(manager.share_session("42", &session_id).unwrap(), token);
+    }
+
+    #[test]
+    fn retention_dry_run_keeps_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let manager = SessionManager::for_test(temp.path(), SessionStoreKind::Fs);
+        let session_id = manager.create_session("42").unwrap();
+        let session_dir = manager.get_session_dir(&session_id);
+        let files = ["input.txt", "response.md", "response_1.png"];
+        for file in files {
+            fs::write(session_dir.join(file), "内容").unwrap();
+        }
+        manager.record_artifacts(&session_id, &files);
+        manager
+            .modify_meta(&session_id, |meta| {
+                meta.updated_at -= chrono::Duration::days(10)
+            })
+            .unwrap();
+
+        let config = RetentionConfig {
+            default: RetentionPolicy {
+                image_days: Some(2),
+                markdown_days: Some(5),
+                session_days: Some(0),
+            },
+            ..Default::default()
+        };
+        let report = manager.run_retention(&config, true).unwrap();
+        assert_eq!(report.scanned, 1);
+        assert_eq!(report.actions.len(), 2);
+        assert!(files.iter().all(|f| session_dir.join(f).exists()));
+
+        let report = manager.run_retention(&config, false).unwrap();
+        assert_eq!(report.failed, 0);
+        assert!(files.iter().all(|f| !session_dir.join(f).exists()));
+        assert!(manager.read_meta(&session_id).unwrap().artifacts.is_empty());
+    }
 }
@@ -0,0 +1,431 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::util::format_bytes;
+
+// 最近仍在活动的会话不参与配额淘汰，避免删除正在生成的回答
+const EVICTION_GRACE_MINUTES: i64 = 10;
+
+/// 会话内容的保存期限（天），按会话最后活动时间计算
+///
+/// 作为服务器覆盖设置时，`None` 表示沿用默认策略，`Some(0)` 表示永久保存。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    /// 回答图片与导出的PDF
+    #[serde(default)]
+    pub image_days: Option<u64>,
+    /// 问题、回答Markdown与图片链接
+    #[serde(default)]
+    pub markdown_days: Option<u64>,
+    /// 整个会话目录与索引记录
+    #[serde(default)]
+    pub session_days: Option<u64>,
+}
+
+impl RetentionPolicy {
+    /// 用服务器设置覆盖对应的字段
+    pub fn merged(&self, guild: &RetentionPolicy) -> RetentionPolicy {
+        RetentionPolicy {
+            image_days: guild.image_days.or(self.image_days),
+            markdown_days: guild.markdown_days.or(self.markdown_days),
+            session_days: guild.session_days.or(self.session_days),
+        }
+    }
+}
+
+// data/retention.json 的格式
+#[derive(Debug, Default, Deserialize)]
+struct RetentionFile {
+    #[serde(default)]
+    guilds: HashMap<String, RetentionPolicy>,
+}
+
+/// 保留策略设置
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RetentionConfig {
+    /// 默认保存期限
+    pub default: RetentionPolicy,
+    /// 按服务器ID覆盖的保存期限，从 data/retention.json 读取
+    pub guilds: HashMap<String, RetentionPolicy>,
+    /// 每个用户的会话占用上限（MB），0 表示不限制
+    pub user_quota_mb: u64,
+    /// 全部会话的占用上限（MB），0 表示不限制
+    pub global_quota_mb: u64,
+    /// 只生成报告，不删除任何文件
+    pub dry_run: bool,
+}
+
+impl RetentionConfig {
+    /// 会话实际使用的保存期限，私信中的会话使用默认策略
+    pub fn policy_for(&self, guild_id: Option<&str>) -> RetentionPolicy {
+        match guild_id.and_then(|id| self.guilds.get(id)) {
+            Some(guild) => self.default.merged(guild),
+            None => self.default,
+        }
+    }
+
+    /// 读取服务器覆盖设置，文件不存在时返回空表
+    pub fn load_guild_overrides(path: &Path) -> Result<HashMap<String, RetentionPolicy>> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => return Err(e).context("读取保留策略文件失败"),
+        };
+        let file: RetentionFile = serde_json::from_str(&content)
+            .with_context(|| format!("解析保留策略文件失败: {}", path.display()))?;
+        Ok(file.guilds)
+    }
+}
+
+/// 参与策略计算的会话占用情况
+#[derive(Debug, Clone)]
+pub struct SessionUsage {
+    pub session_id: String,
+    pub user_id: String,
+    pub guild_id: Option<String>,
+    pub last_active: DateTime<Utc>,
+    /// 回答图片与PDF的大小
+    pub image_bytes: u64,
+    /// 问题、回答与图片链接的大小
+    pub markdown_bytes: u64,
+    /// 元数据等其他文件的大小
+    pub other_bytes: u64,
+}
+
+impl SessionUsage {
+    pub fn total_bytes(&self) -> u64 {
+        self.image_bytes + self.markdown_bytes + self.other_bytes
+    }
+}
+
+/// 保留策略对会话执行的操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetentionAction {
+    /// 图片超过保存期限，删除图片与PDF
+    RemoveImages,
+    /// 文字内容超过保存期限，删除问题与回答
+    RemoveMarkdown,
+    /// 会话超过保存期限，删除整个会话
+    RemoveSession,
+    /// 用户超出配额，删除最旧的会话
+    EvictUserQuota,
+    /// 全部会话超出配额，删除最旧的会话
+    EvictGlobalQuota,
+}
+
+impl RetentionAction {
+    /// 是否删除整个会话
+    pub fn removes_session(&self) -> bool {
+        matches!(
+            self,
+            RetentionAction::RemoveSession
+                | RetentionAction::EvictUserQuota
+                | RetentionAction::EvictGlobalQuota
+        )
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RetentionAction::RemoveImages => "删除图片",
+            RetentionAction::RemoveMarkdown => "删除文字内容",
+            RetentionAction::RemoveSession => "删除过期会话",
+            RetentionAction::EvictUserQuota => "用户配额淘汰",
+            RetentionAction::EvictGlobalQuota => "全局配额淘汰",
+        }
+    }
+}
+
+/// 一条计划执行的操作
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedAction {
+    pub session_id: String,
+    pub user_id: String,
+    pub action: RetentionAction,
+    /// 预计释放的空间
+    pub bytes: u64,
+}
+
+/// 一次策略执行的结果
+#[derive(Debug, Clone, Default)]
+pub struct RetentionReport {
+    /// 为 true 时只列出操作，没有删除文件
+    pub dry_run: bool,
+    /// 检查的会话数
+    pub scanned: usize,
+    /// 执行前的总占用
+    pub total_bytes: u64,
+    pub actions: Vec<PlannedAction>,
+    /// 执行失败的操作数
+    pub failed: usize,
+}
+
+impl RetentionReport {
+    /// 释放（或预计释放）的空间
+    pub fn reclaimed_bytes(&self) -> u64 {
+        self.actions.iter().map(|a| a.bytes).sum()
+    }
+
+    pub fn count(&self, action: RetentionAction) -> usize {
+        self.actions.iter().filter(|a| a.action == action).count()
+    }
+
+    /// 按操作类型汇总的多行文字
+    pub fn summary(&self) -> String {
+        let mut lines = vec![format!(
+            "检查 {} 个会话，共 {}",
+            self.scanned,
+            format_bytes(self.total_bytes)
+        )];
+        for action in [
+            RetentionAction::RemoveImages,
+            RetentionAction::RemoveMarkdown,
+            RetentionAction::RemoveSession,
+            RetentionAction::EvictUserQuota,
+            RetentionAction::EvictGlobalQuota,
+        ] {
+            let count = self.count(action);
+            if count > 0 {
+                lines.push(format!("{}: {} 个会话", action.label(), count));
+            }
+        }
+        lines.push(format!(
+            "{}: {}",
+            if self.dry_run {
+                "预计释放"
+            } else {
+                "已释放"
+            },
+            format_bytes(self.reclaimed_bytes())
+        ));
+        if self.failed > 0 {
+            lines.push(format!("失败: {} 项", self.failed));
+        }
+        lines.join("\n")
+    }
+}
+
+/// 根据保存期限与配额计算需要执行的操作
+///
+/// 先按期限处理每个会话，再按最后活动时间从旧到新淘汰超出用户配额与全局配额的会话。
+pub fn plan(
+    sessions: &[SessionUsage],
+    config: &RetentionConfig,
+    now: DateTime<Utc>,
+) -> Vec<PlannedAction> {
+    let expired = |days: Option<u64>, last_active: DateTime<Utc>| {
+        days.filter(|d| *d > 0)
+            .is_some_and(|d| now - last_active > Duration::days(d as i64))
+    };
+    let mut actions = Vec::new();
+    // 期限处理后仍保留的会话及其剩余大小
+    let mut remaining: Vec<(&SessionUsage, u64)> = Vec::new();
+
+    for session in sessions {
+        let policy = config.policy_for(session.guild_id.as_deref());
+        let mut push = |action, bytes| {
+            actions.push(PlannedAction {
+                session_id: session.session_id.clone(),
+                user_id: session.user_id.clone(),
+                action,
+                bytes,
+            })
+        };
+        if expired(policy.session_days, session.last_active) {
+            push(RetentionAction::RemoveSession, session.total_bytes());
+            continue;
+        }
+        let mut size = session.total_bytes();
+        if session.markdown_bytes > 0 && expired(policy.markdown_days, session.last_active) {
+            push(RetentionAction::RemoveMarkdown, session.markdown_bytes);
+            size -= session.markdown_bytes;
+        }
+        if session.image_bytes > 0 && expired(policy.image_days, session.last_active) {
+            push(RetentionAction::RemoveImages, session.image_bytes);
+            size -= session.image_bytes;
+        }
+        remaining.push((session, size));
+    }
+
+    // 最旧的会话排在前面，优先淘汰
+    remaining.sort_by_key(|(session, _)| session.last_active);
+    let grace = now - Duration::minutes(EVICTION_GRACE_MINUTES);
+    let mut evicted: Vec<bool> = vec![false; remaining.len()];
+
+    if config.user_quota_mb > 0 {
+        let quota = config.user_quota_mb * 1024 * 1024;
+        let mut usage: HashMap<&str, u64> = HashMap::new();
+        for (session, size) in &remaining {
+            *usage.entry(session.user_id.as_str()).or_default() += size;
+        }
+        for (i, (session, size)) in remaining.iter().enumerate() {
+            // 无法确定用户的旧版会话只按期限与全局配额处理
+            if session.user_id.is_empty() {
+                continue;
+            }
+            let used = usage.get_mut(session.user_id.as_str()).unwrap();
+            if *used > quota && session.last_active < grace {
+                *used -= size;
+                evicted[i] = true;
+                actions.push(evict(session, *size, RetentionAction::EvictUserQuota));
+            }
+        }
+    }
+
+    if config.global_quota_mb > 0 {
+        let quota = config.global_quota_mb * 1024 * 1024;
+        let mut used: u64 = remaining
+            .iter()
+            .zip(&evicted)
+            .filter(|(_, evicted)| !**evicted)
+            .map(|((_, size), _)| size)
+            .sum();
+        for (i, (session, size)) in remaining.iter().enumerate() {
+            if used <= quota {
+                break;
+            }
+            if !evicted[i] && session.last_active < grace {
+                used -= size;
+                evicted[i] = true;
+                actions.push(evict(session, *size, RetentionAction::EvictGlobalQuota));
+            }
+        }
+    }
+
+    // 被淘汰的会话只保留淘汰操作，释放空间按整个会话计算
+    let evicted_ids: Vec<&str> = remaining
+        .iter()
+        .zip(&evicted)
+        .filter(|(_, evicted)| **evicted)
+        .map(|((session, _), _)| session.session_id.as_str())
+        .collect();
+    actions.retain(|a| a.action.removes_session() || !evicted_ids.contains(&a.session_id.as_str()));
+    for action in actions.iter_mut().filter(|a| a.action.removes_session()) {
+        if let Some(session) = sessions.iter().find(|s| s.session_id == action.session_id) {
+            action.bytes = session.total_bytes();
+        }
+    }
+    actions
+}
+
+fn evict(session: &SessionUsage, bytes: u64, action: RetentionAction) -> PlannedAction {
+    PlannedAction {
+        session_id: session.session_id.clone(),
+        user_id: session.user_id.clone(),
+        action,
+        bytes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1024 * 1024;
+
+    fn usage(id: &str, user_id: &str, days_ago: i64, image_mb: u64) -> SessionUsage {
+        SessionUsage {
+            session_id: id.to_string(),
+            user_id: user_id.to_string(),
+            guild_id: None,
+            last_active: Utc::now() - Duration::days(days_ago),
+            image_bytes: image_mb * MB,
+            markdown_bytes: 1024,
+            other_bytes: 512,
+        }
+    }
+
+    fn actions_of(actions: &[PlannedAction]) -> Vec<(&str, RetentionAction)> {
+        actions
+            .iter()
+            .map(|a| (a.session_id.as_str(), a.action))
+            .collect()
+    }
+
+    #[test]
+    fn applies_ttls_with_guild_overrides() {
+        let mut config = RetentionConfig {
+            default: RetentionPolicy {
+                image_days: Some(2),
+                markdown_days: Some(30),
+                session_days: Some(90),
+            },
+            ..Default::default()
+        };
+        // 该服务器的图片永久保存，会话保存更短
+        config.guilds.insert(
+            "g1".into(),
+            RetentionPolicy {
+                image_days: Some(0),
+                session_days: Some(7),
+                ..Default::default()
+            },
+        );
+        let mut guild_session = usage("g", "1", 5, 1);
+        guild_session.guild_id = Some("g1".into());
+        let mut old_guild_session = usage("h", "1", 10, 1);
+        old_guild_session.guild_id = Some("g1".into());
+        let sessions = vec![
+            usage("new", "1", 1, 1),
+            usage("a", "1", 3, 1),
+            usage("b", "1", 40, 1),
+            usage("c", "1", 100, 1),
+            guild_session,
+            old_guild_session,
+        ];
+
+        let actions = plan(&sessions, &config, Utc::now());
+        assert_eq!(
+            actions_of(&actions),
+            vec![
+                ("a", RetentionAction::RemoveImages),
+                ("b", RetentionAction::RemoveMarkdown),
+                ("b", RetentionAction::RemoveImages),
+                ("c", RetentionAction::RemoveSession),
+                ("h", RetentionAction::RemoveSession),
+            ]
+        );
+        assert_eq!(actions[0].bytes, MB);
+        assert_eq!(actions[3].bytes, MB + 1536);
+    }
+
+    #[test]
+    fn evicts_oldest_sessions_over_quota() {
+        let config = RetentionConfig {
+            user_quota_mb: 4,
+            global_quota_mb: 4,
+            ..Default::default()
+        };
+        let mut recent = usage("recent", "2", 0, 1);
+        recent.last_active = Utc::now();
+        let sessions = vec![
+            usage("u1-new", "1", 1, 2),
+            usage("u1-old", "1", 3, 2),
+            usage("u2-old", "2", 4, 1),
+            usage("u2-new", "2", 2, 1),
+            recent,
+        ];
+
+        let actions = plan(&sessions, &config, Utc::now());
+        // 用户1超出配额，先删除其最旧的会话；之后按全局配额从最旧的会话开始淘汰
+        assert_eq!(
+            actions_of(&actions),
+            vec![
+                ("u1-old", RetentionAction::EvictUserQuota),
+                ("u2-old", RetentionAction::EvictGlobalQuota),
+                ("u2-new", RetentionAction::EvictGlobalQuota),
+            ]
+        );
+
+        let report = RetentionReport {
+            dry_run: true,
+            actions,
+            ..Default::default()
+        };
+        assert_eq!(report.count(RetentionAction::EvictGlobalQuota), 2);
+        assert!(report.summary().contains("预计释放"));
+    }
+}
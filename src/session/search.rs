@@ -0,0 +1,241 @@
+use chrono::{DateTime, Utc};
+
+use super::SessionMeta;
+
+// 摘要中关键词前后保留的字符数
+const SNIPPET_CONTEXT: usize = 40;
+
+/// 历史问答搜索条件
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchQuery {
+    pub user_id: String,
+    /// 关键词，全部出现在问题或回答中才算匹配（不区分大小写）
+    pub keywords: Vec<String>,
+    /// 会话创建时间下限（包含）
+    pub from: Option<DateTime<Utc>>,
+    /// 会话创建时间上限（不包含）
+    pub to: Option<DateTime<Utc>>,
+    /// 只搜索指定应用的会话
+    pub app_name: Option<String>,
+}
+
+impl SearchQuery {
+    /// 按空白拆分关键词，忽略重复的关键词
+    pub fn parse_keywords(text: &str) -> Vec<String> {
+        let mut keywords: Vec<String> = Vec::new();
+        for word in text.split_whitespace() {
+            if !keywords
+                .iter()
+                .any(|k| k.to_lowercase() == word.to_lowercase())
+            {
+                keywords.push(word.to_string());
+            }
+        }
+        keywords
+    }
+
+    /// 会话元数据是否满足用户、时间与应用条件
+    pub fn accepts(&self, meta: &SessionMeta) -> bool {
+        meta.user_id == self.user_id
+            && self.from.is_none_or(|from| meta.created_at >= from)
+            && self.to.is_none_or(|to| meta.created_at < to)
+            && self
+                .app_name
+                .as_ref()
+                .is_none_or(|app| meta.app_name.as_ref() == Some(app))
+    }
+
+    /// 问题与回答是否包含全部关键词
+    pub fn matches_content(&self, input: &str, response: &str) -> bool {
+        let input = input.to_lowercase();
+        let response = response.to_lowercase();
+        self.keywords.iter().all(|keyword| {
+            let keyword = keyword.to_lowercase();
+            input.contains(&keyword) || response.contains(&keyword)
+        })
+    }
+}
+
+/// 一条搜索结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub session_id: String,
+    pub created_at: DateTime<Utc>,
+    pub app_name: Option<String>,
+    /// 问题摘要，关键词已加粗
+    pub input_snippet: String,
+    /// 回答摘要，回答中没有关键词时为空
+    pub response_snippet: Option<String>,
+}
+
+impl SearchHit {
+    pub fn new(
+        session_id: String,
+        meta: &SessionMeta,
+        input: &str,
+        response: &str,
+        keywords: &[String],
+    ) -> Self {
+        let input_snippet = highlight_snippet(input, keywords)
+            .unwrap_or_else(|| escape_markdown(&truncate_chars(&flatten(input), 60)));
+        SearchHit {
+            session_id,
+            created_at: meta.created_at,
+            app_name: meta.app_name.clone(),
+            input_snippet,
+            response_snippet: highlight_snippet(response, keywords),
+        }
+    }
+}
+
+/// 一页搜索结果
+#[derive(Debug, Clone, Default)]
+pub struct SearchPage {
+    pub hits: Vec<SearchHit>,
+    /// 匹配的会话总数
+    pub total: usize,
+}
+
+/// 截取第一个关键词附近的文字，并将其中的关键词加粗
+///
+/// 文字中的Markdown符号会被转义，换行替换为空格。没有找到关键词时返回 `None`。
+pub fn highlight_snippet(text: &str, keywords: &[String]) -> Option<String> {
+    let chars: Vec<char> = flatten(text).chars().collect();
+    let lower: Vec<char> = chars.iter().map(|c| fold(*c)).collect();
+    let keywords: Vec<Vec<char>> = keywords
+        .iter()
+        .map(|k| k.chars().map(fold).collect::<Vec<char>>())
+        .filter(|k| !k.is_empty())
+        .collect();
+
+    // 找出所有关键词出现的位置
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for keyword in &keywords {
+        let mut i = 0;
+        while i + keyword.len() <= lower.len() {
+            if lower[i..i + keyword.len()] == keyword[..] {
+                ranges.push((i, i + keyword.len()));
+                i += keyword.len();
+            } else {
+                i += 1;
+            }
+        }
+    }
+    let first = ranges.iter().map(|r| r.0).min()?;
+    let start = first.saturating_sub(SNIPPET_CONTEXT);
+    let end = (first + SNIPPET_CONTEXT * 2).min(chars.len());
+
+    // 合并重叠的位置，只保留窗口内的部分
+    ranges.sort();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (s, e) in ranges {
+        let (s, e) = (s.max(start), e.min(end));
+        if s >= e {
+            continue;
+        }
+        match merged.last_mut() {
+            Some(last) if s <= last.1 => last.1 = last.1.max(e),
+            _ => merged.push((s, e)),
+        }
+    }
+
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push('…');
+    }
+    let mut pos = start;
+    for (s, e) in merged {
+        snippet.push_str(&escape_markdown(&chars[pos..s].iter().collect::<String>()));
+        snippet.push_str("**");
+        snippet.push_str(&escape_markdown(&chars[s..e].iter().collect::<String>()));
+        snippet.push_str("**");
+        pos = e;
+    }
+    snippet.push_str(&escape_markdown(
+        &chars[pos..end].iter().collect::<String>(),
+    ));
+    if end < chars.len() {
+        snippet.push('…');
+    }
+    Some(snippet)
+}
+
+// 不区分大小写比较时使用的字符，只转换单个字符以保持位置对应
+fn fold(c: char) -> char {
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(l), None) => l,
+        _ => c,
+    }
+}
+
+// 合并空白，使摘要保持在一行
+fn flatten(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn truncate_chars(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        text.to_string()
+    } else {
+        format!("{}…", text.chars().take(max_chars).collect::<String>())
+    }
+}
+
+// 转义Discord Markdown符号，避免摘要中的符号与高亮冲突
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '*' | '_' | '`' | '~' | '|' | '>' | '#' | '[' | ']' | '\\'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keywords(text: &str) -> Vec<String> {
+        SearchQuery::parse_keywords(text)
+    }
+
+    #[test]
+    fn highlights_keywords_case_insensitively() {
+        let snippet =
+            highlight_snippet("如何在 Rust 中使用\nrust-analyzer？", &keywords("RUST")).unwrap();
+        assert_eq!(snippet, "如何在 **Rust** 中使用 **rust**-analyzer？");
+        assert!(highlight_snippet("没有匹配", &keywords("rust")).is_none());
+    }
+
+    #[test]
+    fn snippet_is_windowed_and_escaped() {
+        let text = format!("{}关键词 *重点*{}", "前".repeat(100), "后".repeat(100));
+        let snippet = highlight_snippet(&text, &keywords("关键词")).unwrap();
+        assert!(snippet.starts_with('…') && snippet.ends_with('…'));
+        assert!(snippet.contains("**关键词** \\*重点\\*"));
+        assert!(snippet.chars().count() < 140);
+    }
+
+    #[test]
+    fn query_requires_every_keyword() {
+        let query = SearchQuery {
+            user_id: "42".into(),
+            keywords: keywords("docker 端口 Docker"),
+            ..Default::default()
+        };
+        assert_eq!(query.keywords.len(), 2);
+        assert!(query.matches_content("Docker 怎么映射", "使用 -p 映射端口"));
+        assert!(!query.matches_content("Docker 怎么映射", "使用 -p 参数"));
+
+        let mut meta = SessionMeta::new("42");
+        assert!(query.accepts(&meta));
+        meta.user_id = "7".into();
+        assert!(!query.accepts(&meta));
+    }
+}
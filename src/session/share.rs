@@ -0,0 +1,38 @@
+use base64::Engine;
+use rand::RngCore;
+
+// 分享令牌的随机字节数，编码后为32个字符
+const TOKEN_BYTES: usize = 24;
+
+/// 生成网页分享链接使用的随机令牌（URL安全的base64）
+pub(super) fn generate_token() -> String {
+    let mut bytes = [0u8; TOKEN_BYTES];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// 检查令牌格式，格式不对的请求无需查询索引
+pub(super) fn is_valid_token(token: &str) -> bool {
+    token.len() == (TOKEN_BYTES * 4).div_ceil(3)
+        && token
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generates_unique_url_safe_tokens() {
+        let token = generate_token();
+        assert_eq!(token.len(), 32);
+        assert!(is_valid_token(&token));
+        assert_ne!(token, generate_token());
+
+        assert!(!is_valid_token(""));
+        assert!(!is_valid_token(&token[1..]));
+        assert!(!is_valid_token(&format!("{}/", &token[1..])));
+        assert!(!is_valid_token("../../../../../../../etc/passwd"));
+    }
+}
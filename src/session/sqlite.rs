@@ -0,0 +1,435 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::Mutex;
+
+use super::search::{SearchHit, SearchPage, SearchQuery};
+use super::store::{SessionRecord, SessionStore};
+use super::{SessionInfo, SessionMeta};
+
+// 数据库结构版本，保存在 PRAGMA user_version 中
+// 2: 增加问答全文索引
+// 3: 增加对话消息数 turns
+// 4: 增加网页分享令牌 share_token
+const SCHEMA_VERSION: i64 = 4;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS sessions (
+    id            TEXT PRIMARY KEY,
+    user_id       TEXT NOT NULL,
+    guild_id      TEXT,
+    channel_id    TEXT,
+    app_name      TEXT,
+    status        TEXT NOT NULL,
+    created_at    INTEGER NOT NULL,
+    updated_at    INTEGER NOT NULL,
+    input_preview TEXT NOT NULL,
+    images        INTEGER NOT NULL,
+    turns         INTEGER NOT NULL DEFAULT 0,
+    share_token   TEXT,
+    meta          TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_sessions_user ON sessions (user_id, updated_at DESC);
+CREATE INDEX IF NOT EXISTS idx_sessions_guild ON sessions (guild_id, updated_at DESC);
+CREATE INDEX IF NOT EXISTS idx_sessions_updated ON sessions (updated_at);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_sessions_share ON sessions (share_token)
+    WHERE share_token IS NOT NULL;
+CREATE TABLE IF NOT EXISTS store_state (
+    key   TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+-- rowid 与 sessions 表一致；trigram 分词支持中文子串匹配，LIKE 查询也能使用该索引
+CREATE VIRTUAL TABLE IF NOT EXISTS session_fts USING fts5(
+    input, response, tokenize = 'trigram'
+);
+";
+
+/// 内嵌SQLite会话索引
+///
+/// 每个会话一行，按用户、服务器与更新时间建立索引，查询历史时无需遍历会话目录。
+/// 完整的元数据以JSON保存在 `meta` 列中。
+#[derive(Debug)]
+pub struct SqliteSessionStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteSessionStore {
+    /// 打开或创建数据库，并创建缺少的表与索引
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("打开会话数据库失败: {}", path.display()))?;
+        // WAL 模式下读取不会被写入阻塞
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .context("设置数据库日志模式失败")?;
+        let version: i64 = conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .context("读取数据库版本失败")?;
+        if version > 0 && version < 3 {
+            conn.execute(
+                "ALTER TABLE sessions ADD COLUMN turns INTEGER NOT NULL DEFAULT 0",
+                [],
+            )
+            .context("升级会话表失败")?;
+        }
+        if version > 0 && version < 4 {
+            conn.execute("ALTER TABLE sessions ADD COLUMN share_token TEXT", [])
+                .context("升级会话表失败")?;
+        }
+        conn.execute_batch(SCHEMA).context("创建会话表失败")?;
+        if version > 0 && version < SCHEMA_VERSION {
+            // 旧版本数据库缺少新增的数据，重新导入会话目录
+            conn.execute("DELETE FROM store_state WHERE key = 'imported_at'", [])?;
+        }
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)
+            .context("写入数据库版本失败")?;
+        Ok(SqliteSessionStore {
+            conn: Mutex::new(conn),
+        })
+    }
+}
+
+impl SessionStore for SqliteSessionStore {
+    fn name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    fn upsert(&self, record: &SessionRecord<'_>) -> Result<()> {
+        let meta = record.meta;
+        let meta_json = serde_json::to_string(meta).context("序列化会话元数据失败")?;
+        let status = serde_json::to_value(meta.status)?
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO sessions (id, user_id, guild_id, channel_id, app_name, status,
+                                   created_at, updated_at, input_preview, images, turns,
+                                   share_token, meta)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+             ON CONFLICT (id) DO UPDATE SET
+                 user_id = excluded.user_id,
+                 guild_id = excluded.guild_id,
+                 channel_id = excluded.channel_id,
+                 app_name = excluded.app_name,
+                 status = excluded.status,
+                 created_at = excluded.created_at,
+                 updated_at = excluded.updated_at,
+                 input_preview = excluded.input_preview,
+                 images = excluded.images,
+                 turns = excluded.turns,
+                 share_token = excluded.share_token,
+                 meta = excluded.meta",
+            params![
+                record.id,
+                meta.user_id,
+                meta.guild_id,
+                meta.channel_id,
+                meta.app_name,
+                status,
+                meta.created_at.timestamp_millis(),
+                meta.updated_at.timestamp_millis(),
+                record.input_preview,
+                meta.image_count(),
+                meta.turns,
+                meta.share.as_ref().map(|s| s.token.as_str()),
+                meta_json,
+            ],
+        )
+        .context("写入会话索引失败")?;
+        Ok(())
+    }
+
+    fn user_sessions(&self, user_id: &str) -> Result<Vec<SessionInfo>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, input_preview, updated_at, images, turns FROM sessions
+             WHERE user_id = ?1 ORDER BY updated_at DESC",
+        )?;
+        let sessions = stmt
+            .query_map(params![user_id], |row| {
+                let updated_at: i64 = row.get(2)?;
+                Ok(SessionInfo {
+                    id: row.get(0)?,
+                    input_preview: row.get(1)?,
+                    last_modified: DateTime::<Utc>::from_timestamp_millis(updated_at)
+                        .unwrap_or_default(),
+                    images: row.get(3)?,
+                    turns: row.get(4)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("查询会话索引失败")?;
+        Ok(sessions)
+    }
+
+    fn index_content(&self, session_id: &str, input: &str, response: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let changed = conn
+            .execute(
+                "INSERT OR REPLACE INTO session_fts (rowid, input, response)
+                 SELECT rowid, ?2, ?3 FROM sessions WHERE id = ?1",
+                params![session_id, input, response],
+            )
+            .context("写入全文索引失败")?;
+        if changed == 0 {
+            return Err(anyhow!("会话 {} 不在索引中", session_id));
+        }
+        Ok(())
+    }
+
+    fn search(&self, query: &SearchQuery, offset: usize, limit: usize) -> Result<SearchPage> {
+        let mut filter = String::from("s.user_id = ?");
+        let mut values = vec![Value::Text(query.user_id.clone())];
+        if let Some(from) = query.from {
+            filter.push_str(" AND s.created_at >= ?");
+            values.push(Value::Integer(from.timestamp_millis()));
+        }
+        if let Some(to) = query.to {
+            filter.push_str(" AND s.created_at < ?");
+            values.push(Value::Integer(to.timestamp_millis()));
+        }
+        if let Some(app_name) = &query.app_name {
+            filter.push_str(" AND s.app_name = ?");
+            values.push(Value::Text(app_name.clone()));
+        }
+        for keyword in &query.keywords {
+            filter.push_str(r" AND (f.input LIKE ? ESCAPE '\' OR f.response LIKE ? ESCAPE '\')");
+            let pattern = format!("%{}%", escape_like(keyword));
+            values.push(Value::Text(pattern.clone()));
+            values.push(Value::Text(pattern));
+        }
+
+        let conn = self.conn.lock().unwrap();
+        let total: i64 = conn
+            .query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM sessions s JOIN session_fts f ON f.rowid = s.rowid
+                     WHERE {}",
+                    filter
+                ),
+                params_from_iter(values.iter()),
+                |row| row.get(0),
+            )
+            .context("统计搜索结果失败")?;
+
+        values.push(Value::Integer(limit as i64));
+        values.push(Value::Integer(offset as i64));
+        let mut stmt = conn.prepare(&format!(
+            "SELECT s.id, s.meta, f.input, f.response
+             FROM sessions s JOIN session_fts f ON f.rowid = s.rowid
+             WHERE {} ORDER BY s.created_at DESC LIMIT ? OFFSET ?",
+            filter
+        ))?;
+        let rows = stmt
+            .query_map(params_from_iter(values.iter()), |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("搜索历史问答失败")?;
+
+        let mut hits = Vec::with_capacity(rows.len());
+        for (id, meta_json, input, response) in rows {
+            let meta: SessionMeta =
+                serde_json::from_str(&meta_json).context("解析会话元数据失败")?;
+            hits.push(SearchHit::new(
+                id,
+                &meta,
+                &input,
+                &response,
+                &query.keywords,
+            ));
+        }
+        Ok(SearchPage {
+            hits,
+            total: total as usize,
+        })
+    }
+
+    fn remove(&self, session_id: &str) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM session_fts WHERE rowid IN (SELECT rowid FROM sessions WHERE id = ?1)",
+            params![session_id],
+        )
+        .context("删除全文索引失败")?;
+        tx.execute("DELETE FROM sessions WHERE id = ?1", params![session_id])
+            .context("删除会话索引失败")?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn find_shared(&self, token: &str) -> Result<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT id FROM sessions WHERE share_token = ?1",
+            params![token],
+            |row| row.get(0),
+        )
+        .optional()
+        .context("查询分享链接失败")
+    }
+
+    fn needs_import(&self) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let imported: Option<String> = conn
+            .query_row(
+                "SELECT value FROM store_state WHERE key = 'imported_at'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(imported.is_none())
+    }
+
+    fn finish_import(&self) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO store_state (key, value) VALUES ('imported_at', ?1)",
+            params![Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+}
+
+// 转义 LIKE 模式中的通配符
+fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SessionMeta;
+
+    #[test]
+    fn upsert_and_query_by_user() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let store = SqliteSessionStore::open(&dir.join("sessions.db")).unwrap();
+        assert!(store.needs_import().unwrap());
+        store.finish_import().unwrap();
+        assert!(!store.needs_import().unwrap());
+
+        let mut older = SessionMeta::new("42");
+        older.updated_at -= chrono::Duration::hours(1);
+        let mut newer = SessionMeta::new("42");
+        newer.add_artifact("response_1.png");
+        let other = SessionMeta::new("7");
+        for (id, meta) in [("a", &older), ("b", &newer), ("c", &other)] {
+            store
+                .upsert(&SessionRecord {
+                    id,
+                    meta,
+                    input_preview: "问题",
+                })
+                .unwrap();
+        }
+        // 再次写入同一会话只更新记录
+        store
+            .upsert(&SessionRecord {
+                id: "b",
+                meta: &newer,
+                input_preview: "新问题",
+            })
+            .unwrap();
+
+        let sessions = store.user_sessions("42").unwrap();
+        let ids: Vec<&str> = sessions.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["b", "a"]);
+        assert_eq!(sessions[0].input_preview, "新问题");
+        assert_eq!(sessions[0].images, 1);
+        assert_eq!(
+            sessions[0].last_modified.timestamp_millis(),
+            newer.updated_at.timestamp_millis()
+        );
+    }
+
+    #[test]
+    fn searches_content_with_filters() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let store = SqliteSessionStore::open(&dir.join("sessions.db")).unwrap();
+
+        let mut old = SessionMeta::new("42");
+        old.created_at -= chrono::Duration::days(10);
+        old.app_name = Some("运维助手".into());
+        let new = SessionMeta::new("42");
+        let other = SessionMeta::new("7");
+        let entries = [
+            ("a", &old, "Docker 端口映射", "使用 -p 8080:80 映射端口"),
+            ("b", &new, "docker compose 怎么写", "示例 100% 可用"),
+            ("c", &other, "Docker 端口", "别人的问题"),
+        ];
+        for (id, meta, input, response) in entries {
+            let record = SessionRecord {
+                id,
+                meta,
+                input_preview: input,
+            };
+            store.upsert(&record).unwrap();
+            store.index_content(id, input, response).unwrap();
+        }
+        assert!(store.index_content("missing", "", "").is_err());
+
+        let query = |keywords: &str| SearchQuery {
+            user_id: "42".into(),
+            keywords: SearchQuery::parse_keywords(keywords),
+            ..Default::default()
+        };
+
+        let page = store.search(&query("docker"), 0, 10).unwrap();
+        assert_eq!(page.total, 2);
+        let ids: Vec<&str> = page.hits.iter().map(|h| h.session_id.as_str()).collect();
+        assert_eq!(ids, vec!["b", "a"]);
+        assert_eq!(page.hits[0].input_snippet, "**docker** compose 怎么写");
+
+        // 两个字的中文关键词短于 trigram，也能匹配
+        let page = store.search(&query("端口"), 0, 10).unwrap();
+        assert_eq!(page.total, 1);
+        assert!(page.hits[0]
+            .response_snippet
+            .as_ref()
+            .unwrap()
+            .contains("**端口**"));
+
+        // 通配符按字面匹配
+        assert_eq!(store.search(&query("100%"), 0, 10).unwrap().total, 1);
+        assert_eq!(store.search(&query("1_0"), 0, 10).unwrap().total, 0);
+
+        let mut filtered = query("docker");
+        filtered.from = Some(Utc::now() - chrono::Duration::days(1));
+        assert_eq!(store.search(&filtered, 0, 10).unwrap().total, 1);
+        filtered.from = None;
+        filtered.app_name = Some("运维助手".into());
+        let page = store.search(&filtered, 0, 10).unwrap();
+        assert_eq!(page.hits[0].session_id, "a");
+
+        // 分页时总数不变
+        let page = store.search(&query("docker"), 1, 1).unwrap();
+        assert_eq!((page.total, page.hits.len()), (2, 1));
+        assert_eq!(page.hits[0].session_id, "a");
+
+        // 删除后不再出现在列表与搜索结果中
+        store.remove("a").unwrap();
+        store.remove("missing").unwrap();
+        assert_eq!(store.search(&query("docker"), 0, 10).unwrap().total, 1);
+        let sessions = store.user_sessions("42").unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, "b");
+    }
+}
@@ -0,0 +1,241 @@
+use anyhow::{anyhow, Result};
+use std::fmt::Debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::warn;
+use uuid::Uuid;
+
+use super::search::{SearchHit, SearchPage, SearchQuery};
+use super::transcript::{self, TurnRole};
+use super::{SessionInfo, SessionMeta};
+
+/// 会话索引后端
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionStoreKind {
+    /// 每次查询时遍历会话目录
+    Fs,
+    /// 内嵌SQLite数据库，按用户、服务器与时间建立索引
+    Sqlite,
+}
+
+impl FromStr for SessionStoreKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "fs" | "file" => Ok(SessionStoreKind::Fs),
+            "sqlite" => Ok(SessionStoreKind::Sqlite),
+            other => Err(anyhow!("不支持的会话存储后端: {}", other)),
+        }
+    }
+}
+
+/// 写入索引的一条会话记录
+#[derive(Debug, Clone, Copy)]
+pub struct SessionRecord<'a> {
+    pub id: &'a str,
+    pub meta: &'a SessionMeta,
+    pub input_preview: &'a str,
+}
+
+impl SessionRecord<'_> {
+    pub fn to_info(self) -> SessionInfo {
+        SessionInfo {
+            id: self.id.to_string(),
+            input_preview: self.input_preview.to_string(),
+            last_modified: self.meta.updated_at,
+            images: self.meta.image_count(),
+            turns: self.meta.turns,
+        }
+    }
+}
+
+/// 会话索引的存储后端
+///
+/// 会话文件始终保存在会话目录中，后端只负责按用户等条件查找会话。
+/// 所有方法都是阻塞调用，异步环境中需在 `spawn_blocking` 中使用。
+pub trait SessionStore: Debug + Send + Sync {
+    /// 后端名称，用于日志与统计
+    fn name(&self) -> &'static str;
+
+    /// 会话元数据变化后写入或更新记录
+    fn upsert(&self, record: &SessionRecord<'_>) -> Result<()>;
+
+    /// 列出用户的会话，按更新时间从新到旧排序
+    fn user_sessions(&self, user_id: &str) -> Result<Vec<SessionInfo>>;
+
+    /// 更新会话问题与回答的全文索引
+    fn index_content(&self, session_id: &str, input: &str, response: &str) -> Result<()>;
+
+    /// 搜索用户的历史问答，按创建时间从新到旧排序，返回指定范围内的结果
+    fn search(&self, query: &SearchQuery, offset: usize, limit: usize) -> Result<SearchPage>;
+
+    /// 删除会话的索引记录，会话不在索引中时忽略
+    fn remove(&self, session_id: &str) -> Result<()>;
+
+    /// 根据网页分享令牌查找会话ID，令牌不存在或已撤销时返回 `None`
+    fn find_shared(&self, token: &str) -> Result<Option<String>>;
+
+    /// 是否需要从会话目录导入已有会话
+    fn needs_import(&self) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// 导入已有会话完成后调用
+    fn finish_import(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// 读取会话中全部的问题与回答，多轮对话按顺序以空行连接，读取失败时视为空
+pub(super) fn read_content(session_dir: &Path) -> (String, String) {
+    let turns = transcript::load_turns(session_dir).unwrap_or_else(|e| {
+        warn!("读取对话记录失败 {}: {:?}", session_dir.display(), e);
+        Vec::new()
+    });
+    let join = |role: TurnRole| {
+        turns
+            .iter()
+            .filter(|turn| turn.role == role)
+            .map(|turn| turn.content.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    };
+    (join(TurnRole::User), join(TurnRole::Assistant))
+}
+
+/// 读取会话第一个问题的前30个字符作为预览
+pub(super) fn read_input_preview(session_dir: &Path) -> String {
+    let first_question = transcript::load_turns(session_dir)
+        .ok()
+        .and_then(|turns| turns.into_iter().find(|turn| turn.role == TurnRole::User));
+    match first_question {
+        Some(turn) => {
+            let content = turn.content;
+            // 使用字符截断避免多字节边界错误
+            let preview: String = content.chars().take(30).collect();
+            if content.chars().count() > 30 {
+                format!("{}...", preview)
+            } else {
+                preview
+            }
+        }
+        None => String::from("无法读取输入"),
+    }
+}
+
+/// 基于会话目录的后端，即原有的存储方式
+///
+/// 不维护额外的索引，每次查询遍历所有会话目录并读取 session.json。
+#[derive(Debug)]
+pub struct FsSessionStore {
+    sessions_dir: PathBuf,
+}
+
+impl FsSessionStore {
+    pub fn new(sessions_dir: PathBuf) -> Self {
+        FsSessionStore { sessions_dir }
+    }
+
+    // 遍历所有会话目录，返回会话ID、目录与元数据
+    fn sessions(&self) -> Result<Vec<(String, PathBuf, SessionMeta)>> {
+        let mut sessions = Vec::new();
+        for entry in fs::read_dir(&self.sessions_dir)?.filter_map(Result::ok) {
+            if !entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            if Uuid::parse_str(&name).is_err() {
+                continue;
+            }
+            let session_dir = entry.path();
+            // 旧版会话在此只推断元数据，由 SessionManager 读取时再迁移
+            let meta = match SessionMeta::load(&session_dir) {
+                Ok(Some(meta)) => meta,
+                Ok(None) => match SessionMeta::from_legacy_dir(&session_dir) {
+                    Ok(meta) => meta,
+                    Err(e) => {
+                        warn!("读取旧版会话 {} 失败: {:?}", name, e);
+                        continue;
+                    }
+                },
+                Err(e) => {
+                    warn!("读取会话 {} 元数据失败: {:?}", name, e);
+                    continue;
+                }
+            };
+            sessions.push((name, session_dir, meta));
+        }
+        Ok(sessions)
+    }
+}
+
+impl SessionStore for FsSessionStore {
+    fn name(&self) -> &'static str {
+        "fs"
+    }
+
+    fn upsert(&self, _record: &SessionRecord<'_>) -> Result<()> {
+        // 元数据已写入会话目录，无需额外处理
+        Ok(())
+    }
+
+    fn user_sessions(&self, user_id: &str) -> Result<Vec<SessionInfo>> {
+        let mut sessions = Vec::new();
+        for (name, session_dir, meta) in self.sessions()? {
+            if meta.user_id == user_id {
+                let record = SessionRecord {
+                    id: &name,
+                    meta: &meta,
+                    input_preview: &read_input_preview(&session_dir),
+                };
+                sessions.push(record.to_info());
+            }
+        }
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.last_modified));
+        Ok(sessions)
+    }
+
+    fn index_content(&self, _session_id: &str, _input: &str, _response: &str) -> Result<()> {
+        // 搜索时直接读取会话文件
+        Ok(())
+    }
+
+    fn search(&self, query: &SearchQuery, offset: usize, limit: usize) -> Result<SearchPage> {
+        let mut matched = Vec::new();
+        for (name, session_dir, meta) in self.sessions()? {
+            if !query.accepts(&meta) {
+                continue;
+            }
+            let (input, response) = read_content(&session_dir);
+            if query.matches_content(&input, &response) {
+                matched.push((name, meta, input, response));
+            }
+        }
+        matched.sort_by_key(|(_, meta, _, _)| std::cmp::Reverse(meta.created_at));
+        let total = matched.len();
+        let hits = matched
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(|(name, meta, input, response)| {
+                SearchHit::new(name, &meta, &input, &response, &query.keywords)
+            })
+            .collect();
+        Ok(SearchPage { hits, total })
+    }
+
+    fn remove(&self, _session_id: &str) -> Result<()> {
+        // 删除会话目录即可
+        Ok(())
+    }
+
+    fn find_shared(&self, token: &str) -> Result<Option<String>> {
+        Ok(self
+            .sessions()?
+            .into_iter()
+            .find(|(_, _, meta)| meta.share.as_ref().is_some_and(|s| s.token == token))
+            .map(|(name, _, _)| name))
+    }
+}
@@ -0,0 +1,255 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use tracing::warn;
+
+/// 对话记录文件名，每行一条JSON
+pub const TRANSCRIPT_FILE: &str = "transcript.jsonl";
+
+// 作为上下文显示时，每条之前的消息最多保留的字符数
+const CONTEXT_TURN_MAX_CHARS: usize = 300;
+
+/// 对话中一条消息的发送方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TurnRole {
+    User,
+    Assistant,
+}
+
+/// 对话中的一条消息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Turn {
+    pub role: TurnRole,
+    pub content: String,
+    /// 用户消息为提供的图片链接，回答为渲染出的图片文件名
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<String>,
+    pub timestamp: DateTime<Utc>,
+    /// 触发本条消息的Discord消息或交互ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+}
+
+impl Turn {
+    pub fn user(content: &str, attachments: &[String], message_id: Option<String>) -> Self {
+        Turn {
+            role: TurnRole::User,
+            content: content.to_string(),
+            attachments: attachments.to_vec(),
+            timestamp: Utc::now(),
+            message_id,
+        }
+    }
+
+    pub fn assistant(content: &str) -> Self {
+        Turn {
+            role: TurnRole::Assistant,
+            content: content.to_string(),
+            attachments: Vec::new(),
+            timestamp: Utc::now(),
+            message_id: None,
+        }
+    }
+
+    /// 设置消息的附件：用户消息为图片链接，回答为渲染出的图片文件名
+    pub fn with_attachments(mut self, attachments: Vec<String>) -> Self {
+        self.attachments = attachments;
+        self
+    }
+}
+
+/// 在对话记录末尾追加一条消息，返回追加后的消息数
+///
+/// 没有对话记录的旧版会话先根据 input.txt 与 response.md 补写之前的问答，避免追问时丢失。
+pub(super) fn append_turn(dir: &Path, turn: &Turn) -> Result<usize> {
+    let path = dir.join(TRANSCRIPT_FILE);
+    let mut data = String::new();
+    let mut turns = match fs::read(&path) {
+        Ok(existing) => {
+            // 上次写入中途退出时最后一行不完整，从新的一行开始写
+            if !existing.is_empty() && !existing.ends_with(b"\n") {
+                data.push('\n');
+            }
+            Vec::new()
+        }
+        Err(_) => legacy_turns(dir),
+    };
+    turns.push(turn.clone());
+
+    for turn in &turns {
+        data.push_str(&serde_json::to_string(turn).context("序列化对话记录失败")?);
+        data.push('\n');
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .context("打开对话记录失败")?;
+    file.write_all(data.as_bytes())
+        .context("写入对话记录失败")?;
+    Ok(load_turns(dir)?.len())
+}
+
+/// 读取会话的全部消息，没有对话记录的旧版会话由 input.txt 与 response.md 推断
+///
+/// 写入中途退出可能留下不完整的最后一行，无法解析的行会被跳过。
+pub(super) fn load_turns(dir: &Path) -> Result<Vec<Turn>> {
+    let data = match fs::read_to_string(dir.join(TRANSCRIPT_FILE)) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(legacy_turns(dir)),
+        Err(e) => return Err(e).context("读取对话记录失败"),
+    };
+    let mut turns = Vec::new();
+    for (i, line) in data.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(line) {
+            Ok(turn) => turns.push(turn),
+            Err(e) => warn!("跳过无法解析的对话记录 {}:{}: {}", dir.display(), i + 1, e),
+        }
+    }
+    Ok(turns)
+}
+
+// 旧版会话只保存了一问一答，时间取文件的修改时间
+fn legacy_turns(dir: &Path) -> Vec<Turn> {
+    let mut turns = Vec::new();
+    let read = |file: &str| -> Option<(String, DateTime<Utc>)> {
+        let path = dir.join(file);
+        let content = fs::read_to_string(&path).ok()?;
+        let modified = fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .map(DateTime::<Utc>::from)
+            .unwrap_or_else(|_| Utc::now());
+        Some((content, modified))
+    };
+    if let Some((content, timestamp)) = read("input.txt") {
+        let attachments = read("image_urls.txt")
+            .map(|(urls, _)| {
+                urls.lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        turns.push(Turn {
+            role: TurnRole::User,
+            content,
+            attachments,
+            timestamp,
+            message_id: None,
+        });
+    }
+    if let Some((content, timestamp)) = read("response.md") {
+        turns.push(Turn {
+            role: TurnRole::Assistant,
+            content,
+            attachments: Vec::new(),
+            timestamp,
+            message_id: None,
+        });
+    }
+    turns
+}
+
+/// 在回答前加上之前的对话作为上下文，用于渲染多轮会话
+///
+/// `turns` 为本次回答之前的消息，只保留最后 `max_turns` 条，每条截断后以引用块显示，
+/// 与本次回答之间用分隔线隔开。没有之前的消息时原样返回回答。
+pub fn markdown_with_context(turns: &[Turn], markdown: &str, max_turns: usize) -> String {
+    let earlier = &turns[turns.len().saturating_sub(max_turns)..];
+    if earlier.is_empty() {
+        return markdown.to_string();
+    }
+    let mut result = String::new();
+    for turn in earlier {
+        let label = match turn.role {
+            TurnRole::User => "🙋 问",
+            TurnRole::Assistant => "🤖 答",
+        };
+        let content: String = turn
+            .content
+            .trim()
+            .chars()
+            .take(CONTEXT_TURN_MAX_CHARS)
+            .collect();
+        let ellipsis = if turn.content.trim().chars().count() > CONTEXT_TURN_MAX_CHARS {
+            "…"
+        } else {
+            ""
+        };
+        result.push_str(&format!("> **{}：**\n", label));
+        for line in format!("{}{}", content, ellipsis).lines() {
+            result.push_str(&format!("> {}\n", line));
+        }
+        result.push('\n');
+    }
+    result.push_str("---\n\n");
+    result.push_str(markdown);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn appends_after_legacy_turns() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        fs::write(dir.join("input.txt"), "第一个问题").unwrap();
+        fs::write(dir.join("image_urls.txt"), "https://example.com/a.png\n").unwrap();
+        fs::write(dir.join("response.md"), "第一个回答").unwrap();
+        assert_eq!(load_turns(dir).unwrap().len(), 2);
+
+        let count = append_turn(dir, &Turn::user("追问", &[], Some("1".into()))).unwrap();
+        assert_eq!(count, 3);
+        let count = append_turn(dir, &Turn::assistant("追问的回答")).unwrap();
+        assert_eq!(count, 4);
+
+        // 模拟写入中途退出留下的不完整行
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(dir.join(TRANSCRIPT_FILE))
+            .unwrap();
+        file.write_all(br#"{"role":"user","con"#).unwrap();
+        let count = append_turn(dir, &Turn::user("第三个问题", &[], None)).unwrap();
+        assert_eq!(count, 5);
+
+        let turns = load_turns(dir).unwrap();
+        let roles: Vec<TurnRole> = turns.iter().map(|t| t.role).collect();
+        assert_eq!(
+            roles,
+            [
+                TurnRole::User,
+                TurnRole::Assistant,
+                TurnRole::User,
+                TurnRole::Assistant,
+                TurnRole::User
+            ]
+        );
+        assert_eq!(turns[0].attachments, ["https://example.com/a.png"]);
+        assert_eq!(turns[2].message_id.as_deref(), Some("1"));
+        assert_eq!(turns[3].content, "追问的回答");
+    }
+
+    #[test]
+    fn renders_earlier_turns_as_quotes() {
+        let turns = vec![
+            Turn::user("旧问题", &[], None),
+            Turn::assistant("第一行\n第二行"),
+            Turn::user("新问题", &[], None),
+        ];
+        assert_eq!(markdown_with_context(&[], "回答", 4), "回答");
+        assert_eq!(
+            markdown_with_context(&turns, "回答", 2),
+            "> **🤖 答：**\n> 第一行\n> 第二行\n\n> **🙋 问：**\n> 新问题\n\n---\n\n回答"
+        );
+    }
+}
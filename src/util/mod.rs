@@ -0,0 +1,9 @@
+/// 以合适的单位显示字节数
+pub fn format_bytes(bytes: u64) -> String {
+    const MB: f64 = 1024.0 * 1024.0;
+    if bytes as f64 >= MB {
+        format!("{:.2} MB", bytes as f64 / MB)
+    } else {
+        format!("{:.2} KB", bytes as f64 / 1024.0)
+    }
+}
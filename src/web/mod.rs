@@ -0,0 +1,177 @@
+mod page;
+
+use anyhow::{Context, Result};
+use hyper::header::{self, HeaderValue};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tracing::{error, info, warn};
+
+use crate::config::Config;
+use crate::session::SessionManager;
+
+/// 分享页面的路径前缀
+const SHARE_PATH: &str = "/s/";
+
+// 同时生成的分享页面数上限，超出时直接返回 503，避免大量请求占满阻塞线程池
+const MAX_CONCURRENT_PAGES: usize = 8;
+
+/// 会话的网页分享地址，未启用分享网页服务器或无法确定对外地址时返回 `None`
+pub fn share_url(config: &Config, token: &str) -> Option<String> {
+    share_base_url(config).map(|base_url| format!("{}{}{}", base_url, SHARE_PATH, token))
+}
+
+/// 分享链接的对外地址前缀
+///
+/// 未设置 `SHARE_BASE_URL` 时使用监听地址；监听 0.0.0.0 等未指定地址时无法得知对外地址，返回 `None`。
+pub fn share_base_url(config: &Config) -> Option<String> {
+    base_url(config.share_web_addr?, config.share_base_url.as_deref())
+}
+
+fn base_url(addr: SocketAddr, configured: Option<&str>) -> Option<String> {
+    match configured {
+        Some(base_url) => Some(base_url.to_string()),
+        None if addr.ip().is_unspecified() => None,
+        None => Some(format!("http://{}", addr)),
+    }
+}
+
+/// 启动只读的分享网页服务器，在后台运行
+///
+/// 只提供 `/s/{令牌}` 页面，每次请求都重新检查令牌，撤销后立即失效。
+pub fn start_share_server(addr: SocketAddr, session_manager: SessionManager) -> Result<()> {
+    let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_PAGES));
+    let make_service = make_service_fn(move |_conn| {
+        let session_manager = session_manager.clone();
+        let permits = Arc::clone(&permits);
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let session_manager = session_manager.clone();
+                let permits = Arc::clone(&permits);
+                async move { Ok::<_, Infallible>(handle(request, session_manager, &permits).await) }
+            }))
+        }
+    });
+    let server = Server::try_bind(&addr)
+        .with_context(|| format!("监听分享网页地址失败: {}", addr))?
+        .serve(make_service);
+    info!("分享网页服务器已启动: http://{}", addr);
+    tokio::spawn(async move {
+        if let Err(e) = server.await {
+            error!("分享网页服务器异常退出: {}", e);
+        }
+    });
+    Ok(())
+}
+
+async fn handle(
+    request: Request<Body>,
+    session_manager: SessionManager,
+    permits: &Semaphore,
+) -> Response<Body> {
+    if request.method() != Method::GET && request.method() != Method::HEAD {
+        return html_response(StatusCode::METHOD_NOT_ALLOWED, page::not_found_page());
+    }
+    let Some(token) = share_token(request.uri().path()) else {
+        return html_response(StatusCode::NOT_FOUND, page::not_found_page());
+    };
+    let Ok(_permit) = permits.try_acquire() else {
+        let mut response = html_response(StatusCode::SERVICE_UNAVAILABLE, page::busy_page());
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from_static("5"));
+        return response;
+    };
+    let token = token.to_string();
+    let result = tokio::task::spawn_blocking(move || shared_page(&session_manager, &token)).await;
+    match result {
+        Ok(Ok(Some(html))) => html_response(StatusCode::OK, html),
+        Ok(Ok(None)) => html_response(StatusCode::NOT_FOUND, page::not_found_page()),
+        Ok(Err(e)) => {
+            warn!("生成分享页面失败: {:?}", e);
+            html_response(StatusCode::INTERNAL_SERVER_ERROR, page::not_found_page())
+        }
+        Err(e) => {
+            warn!("生成分享页面任务失败: {:?}", e);
+            html_response(StatusCode::INTERNAL_SERVER_ERROR, page::not_found_page())
+        }
+    }
+}
+
+// 查找令牌对应的会话并生成页面，令牌无效或已撤销时返回 None
+//
+// 查找与读取会话都是阻塞的文件操作，在阻塞线程中整体执行。
+fn shared_page(session_manager: &SessionManager, token: &str) -> Result<Option<String>> {
+    Ok(session_manager
+        .load_shared_session(token)?
+        .map(|(meta, turns)| page::share_page(&turns, &meta)))
+}
+
+// 从请求路径中取出分享令牌
+fn share_token(path: &str) -> Option<&str> {
+    path.strip_prefix(SHARE_PATH)
+        .map(|token| token.trim_end_matches('/'))
+        .filter(|token| !token.is_empty() && !token.contains('/'))
+}
+
+fn html_response(status: StatusCode, html: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(html));
+    *response.status_mut() = status;
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+    // 撤销后不应再从缓存中读到页面
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    // 页面只使用内联样式，不加载任何脚本与外部资源
+    headers.insert(
+        header::CONTENT_SECURITY_POLICY,
+        HeaderValue::from_static("default-src 'none'; style-src 'unsafe-inline'; img-src data:"),
+    );
+    headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    headers.insert(
+        header::REFERRER_POLICY,
+        HeaderValue::from_static("no-referrer"),
+    );
+    headers.insert("x-robots-tag", HeaderValue::from_static("noindex"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base_url_requires_public_address() {
+        let local: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let any: SocketAddr = "0.0.0.0:8080".parse().unwrap();
+        let any_v6: SocketAddr = "[::]:8080".parse().unwrap();
+        assert_eq!(
+            base_url(local, None).as_deref(),
+            Some("http://127.0.0.1:8080")
+        );
+        assert_eq!(base_url(any, None), None);
+        assert_eq!(base_url(any_v6, None), None);
+        assert_eq!(
+            base_url(any, Some("https://qa.example.com")).as_deref(),
+            Some("https://qa.example.com")
+        );
+    }
+
+    #[test]
+    fn extracts_share_token_from_path() {
+        assert_eq!(share_token("/s/abc"), Some("abc"));
+        assert_eq!(share_token("/s/abc/"), Some("abc"));
+        assert_eq!(share_token("/s/"), None);
+        assert_eq!(share_token("/s/abc/def"), None);
+        assert_eq!(share_token("/"), None);
+        assert_eq!(share_token("/favicon.ico"), None);
+    }
+}
@@ -0,0 +1,154 @@
+use chrono::Local;
+
+use crate::image::markdown_to_html_fragment;
+use crate::session::{SessionMeta, Turn, TurnRole};
+
+// 页面标题中问题的最大字符数
+const TITLE_MAX_CHARS: usize = 60;
+
+const STYLE: &str = r#"
+    body {
+        margin: 0;
+        padding: 24px 16px;
+        background-color: #2b2b2b;
+        color: #f0f0f0;
+        font-family: -apple-system, "Segoe UI", "PingFang SC", "Microsoft YaHei", "Noto Sans CJK SC", sans-serif;
+        font-size: 16px;
+        line-height: 1.7;
+    }
+    main { max-width: 860px; margin: 0 auto; }
+    .question {
+        padding: 14px 18px;
+        margin: 24px 0 16px;
+        background-color: #353535;
+        border-radius: 8px;
+        border-left: 4px solid #78a9ff;
+        white-space: pre-wrap;
+        word-wrap: break-word;
+        font-weight: 600;
+    }
+    .answer { overflow-wrap: break-word; }
+    pre {
+        background-color: #383838;
+        padding: 16px;
+        border-radius: 8px;
+        overflow-x: auto;
+        border-left: 3px solid #666666;
+    }
+    code { font-family: Consolas, "Source Code Pro", Menlo, monospace; font-size: 0.9em; }
+    :not(pre) > code { background-color: #3a3a3a; padding: 2px 5px; border-radius: 4px; }
+    a { color: #78a9ff; }
+    blockquote { margin: 0; padding-left: 16px; border-left: 4px solid #666666; color: #cccccc; }
+    table { border-collapse: collapse; display: block; overflow-x: auto; }
+    th, td { border: 1px solid #555555; padding: 6px 12px; }
+    th { background-color: #353535; }
+    .meta, footer { color: #999999; font-size: 0.85em; }
+    footer { margin-top: 40px; padding-top: 12px; border-top: 1px solid #555555; }
+"#;
+
+/// 生成会话的只读分享页面
+///
+/// 问题按纯文本转义显示，回答与渲染图片时一样经过过滤；不包含提问者与会话ID等信息。
+pub(super) fn share_page(turns: &[Turn], meta: &SessionMeta) -> String {
+    let first_question = turns
+        .iter()
+        .find(|turn| turn.role == TurnRole::User)
+        .map(|turn| turn.content.trim())
+        .unwrap_or_default();
+    let mut title: String = first_question.chars().take(TITLE_MAX_CHARS).collect();
+    if first_question.chars().count() > TITLE_MAX_CHARS {
+        title.push('…');
+    }
+    if title.is_empty() {
+        title = "分享的问答".to_string();
+    }
+
+    let mut body = String::new();
+    for turn in turns {
+        match turn.role {
+            TurnRole::User => body.push_str(&format!(
+                "<div class=\"question\">🙋 {}</div>\n",
+                html_escape::encode_text(turn.content.trim())
+            )),
+            TurnRole::Assistant => body.push_str(&format!(
+                "<div class=\"answer\">{}</div>\n",
+                markdown_to_html_fragment(&turn.content)
+            )),
+        }
+    }
+
+    let mut details = vec![format!(
+        "提问于 {}",
+        meta.created_at
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M")
+    )];
+    if let Some(app_name) = &meta.app_name {
+        details.push(html_escape::encode_text(app_name).to_string());
+    }
+
+    layout(
+        &title,
+        &format!(
+            "<div class=\"meta\">{}</div>\n{}<footer>此页面由会话所有者公开分享，所有者撤销后将无法访问</footer>",
+            details.join(" · "),
+            body
+        ),
+    )
+}
+
+/// 链接不存在或已撤销时显示的页面
+pub(super) fn not_found_page() -> String {
+    layout(
+        "分享不存在",
+        "<h2>分享不存在</h2><p class=\"meta\">链接有误，或该分享已被撤销。</p>",
+    )
+}
+
+/// 同时访问的人数过多时显示的页面
+pub(super) fn busy_page() -> String {
+    layout(
+        "请稍后再试",
+        "<h2>请稍后再试</h2><p class=\"meta\">当前访问的人数较多，请稍后刷新页面。</p>",
+    )
+}
+
+fn layout(title: &str, content: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"zh-CN\">\n<head>\n<meta charset=\"UTF-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <meta name=\"robots\" content=\"noindex\">\n<title>{}</title>\n<style>{}</style>\n</head>\n\
+         <body>\n<main>\n{}\n</main>\n</body>\n</html>\n",
+        html_escape::encode_text(title),
+        STYLE,
+        content
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_questions_and_sanitizes_answers() {
+        let turns = vec![
+            Turn::user("<script>alert(1)</script> 怎么办？", &[], None),
+            Turn::assistant("**加粗** <img src=x onerror=alert(1)> [链接](javascript:alert(1))"),
+            Turn::user("追问", &[], None),
+            Turn::assistant("```rust\nfn main() {}\n```"),
+        ];
+        let mut meta = SessionMeta::new("user-7788");
+        meta.app_name = Some("答疑<应用>".into());
+        let html = share_page(&turns, &meta);
+
+        assert!(html.contains("<title>&lt;script&gt;alert(1)&lt;/script&gt; 怎么办？</title>"));
+        assert!(!html.contains("<script>"));
+        assert!(!html.contains("<img"));
+        assert!(!html.contains("javascript:"));
+        assert!(html.contains("<strong>加粗</strong>"));
+        assert!(html.contains("答疑&lt;应用&gt;"));
+        assert!(!html.contains("user-7788"));
+        assert_eq!(html.matches("class=\"question\"").count(), 2);
+        assert!(html.contains("<code class=\"language-rust\">"));
+    }
+}